@@ -0,0 +1,11 @@
+#![no_main]
+
+use chinese_bitmap_font_demo::script::get_unique_chinese_chars;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|game_script: &str| {
+  let chars = get_unique_chinese_chars(game_script);
+  // Strictly increasing means both sorted and free of duplicates.
+  assert!(chars.windows(2).all(|pair| pair[0] < pair[1]));
+  assert!(chars.iter().all(|c| game_script.contains(*c)));
+});
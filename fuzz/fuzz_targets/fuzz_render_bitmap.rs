@@ -0,0 +1,29 @@
+#![no_main]
+
+use chinese_bitmap_font_demo::builder::AtlasBuilder;
+use libfuzzer_sys::fuzz_target;
+
+/// Synthetic pixel font shared with the integration tests.
+const TEST_FONT: &[u8] = include_bytes!("../../tests/fixtures/test_font.ttf");
+/// Keep each run fast; placement bugs show up with a handful of characters.
+const MAX_CHARS: usize = 256;
+
+fuzz_target!(|data: (bool, u8, &str)| {
+  let (is_11px, chars_per_line, text) = data;
+  let mut chars = text.chars().take(MAX_CHARS).collect::<Vec<_>>();
+  chars.sort_unstable();
+  chars.dedup();
+  if chars.is_empty() {
+    return;
+  }
+  let generator = AtlasBuilder::new()
+    .font_bytes(TEST_FONT.to_vec())
+    .size(if is_11px { 11 } else { 10 })
+    .chars_per_line(chars_per_line.max(1) as usize)
+    .build()
+    .unwrap();
+  // Errors (e.g. atlas too large) are fine, panics in the pixel placement are not.
+  if let Ok(atlas) = generator.generate(&chars) {
+    assert_eq!(atlas.placements.len(), chars.len());
+  }
+});
@@ -0,0 +1,1111 @@
+use std::{cmp::Reverse, collections::BTreeMap};
+
+use ab_glyph::{Font, PxScale, ScaleFont};
+use anyhow::{Result, bail};
+use clap::ValueEnum;
+use image::{Rgb, RgbImage, RgbaImage, imageops};
+use serde::Serialize;
+
+use crate::{
+  block_colors::BlockColors,
+  error::{Failure, FailureKind},
+  pack::{PackMode, shelf_pack},
+  placement::{clamp_origin, glyph_origin},
+  punctuation::{CHINESE_PUNCTUATION_MARKS, get_chinese_punctuation_offset, vertical_punctuation},
+  render::{
+    CellBuffer, CellColors, Coverage, GlyphStyle, RenderStyle, Rotation, ShadowDirection,
+    ShadowStyle, blend, render_cell,
+  },
+  script::IDEOGRAPHIC_SPACE,
+  warning::{RenderContext, RenderWarning},
+};
+
+/// Width and height (px) of an atlas holding `char_count` cells of `cell_size` px, checked
+/// against the configured maximum dimensions.
+pub fn atlas_dimensions(
+  char_count: usize,
+  chars_per_line: usize,
+  cell_size: u32,
+  max_width: u32,
+  max_height: u32,
+) -> Result<(u32, u32)> {
+  if chars_per_line == 0 {
+    bail!("[Error] chars_per_line must be greater than 0!");
+  }
+  let rows = char_count.div_ceil(chars_per_line);
+  let width = chars_per_line as u64 * cell_size as u64;
+  let height = rows as u64 * cell_size as u64;
+  if width > max_width as u64 || height > max_height as u64 {
+    return Err(overflow(format!(
+      "[Error] Atlas would be {}x{} px ({} characters, {} per line, {} rows), exceeding the \
+       {}x{} limit! Adjust chars_per_line, or split the script into several atlases.",
+      width, height, char_count, chars_per_line, rows, max_width, max_height
+    )));
+  }
+  Ok((width as u32, height as u32))
+}
+
+/// The `chars_per_line` whose grid holds `char_count` cells in the smallest area among the
+/// grids with at most one more column than rows or row than columns, the most square (then the
+/// widest) one among equals. Without such a grid within the maximum dimensions, the most square
+/// one that fits wins. `None` when no width fits the maximum dimensions.
+pub fn optimal_chars_per_line(
+  char_count: usize,
+  cell_size: u32,
+  max_width: u32,
+  max_height: u32,
+) -> Option<usize> {
+  (1..=char_count.max(1))
+    .filter_map(|chars_per_line| {
+      let (width, height) =
+        atlas_dimensions(char_count, chars_per_line, cell_size, max_width, max_height).ok()?;
+      let rows = char_count.div_ceil(chars_per_line);
+      // A prime count fills a single row exactly; only near-square grids compete on area.
+      let elongation = chars_per_line.abs_diff(rows).saturating_sub(1);
+      let area = width as u64 * height as u64;
+      Some((
+        elongation,
+        area,
+        width.abs_diff(height),
+        Reverse(chars_per_line),
+      ))
+    })
+    .min()
+    .map(|(_, _, _, Reverse(chars_per_line))| chars_per_line)
+}
+
+fn overflow(message: String) -> anyhow::Error {
+  Failure::new(FailureKind::Overflow, message).into()
+}
+
+/// Number of tiles to lay out for `char_count` characters, after optional blank padding.
+pub fn padded_tile_count(
+  char_count: usize,
+  chars_per_line: usize,
+  pad_to_full_rows: bool,
+  pad_to_count: Option<usize>,
+) -> Result<usize> {
+  if let Some(count) = pad_to_count {
+    if count < char_count {
+      return Err(overflow(format!(
+        "[Error] Cannot pad to {} tiles, there are already {} characters!",
+        count, char_count
+      )));
+    }
+    Ok(count)
+  } else if pad_to_full_rows {
+    Ok(char_count.div_ceil(chars_per_line) * chars_per_line)
+  } else {
+    Ok(char_count)
+  }
+}
+
+/// Number of cell columns and rows, counted from the top-left, that contain at least one
+/// non-background pixel.
+pub fn used_cell_extent(image: &RgbImage, bg_color: Rgb<u8>, cell_size: u32) -> (u32, u32) {
+  let (mut cols, mut rows) = (0, 0);
+  for (x, y, pixel) in image.enumerate_pixels() {
+    if *pixel != bg_color {
+      cols = cols.max(x / cell_size + 1);
+      rows = rows.max(y / cell_size + 1);
+    }
+  }
+  (cols, rows)
+}
+
+/// Crop trailing all-background cell rows and columns from the atlas.
+pub fn strip_unused_cells(image: &RgbImage, bg_color: Rgb<u8>, cell_size: u32) -> RgbImage {
+  let (cols, rows) = used_cell_extent(image, bg_color, cell_size);
+  let width = (cols * cell_size).min(image.width());
+  let height = (rows * cell_size).min(image.height());
+  imageops::crop_imm(image, 0, 0, width, height).to_image()
+}
+
+/// Points per pixel at 96 DPI (screen standard): 1px = 0.75pt.
+pub const PT_PER_PX: f32 = 0.75;
+
+/// Scale for a font size given in points: 6.75 pt * 2 = 13.5.
+pub fn scale_from_pt(font_size_pt: f32) -> PxScale {
+  PxScale::from(font_size_pt * 2.0)
+}
+
+/// 6.75 pt = 9 px, 6.75 * 2 = 13.5. Every font in a fallback chain uses the same scale, so
+/// glyphs have the same size regardless of which font supplied them.
+pub fn glyph_scale(char_size: f32) -> PxScale {
+  scale_from_pt(char_size * PT_PER_PX)
+}
+
+/// Character measured by [`calibrate_scale`].
+pub const CALIBRATION_CHAR: char = '中';
+
+/// Scales tried by [`calibrate_scale`]: 8.0 to 20.0 in 0.5 steps.
+const CALIBRATION_SCALES: std::ops::RangeInclusive<u32> = 16..=40;
+
+/// Width and height (px) of the inked pixels of `c` rendered at `scale`, `None` when nothing
+/// is inked.
+pub fn inked_size<F: Font>(
+  font: &F,
+  c: char,
+  scale: PxScale,
+  coverage: Coverage,
+) -> Option<(u32, u32)> {
+  let outlined = font.outline_glyph(font.glyph_id(c).with_scale(scale))?;
+  let (mut min, mut max) = ((u32::MAX, u32::MAX), (0, 0));
+  outlined.draw(|x, y, v| {
+    if coverage.is_inked(v) {
+      min = (min.0.min(x), min.1.min(y));
+      max = (max.0.max(x), max.1.max(y));
+    }
+  });
+  (min.0 <= max.0).then(|| (max.0 - min.0 + 1, max.1 - min.1 + 1))
+}
+
+/// Vertical metrics of a font at a scale, and the size of its [`CALIBRATION_CHAR`], the
+/// numbers the glyph positioning is based on.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FontMetrics {
+  /// Scaled ascent (px) above the baseline.
+  pub ascender: f32,
+  /// Scaled descent (px), negative below the baseline.
+  pub descender: f32,
+  pub line_gap: f32,
+  /// Design units per em, 0 when the font does not say.
+  pub units_per_em: u16,
+  /// Outline bounds (px) of [`CALIBRATION_CHAR`], 0 when the font lacks it.
+  pub cjk_char_width: f32,
+  pub cjk_char_height: f32,
+}
+
+/// Metrics of `font` rendered at `scale`.
+pub fn measure_font<F: Font>(font: &F, scale: PxScale) -> FontMetrics {
+  let scaled_font = font.as_scaled(scale);
+  let glyph_id = font.glyph_id(CALIBRATION_CHAR);
+  let bounds = (glyph_id.0 != 0)
+    .then(|| font.outline_glyph(glyph_id.with_scale(scale)))
+    .flatten()
+    .map(|outlined| outlined.px_bounds());
+  FontMetrics {
+    ascender: scaled_font.ascent(),
+    descender: scaled_font.descent(),
+    line_gap: scaled_font.line_gap(),
+    units_per_em: font.units_per_em().map_or(0, |units| units as u16),
+    cjk_char_width: bounds.map_or(0.0, |bounds| bounds.width()),
+    cjk_char_height: bounds.map_or(0.0, |bounds| bounds.height()),
+  }
+}
+
+/// Inked size of [`CALIBRATION_CHAR`] at every calibration scale, skipping blank renders.
+pub fn calibrate_scale<F: Font>(font: &F, coverage: Coverage) -> Vec<(f32, (u32, u32))> {
+  CALIBRATION_SCALES
+    .filter_map(|step| {
+      let scale = step as f32 / 2.0;
+      inked_size(font, CALIBRATION_CHAR, PxScale::from(scale), coverage).map(|size| (scale, size))
+    })
+    .collect()
+}
+
+/// The calibrated scale whose inked size is closest to `target`×`target` px; the smaller scale
+/// wins ties, so the glyph never grows past a size that already fits.
+pub fn closest_scale(samples: &[(f32, (u32, u32))], target: u32) -> Option<f32> {
+  samples
+    .iter()
+    .min_by_key(|(_, (width, height))| width.abs_diff(target) + height.abs_diff(target))
+    .map(|(scale, _)| *scale)
+}
+
+/// Index of the first font in the fallback chain that has a glyph for each character.
+pub fn resolve_fonts<F: Font>(fonts: &[F], chars: &[char]) -> Vec<Option<usize>> {
+  chars
+    .iter()
+    .map(|c| fonts.iter().position(|font| font.glyph_id(*c).0 != 0))
+    .collect()
+}
+
+/// Background drawn behind each cell of a grid atlas.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum CharBackgroundMode {
+  /// Every cell uses the image background color.
+  #[default]
+  None,
+  /// Odd cells use the alternate background color, to check cell alignment.
+  #[value(alias = "alternate")]
+  Checkerboard,
+}
+
+/// Top-left corner of every tile of a grid atlas, and the atlas height.
+///
+/// With a `group_spacing` above 0, a character from a different Unicode block than the previous
+/// one (approximated as `c / 256`) starts a new row, `group_spacing` px below the previous row.
+/// Padding tiles after the last character continue its row.
+pub fn grid_layout(
+  chars: &[char],
+  tile_count: usize,
+  chars_per_line: usize,
+  cell_size: u32,
+  group_spacing: u32,
+) -> (Vec<(u32, u32)>, u32) {
+  let mut positions = Vec::with_capacity(tile_count);
+  let (mut column, mut y) = (0, 0);
+  for j in 0..tile_count {
+    if j > 0 {
+      let new_group = group_spacing > 0
+        && match (chars.get(j - 1), chars.get(j)) {
+          (Some(prev), Some(c)) => *prev as u32 / 256 != *c as u32 / 256,
+          _ => false,
+        };
+      if new_group {
+        y += cell_size + group_spacing;
+        column = 0;
+      } else if column == chars_per_line {
+        y += cell_size;
+        column = 0;
+      }
+    }
+    positions.push((column as u32 * cell_size, y));
+    column += 1;
+  }
+  let height = if tile_count == 0 { 0 } else { y + cell_size };
+  (positions, height)
+}
+
+/// Fill every odd cell (by column + row) of a grid atlas with `alt_bg_color`.
+pub fn fill_checkerboard(image: &mut RgbImage, cell_size: u32, alt_bg_color: Rgb<u8>) {
+  for (x, y, pixel) in image.enumerate_pixels_mut() {
+    if (x / cell_size + y / cell_size) % 2 == 1 {
+      *pixel = alt_bg_color;
+    }
+  }
+}
+
+/// Tile `pattern` across the whole image, the atlas pixel (x, y) taking the pattern pixel
+/// (x % width, y % height).
+pub fn fill_tiled(image: &mut RgbImage, pattern: &RgbImage) {
+  for (x, y, pixel) in image.enumerate_pixels_mut() {
+    *pixel = *pattern.get_pixel(x % pattern.width(), y % pattern.height());
+  }
+}
+
+/// Opaque copy of `image`, transparent pixels blended over white.
+pub fn flatten_on_white(image: &RgbaImage) -> RgbImage {
+  RgbImage::from_fn(image.width(), image.height(), |x, y| {
+    let [r, g, b, a] = image.get_pixel(x, y).0;
+    blend(Rgb([r, g, b]), Rgb([255, 255, 255]), a)
+  })
+}
+
+/// Copy of a finished grid atlas (or page) whose odd cells have their `bg_color` pixels replaced
+/// by `alt_bg_color`, for `--debug-checker`. Glyph pixels are kept.
+pub fn debug_checker(
+  image: &RgbImage,
+  cell_size: u32,
+  bg_color: Rgb<u8>,
+  alt_bg_color: Rgb<u8>,
+) -> RgbImage {
+  let mut checker = image.clone();
+  for (x, y, pixel) in checker.enumerate_pixels_mut() {
+    if (x / cell_size + y / cell_size) % 2 == 1 && *pixel == bg_color {
+      *pixel = alt_bg_color;
+    }
+  }
+  checker
+}
+
+/// Draw a 1px grid line on the top and left inner edge of every cell, plus the atlas' right and
+/// bottom edge, so neighboring cells share a single line.
+pub fn draw_cell_borders(image: &mut RgbImage, cell_size: u32, color: Rgb<u8>) {
+  let (width, height) = image.dimensions();
+  for (x, y, pixel) in image.enumerate_pixels_mut() {
+    if x % cell_size == 0 || y % cell_size == 0 || x == width - 1 || y == height - 1 {
+      *pixel = color;
+    }
+  }
+}
+
+/// Everything [`render_atlas`] needs besides the fonts and characters.
+#[derive(Clone)]
+pub struct RenderOptions {
+  /// Cell size (px), 10 or 11.
+  pub font_size: u32,
+  pub shadow: ShadowStyle,
+  /// Direction of the [`ShadowStyle::Drop`] shadow.
+  pub shadow_direction: ShadowDirection,
+  /// Single shadow offset (dx, dy) replacing the style's shadow pixels.
+  pub shadow_offset: Option<(i32, i32)>,
+  /// Shadow opacity, blended over the atlas pixels below the shadow when blitting.
+  pub shadow_opacity: u8,
+  /// Shadow spread (px): the shadow pixels dilated this many times, 1 for the plain shadow.
+  pub shadow_width: u32,
+  pub style: RenderStyle,
+  pub rotation: Rotation,
+  /// Clockwise rotation of every finished cell, shadow included, for engines storing their
+  /// font texture rotated. The grid layout is unchanged.
+  pub tile_rotation: Rotation,
+  pub coverage: Coverage,
+  /// Coverage thresholds replacing `coverage.threshold` for single characters.
+  pub char_thresholds: BTreeMap<char, f32>,
+  /// Character drawn in the tile of a character no font has; `missing_fallbacks` wins.
+  pub missing_fallback: Option<char>,
+  /// Per-character replacements of `missing_fallback`.
+  pub missing_fallbacks: BTreeMap<char, char>,
+  /// Font size in points, overriding the size derived from the glyph area.
+  pub font_size_pt: Option<f32>,
+  /// Use the zh-hant punctuation offsets.
+  pub is_zh_hant: bool,
+  /// Configured glyph positions replacing the built-in punctuation offsets.
+  pub punctuation_offsets: BTreeMap<char, (u32, u32)>,
+  /// Signed vertical shift (px) of every glyph, negative moves up. Pixels pushed past the glyph
+  /// area stay on its edge.
+  pub baseline_offset: i32,
+  pub chars_per_line: usize,
+  /// Cell rows per page; the size limits apply to each page instead of the whole atlas.
+  pub max_rows: Option<usize>,
+  /// Number of tiles to lay out, at least the number of characters.
+  pub tile_count: usize,
+  pub max_image_width: u32,
+  pub max_image_height: u32,
+  pub bg_color: Rgb<u8>,
+  pub background_mode: CharBackgroundMode,
+  /// Background of the odd cells in [`CharBackgroundMode::Checkerboard`] mode.
+  pub alt_bg_color: Rgb<u8>,
+  /// Image tiled across the atlas instead of the flat `bg_color`.
+  pub background_image: Option<RgbImage>,
+  /// Extra rows of background (px) between characters of different Unicode blocks, 0 for none.
+  pub group_spacing: u32,
+  /// Grid line drawn under the glyphs of a grid atlas.
+  pub cell_border_color: Option<Rgb<u8>>,
+  pub colors: CellColors,
+  /// Fake bold: draw every glyph mask twice, 1px apart horizontally, before the shadow pass.
+  pub bold: bool,
+  /// Append a tile with the vertical presentation form of every punctuation mark, see
+  /// [`vertical_punctuation`].
+  pub vertical_punctuation: bool,
+  /// Fake italic: shear every glyph mask right by up to this many px (0-2) at the top, before
+  /// the shadow pass.
+  pub slant: u32,
+  /// Character colors of the regular tiles by Unicode block, replacing `colors.char_color`.
+  pub block_colors: Option<BlockColors>,
+  /// Report glyphs that render pixels in this color.
+  pub color_key: Option<Rgb<u8>>,
+  pub pack: PackMode,
+}
+
+impl RenderOptions {
+  /// Rasterizer scale of every glyph: `font_size_pt`, or the one fitting the glyph area.
+  pub fn scale(&self) -> PxScale {
+    self
+      .font_size_pt
+      .map(scale_from_pt)
+      .unwrap_or_else(|| glyph_scale(self.shadow.glyph_area_size(self.font_size) as f32))
+  }
+}
+
+/// Cell-local pixel rectangle.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct PixelRect {
+  pub x: u32,
+  pub y: u32,
+  pub width: u32,
+  pub height: u32,
+}
+
+/// Mirroring applied to the finished atlas, for pipelines whose texture origin is not the
+/// top-left corner.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Flip {
+  pub horizontal: bool,
+  pub vertical: bool,
+}
+
+impl Flip {
+  pub fn is_identity(self) -> bool {
+    !self.horizontal && !self.vertical
+  }
+
+  pub fn apply(self, image: &mut RgbImage) {
+    if self.horizontal {
+      imageops::flip_horizontal_in_place(image);
+    }
+    if self.vertical {
+      imageops::flip_vertical_in_place(image);
+    }
+  }
+
+  /// Where `rect` ends up once a `width × height` area is mirrored.
+  pub fn rect(self, rect: PixelRect, width: u32, height: u32) -> PixelRect {
+    PixelRect {
+      x: if self.horizontal {
+        width - rect.x - rect.width
+      } else {
+        rect.x
+      },
+      y: if self.vertical {
+        height - rect.y - rect.height
+      } else {
+        rect.y
+      },
+      ..rect
+    }
+  }
+
+  /// Rewrite the placements so they describe the atlas after [`Flip::apply`]. Cell-local boxes
+  /// are mirrored within their cell, cells and packed rects within the image.
+  pub fn placements<'a>(
+    self,
+    placements: impl IntoIterator<Item = &'a mut GlyphPlacement>,
+    image_width: u32,
+    image_height: u32,
+    cell_size: u32,
+  ) {
+    let flip_in_cell = |rect: PixelRect| self.rect(rect, cell_size, cell_size);
+    for placement in placements {
+      placement.glyph_bbox = placement.glyph_bbox.map(flip_in_cell);
+      placement.ink_bbox = placement.ink_bbox.map(flip_in_cell);
+      if let Some(packed) = placement.packed {
+        placement.packed = Some(self.rect(packed, image_width, image_height));
+      } else {
+        let cell = self.rect(
+          PixelRect {
+            x: placement.cell_x,
+            y: placement.cell_y,
+            width: cell_size,
+            height: cell_size,
+          },
+          image_width,
+          image_height,
+        );
+        placement.cell_x = cell.x;
+        placement.cell_y = cell.y;
+      }
+    }
+  }
+}
+
+/// Characters rendered a second time, after the regular tiles, in a `[color_classes]` color.
+#[derive(Clone, Debug)]
+pub struct ColorClassTiles {
+  pub name: String,
+  pub colors: CellColors,
+  /// Draw the class glyphs double-struck, see [`RenderOptions::bold`].
+  pub bold: bool,
+  /// Slant of the class glyphs, see [`RenderOptions::slant`]; the larger of the two applies.
+  pub slant: u32,
+  pub chars: Vec<char>,
+}
+
+/// One tile to render: `char` drawn with the glyph of `glyph`.
+struct Tile<'a> {
+  char: char,
+  /// `char` itself, or the horizontal mark rotated in place of a missing vertical form.
+  glyph: char,
+  class: Option<&'a ColorClassTiles>,
+  vertical_of: Option<char>,
+}
+
+impl<'a> Tile<'a> {
+  fn plain(c: char, class: Option<&'a ColorClassTiles>) -> Self {
+    Tile {
+      char: c,
+      glyph: c,
+      class,
+      vertical_of: None,
+    }
+  }
+}
+
+/// Where and how a single character ended up in the atlas. All exporters are built on these.
+#[derive(Clone, Debug, Default)]
+pub struct GlyphPlacement {
+  pub char: char,
+  pub tile_index: usize,
+  /// Page of a `--max-rows` split atlas; `cell_y` is relative to that page.
+  pub page: usize,
+  /// Top-left corner of the cell in the grid layout (px).
+  pub cell_x: u32,
+  pub cell_y: u32,
+  /// Bounding box of the character pixels (shadow excluded) within the cell, `None` when
+  /// nothing was drawn.
+  pub glyph_bbox: Option<PixelRect>,
+  /// Bounding box of the character and shadow pixels within the cell.
+  pub ink_bbox: Option<PixelRect>,
+  /// Where the ink bounding box was packed in the atlas, in [`PackMode::Tight`] mode. Blank
+  /// glyphs get an empty rect.
+  pub packed: Option<PixelRect>,
+  /// Horizontal advance (px) reported by the font.
+  pub advance: f32,
+  /// No font in the fallback chain has a glyph for the character.
+  pub missing: bool,
+  /// The tile holds a different character's glyph.
+  pub substituted: bool,
+  /// The glyph had to be moved or cropped to fit the cell.
+  pub clipped: bool,
+  /// Font in the fallback chain that supplied the glyph.
+  pub font_index: Option<usize>,
+  /// Color class of a duplicated tile, `None` for the regular tiles.
+  pub class: Option<String>,
+  /// Horizontal punctuation mark of a `--vertical-punctuation` tile.
+  pub vertical_of: Option<char>,
+  /// Tile of the vertical form of this punctuation mark.
+  pub vertical_index: Option<usize>,
+  /// The tile holds the horizontal mark turned 90°, as no font has the vertical form.
+  pub rotated: bool,
+}
+
+impl GlyphPlacement {
+  /// The atlas area holding this character: its packed rect in tight mode, its cell otherwise.
+  pub fn atlas_rect(&self, cell_size: u32) -> PixelRect {
+    self.packed.unwrap_or(PixelRect {
+      x: self.cell_x,
+      y: self.cell_y,
+      width: cell_size,
+      height: cell_size,
+    })
+  }
+
+  /// Top-left corner of the character's cell in the atlas. In tight mode it lies left of and
+  /// above the packed rect, by the offset of the ink bounding box in the cell.
+  pub fn cell_origin(&self) -> (i64, i64) {
+    match (self.packed, self.ink_bbox) {
+      (Some(packed), Some(ink_bbox)) => (
+        packed.x as i64 - ink_bbox.x as i64,
+        packed.y as i64 - ink_bbox.y as i64,
+      ),
+      _ => (self.cell_x as i64, self.cell_y as i64),
+    }
+  }
+}
+
+/// Rendered atlas plus what happened to each character.
+pub struct Atlas {
+  pub image: RgbImage,
+  /// Number of tiles laid out, including blank padding.
+  pub tile_count: usize,
+  /// One record per character, in atlas order.
+  pub placements: Vec<GlyphPlacement>,
+  /// Characters whose negative side bearings were clamped to the cell edge.
+  pub clamped_chars: Vec<char>,
+  /// Mask pixels the coverage gamma turned on or off compared to gamma 1.0.
+  pub gamma_flipped_pixels: usize,
+  /// Characters rendered with a `char_thresholds` override.
+  pub threshold_overrides: Vec<char>,
+  /// Rendered cell of each placement, `None` when nothing was drawn.
+  pub cells: Vec<Option<CellBuffer>>,
+}
+
+/// Render `chars` into an atlas, one cell per character, laid out according to
+/// `options.pack`. The characters of each color class follow as extra tiles in the class colors.
+/// Non-fatal problems are collected in `context`.
+pub fn render_atlas<F: Font>(
+  fonts: &[F],
+  chars: &[char],
+  classes: &[ColorClassTiles],
+  options: &RenderOptions,
+  context: &mut RenderContext,
+) -> Result<Atlas> {
+  let font_size = options.font_size;
+  let char_size = options.shadow.glyph_area_size(font_size) as f32;
+  let custom_shadow = options.shadow_offset.map(|offset| [offset]);
+  let shadow_offsets = match &custom_shadow {
+    Some(offsets) => offsets.as_slice(),
+    None if options.shadow == ShadowStyle::Drop => options.shadow_direction.offsets(),
+    None => options.shadow.offsets(),
+  };
+  let vertical = if options.vertical_punctuation {
+    vertical_punctuation(chars)
+  } else {
+    Vec::new()
+  };
+  let forms = vertical.iter().map(|(_, form)| *form).collect::<Vec<_>>();
+  let form_fonts = resolve_fonts(fonts, &forms);
+  let tiles = chars
+    .iter()
+    .map(|c| Tile::plain(*c, None))
+    .chain(
+      vertical
+        .iter()
+        .zip(&form_fonts)
+        .map(|((mark, form), font_index)| Tile {
+          char: *form,
+          // Fonts without the vertical form get the horizontal mark turned a quarter.
+          glyph: if font_index.is_some() { *form } else { *mark },
+          class: None,
+          vertical_of: Some(*mark),
+        }),
+    )
+    .chain(classes.iter().flat_map(|class| {
+      class
+        .chars
+        .iter()
+        .map(move |c| Tile::plain(*c, Some(class)))
+    }))
+    .collect::<Vec<_>>();
+  let tile_chars = tiles.iter().map(|tile| tile.char).collect::<Vec<_>>();
+  let glyph_chars = tiles.iter().map(|tile| tile.glyph).collect::<Vec<_>>();
+  let (positions, grid_height) = grid_layout(
+    &tile_chars,
+    options.tile_count,
+    options.chars_per_line,
+    font_size,
+    options.group_spacing,
+  );
+  let grid_size = match options.pack {
+    PackMode::Grid => {
+      let page_tiles = options.max_rows.map_or(options.tile_count, |max_rows| {
+        options.tile_count.min(max_rows * options.chars_per_line)
+      });
+      let (width, _) = atlas_dimensions(
+        page_tiles,
+        options.chars_per_line,
+        font_size,
+        options.max_image_width,
+        options.max_image_height,
+      )?;
+      if options.group_spacing > 0 && grid_height > options.max_image_height {
+        return Err(overflow(format!(
+          "[Error] Atlas would be {}x{} px with group spacing, exceeding the {}x{} limit!",
+          width, grid_height, options.max_image_width, options.max_image_height
+        )));
+      }
+      Some((width, grid_height))
+    }
+    PackMode::Tight => None,
+  };
+  let scale = options.scale();
+  let font_indices = resolve_fonts(fonts, &glyph_chars);
+  let mut placements = Vec::with_capacity(tiles.len());
+  let mut cells = Vec::with_capacity(tiles.len());
+  let mut clamped_chars = Vec::new();
+  let mut gamma_flipped_pixels = 0;
+  let mut threshold_overrides = Vec::new();
+
+  for (j, tile) in tiles.iter().enumerate() {
+    // The ideographic space is always a blank tile, whatever the fonts contain.
+    let blank = tile.glyph == IDEOGRAPHIC_SPACE;
+    let missing = font_indices[j].is_none() && !blank;
+    // A missing character borrows the glyph of its configured fallback, if some font has it.
+    let substitute = options
+      .missing_fallbacks
+      .get(&tile.glyph)
+      .copied()
+      .or(options.missing_fallback)
+      .filter(|_| missing)
+      .and_then(|fallback| {
+        let font_index = fonts
+          .iter()
+          .position(|font| font.glyph_id(fallback).0 != 0)?;
+        Some((fallback, font_index))
+      });
+    let (glyph, font_index) = match substitute {
+      Some((fallback, font_index)) => (fallback, Some(font_index)),
+      None => (tile.glyph, font_indices[j].filter(|_| !blank)),
+    };
+    let (c, class) = (&glyph, tile.class);
+    let rotated = tile.glyph != tile.char;
+    let mut colors = class.map_or(options.colors, |class| class.colors);
+    if class.is_none()
+      && let Some(color) = options
+        .block_colors
+        .as_ref()
+        .and_then(|block_colors| block_colors.color_of(*c))
+    {
+      colors.char_color = color;
+    }
+    let mut placement = GlyphPlacement {
+      char: tile.char,
+      tile_index: j,
+      page: 0,
+      cell_x: positions[j].0,
+      cell_y: positions[j].1,
+      glyph_bbox: None,
+      ink_bbox: None,
+      packed: None,
+      advance: if blank { font_size as f32 } else { 0.0 },
+      missing,
+      substituted: substitute.is_some(),
+      clipped: false,
+      font_index,
+      class: class.map(|class| class.name.clone()),
+      vertical_of: tile.vertical_of,
+      vertical_index: None,
+      rotated,
+    };
+    let mut rendered = None;
+    if placement.missing {
+      context.warn(RenderWarning::GlyphNotFound {
+        char: tile.glyph,
+        codepoint: tile.glyph as u32,
+        index: j,
+      });
+    }
+    if let Some((fallback, _)) = substitute {
+      context.warn(RenderWarning::Substituted {
+        char: tile.glyph,
+        codepoint: tile.glyph as u32,
+        fallback,
+      });
+    }
+
+    if let Some(font_index) = font_index {
+      let font = &fonts[font_index];
+      let scaled_font = font.as_scaled(scale);
+      let glyph_id = font.glyph_id(*c);
+      placement.advance = scaled_font.h_advance(glyph_id);
+      let glyph = glyph_id.with_scale(scale);
+      if let Some(outlined_glyph) = scaled_font.outline_glyph(glyph) {
+        let bounds = outlined_glyph.px_bounds();
+        let glyph_dimension = bounds.width().max(bounds.height()).ceil() as u32;
+        if glyph_dimension > char_size as u32 {
+          context.warn(RenderWarning::GlyphOverflow {
+            char: *c,
+            codepoint: *c as u32,
+            cell_dimension: char_size as u32,
+            glyph_dimension,
+          });
+        }
+        let origin = if let Some((x, y)) = options.punctuation_offsets.get(c) {
+          (*x as i32, *y as i32)
+        } else if CHINESE_PUNCTUATION_MARKS.contains(c) {
+          let (h_side_bearing, v_side_bearing) =
+            get_chinese_punctuation_offset(*c, options.is_zh_hant);
+          (h_side_bearing as i32, v_side_bearing as i32)
+        } else {
+          glyph_origin(
+            bounds.width(),
+            bounds.height(),
+            scaled_font.h_side_bearing(glyph_id),
+            scaled_font.v_side_bearing(glyph_id),
+            char_size,
+          )
+        };
+        let (origin, clamped) = clamp_origin(origin);
+        if clamped {
+          clamped_chars.push(*c);
+        }
+        let origin = (origin.0, origin.1 + options.baseline_offset);
+        placement.clipped = clamped
+          || origin.1 < 0
+          || origin.0 + bounds.width().ceil() as i32 > char_size as i32
+          || origin.1 + bounds.height().ceil() as i32 > char_size as i32;
+
+        let coverage = match options.char_thresholds.get(c) {
+          Some(threshold) => {
+            threshold_overrides.push(*c);
+            Coverage {
+              threshold: *threshold,
+              ..options.coverage
+            }
+          }
+          None => options.coverage,
+        };
+        let style = GlyphStyle {
+          bold: options.bold || class.is_some_and(|class| class.bold),
+          slant: class.map_or(options.slant, |class| class.slant.max(options.slant)),
+          rotation: if rotated {
+            options.rotation.turned()
+          } else {
+            options.rotation
+          },
+        };
+        let rendered_cell = render_cell(
+          &outlined_glyph,
+          origin,
+          options,
+          shadow_offsets,
+          coverage,
+          style,
+        );
+        let cell = match options.tile_rotation {
+          Rotation::None => rendered_cell.cell,
+          rotation => rendered_cell.cell.rotated(rotation),
+        };
+        gamma_flipped_pixels += rendered_cell.gamma_flips;
+        if rendered_cell.bold_clipped {
+          placement.clipped = true;
+          context.warn(RenderWarning::BoldClipped {
+            char: *c,
+            codepoint: *c as u32,
+          });
+        }
+        if rendered_cell.shadow_clipped {
+          placement.clipped = true;
+          context.warn(RenderWarning::ShadowClipped {
+            char: *c,
+            codepoint: *c as u32,
+          });
+        }
+        if rendered_cell.slant_clipped {
+          placement.clipped = true;
+          context.warn(RenderWarning::SlantClipped {
+            char: *c,
+            codepoint: *c as u32,
+          });
+        }
+        if let (Some((dx, dy)), Some(bbox)) = (options.shadow_offset, cell.char_bbox()) {
+          let (x, y) = (bbox.x as i32 + dx, bbox.y as i32 + dy);
+          if x < 0
+            || y < 0
+            || x + bbox.width as i32 > font_size as i32
+            || y + bbox.height as i32 > font_size as i32
+          {
+            bail!(Failure::new(
+              FailureKind::InvalidArguments,
+              format!(
+                "[Error] The shadow of '{}' with offset ({}, {}) does not fit the {}px cell! \
+                 Use a smaller shadow_offset or font size.",
+                c, dx, dy, font_size
+              )
+            ));
+          }
+        }
+        if let Some(color_key) = options.color_key
+          && cell.colored_pixels(colors).any(|(x, y, color)| {
+            cell.over(x, y, color, options.bg_color, options.shadow_opacity) == color_key
+          })
+        {
+          context.warn(RenderWarning::ColorKeyCollision {
+            char: *c,
+            codepoint: *c as u32,
+          });
+        }
+        placement.glyph_bbox = cell.char_bbox();
+        placement.ink_bbox = cell.ink_bbox();
+        rendered = Some((cell, colors));
+      }
+    }
+
+    placements.push(placement);
+    cells.push(rendered);
+  }
+  for j in chars.len()..chars.len() + vertical.len() {
+    let mark = vertical[j - chars.len()].0;
+    if let Some(base) = chars.iter().position(|c| *c == mark) {
+      placements[base].vertical_index = Some(j);
+    }
+  }
+
+  let image = match grid_size {
+    Some((img_width, img_height)) => {
+      let mut image = RgbImage::from_pixel(img_width, img_height, options.bg_color);
+      if options.background_mode == CharBackgroundMode::Checkerboard {
+        fill_checkerboard(&mut image, font_size, options.alt_bg_color);
+      }
+      if let Some(background) = &options.background_image {
+        fill_tiled(&mut image, background);
+      }
+      if let Some(color) = options.cell_border_color {
+        draw_cell_borders(&mut image, font_size, color);
+      }
+      for (placement, cell) in placements.iter().zip(&cells) {
+        if let Some((cell, colors)) = cell {
+          cell.blit(
+            &mut image,
+            placement.cell_x,
+            placement.cell_y,
+            *colors,
+            options.shadow_opacity,
+          );
+        }
+      }
+      image
+    }
+    None => pack_tight(&mut placements, &cells, options)?,
+  };
+
+  Ok(Atlas {
+    image,
+    tile_count: options.tile_count,
+    placements,
+    clamped_chars,
+    gamma_flipped_pixels,
+    threshold_overrides,
+    cells: cells
+      .into_iter()
+      .map(|cell| cell.map(|(cell, _)| cell))
+      .collect(),
+  })
+}
+
+/// Shelf-pack every glyph's ink bounding box into an atlas no wider than the grid layout.
+fn pack_tight(
+  placements: &mut [GlyphPlacement],
+  cells: &[Option<(CellBuffer, CellColors)>],
+  options: &RenderOptions,
+) -> Result<RgbImage> {
+  let max_width = (options.chars_per_line as u64 * options.font_size as u64)
+    .min(options.max_image_width as u64) as u32;
+  let sizes = placements
+    .iter()
+    .map(|placement| {
+      placement
+        .ink_bbox
+        .map_or((0, 0), |rect| (rect.width, rect.height))
+    })
+    .collect::<Vec<_>>();
+  let packing = shelf_pack(&sizes, max_width);
+  if packing.height > options.max_image_height {
+    return Err(overflow(format!(
+      "[Error] Packed atlas would be {}x{} px, exceeding the {}x{} limit! Adjust \
+       max_image_width, or split the script into several atlases.",
+      packing.width, packing.height, options.max_image_width, options.max_image_height
+    )));
+  }
+
+  let mut image = RgbImage::from_pixel(
+    packing.width.max(1),
+    packing.height.max(1),
+    options.bg_color,
+  );
+  if let Some(background) = &options.background_image {
+    fill_tiled(&mut image, background);
+  }
+  for ((placement, cell), (&(x, y), &(width, height))) in placements
+    .iter_mut()
+    .zip(cells)
+    .zip(packing.positions.iter().zip(&sizes))
+  {
+    placement.packed = Some(PixelRect {
+      x,
+      y,
+      width,
+      height,
+    });
+    if let (Some((cell, colors)), Some(ink_bbox)) = (cell, placement.ink_bbox) {
+      cell.blit_rect(&mut image, ink_bbox, x, y, *colors, options.shadow_opacity);
+    }
+  }
+  Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn closest_scale_prefers_the_smaller_of_equals() {
+    let samples = [
+      (12.0, (8, 8)),
+      (12.5, (8, 9)),
+      (13.0, (10, 9)),
+      (13.5, (10, 10)),
+    ];
+    assert_eq!(closest_scale(&samples, 9), Some(12.5));
+    assert_eq!(closest_scale(&samples, 11), Some(13.5));
+    assert_eq!(closest_scale(&[], 9), None);
+  }
+
+  #[test]
+  fn optimal_chars_per_line_prefers_near_square_grids() {
+    assert_eq!(optimal_chars_per_line(36, 10, 4096, 4096), Some(6));
+    // 10 = 5x2 has no padding, but only 4x3 and 3x4 are within a row of square; 4x3 is wider.
+    assert_eq!(optimal_chars_per_line(10, 10, 4096, 4096), Some(4));
+    // Primes fill a single row exactly, but a near-square grid wins.
+    assert_eq!(optimal_chars_per_line(7, 10, 4096, 4096), Some(3));
+    assert_eq!(optimal_chars_per_line(101, 10, 4096, 4096), Some(11));
+    assert_eq!(optimal_chars_per_line(10, 10, 30, 4096), Some(3));
+    // Only 1 or 2 columns fit: 2x5 is the squarer one.
+    assert_eq!(optimal_chars_per_line(10, 10, 20, 4096), Some(2));
+    assert_eq!(optimal_chars_per_line(10, 10, 10, 50), None);
+  }
+
+  #[test]
+  fn dimensions_round_up_to_full_rows() {
+    assert_eq!(
+      atlas_dimensions(33, 32, 10, 16384, 16384).unwrap(),
+      (320, 20)
+    );
+  }
+
+  #[test]
+  fn oversized_atlas_is_rejected() {
+    assert!(atlas_dimensions(50_000, 1, 10, 16384, 16384).is_err());
+    assert!(atlas_dimensions(10, 5000, 10, 16384, 16384).is_err());
+  }
+
+  #[test]
+  fn padding_defaults_to_char_count() {
+    assert_eq!(padded_tile_count(33, 32, false, None).unwrap(), 33);
+    assert_eq!(padded_tile_count(33, 32, true, None).unwrap(), 64);
+    assert_eq!(padded_tile_count(33, 32, false, Some(100)).unwrap(), 100);
+    assert!(padded_tile_count(33, 32, false, Some(10)).is_err());
+  }
+
+  #[test]
+  fn checkerboard_alternates_cells() {
+    let mut image = RgbImage::from_pixel(30, 20, Rgb([0, 0, 0]));
+    fill_checkerboard(&mut image, 10, Rgb([9, 9, 9]));
+    assert_eq!(*image.get_pixel(5, 5), Rgb([0, 0, 0]));
+    assert_eq!(*image.get_pixel(15, 5), Rgb([9, 9, 9]));
+    assert_eq!(*image.get_pixel(5, 15), Rgb([9, 9, 9]));
+    assert_eq!(*image.get_pixel(15, 15), Rgb([0, 0, 0]));
+  }
+
+  #[test]
+  fn background_image_is_tiled_and_flattened() {
+    let pattern = RgbaImage::from_fn(2, 1, |x, _| {
+      image::Rgba([0, 0, 0, if x == 0 { 255 } else { 0 }])
+    });
+    let pattern = flatten_on_white(&pattern);
+    let mut image = RgbImage::new(5, 2);
+    fill_tiled(&mut image, &pattern);
+    assert_eq!(*image.get_pixel(2, 1), Rgb([0, 0, 0]));
+    assert_eq!(*image.get_pixel(3, 0), Rgb([255, 255, 255]));
+  }
+
+  #[test]
+  fn debug_checker_keeps_glyph_pixels() {
+    let (bg, alt, ink) = (Rgb([0, 0, 0]), Rgb([9, 9, 9]), Rgb([255, 255, 255]));
+    let mut image = RgbImage::from_pixel(20, 10, bg);
+    image.put_pixel(15, 5, ink);
+    let checker = debug_checker(&image, 10, bg, alt);
+    assert_eq!(*checker.get_pixel(5, 5), bg);
+    assert_eq!(*checker.get_pixel(14, 5), alt);
+    assert_eq!(*checker.get_pixel(15, 5), ink);
+    assert_eq!(*image.get_pixel(14, 5), bg);
+  }
+
+  #[test]
+  fn group_spacing_starts_new_rows() {
+    let chars = ['一', '丁', '㐀', '㐁', '㐂'];
+    let (positions, height) = grid_layout(&chars, 6, 2, 10, 2);
+    assert_eq!(
+      positions,
+      vec![(0, 0), (10, 0), (0, 12), (10, 12), (0, 22), (10, 22)]
+    );
+    assert_eq!(height, 32);
+    assert_eq!(grid_layout(&chars, 5, 2, 10, 0).1, 30);
+  }
+
+  #[test]
+  fn cell_borders_are_shared() {
+    let mut image = RgbImage::from_pixel(20, 10, Rgb([0, 0, 0]));
+    draw_cell_borders(&mut image, 10, Rgb([9, 9, 9]));
+    for x in [0, 10, 19] {
+      assert_eq!(*image.get_pixel(x, 5), Rgb([9, 9, 9]));
+    }
+    assert_eq!(*image.get_pixel(9, 5), Rgb([0, 0, 0]));
+    assert_eq!(*image.get_pixel(5, 9), Rgb([9, 9, 9]));
+  }
+
+  #[test]
+  fn vertical_flip_mirrors_rows() {
+    let flip = Flip {
+      horizontal: false,
+      vertical: true,
+    };
+    let rect = PixelRect {
+      x: 10,
+      y: 0,
+      width: 10,
+      height: 10,
+    };
+    assert_eq!(flip.rect(rect, 30, 20), PixelRect { y: 10, ..rect });
+    let mut image = RgbImage::new(1, 2);
+    image.put_pixel(0, 0, Rgb([1, 1, 1]));
+    flip.apply(&mut image);
+    assert_eq!(*image.get_pixel(0, 1), Rgb([1, 1, 1]));
+  }
+
+  #[test]
+  fn zero_chars_per_line_is_rejected() {
+    assert!(atlas_dimensions(10, 0, 10, 16384, 16384).is_err());
+  }
+}
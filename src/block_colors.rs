@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Result, anyhow, bail};
+use image::Rgb;
+
+use crate::labels::{BLOCK_LABELS, unicode_block_label};
+
+/// Character colors of the `char_colors` config table, by codepoint range or Unicode block.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockColors {
+  /// `U+XXXX-U+YYYY` keys, checked first, in key order.
+  ranges: Vec<(u32, u32, Rgb<u8>)>,
+  /// Block label keys (see [`unicode_block_label`]).
+  blocks: BTreeMap<&'static str, Rgb<u8>>,
+}
+
+/// A `U+XXXX` codepoint of a `char_colors` range key.
+fn parse_codepoint(value: &str, key: &str) -> Result<u32> {
+  value
+    .trim()
+    .strip_prefix("U+")
+    .or_else(|| value.trim().strip_prefix("u+"))
+    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+    .filter(|codepoint| char::from_u32(*codepoint).is_some())
+    .ok_or_else(|| {
+      anyhow!(
+        "[Error] Invalid char_colors key \"{}\": '{}' is not a U+XXXX codepoint!",
+        key,
+        value
+      )
+    })
+}
+
+impl BlockColors {
+  /// Parse the `char_colors` table. Keys are a block label such as `"CJK"` or `"FullW"`, or a
+  /// codepoint range such as `"U+4E00-U+9FFF"`.
+  pub fn parse(table: &BTreeMap<String, [u8; 3]>) -> Result<BlockColors> {
+    let mut colors = BlockColors::default();
+    for (key, color) in table {
+      if key.starts_with("U+") || key.starts_with("u+") {
+        let Some((start, end)) = key.split_once('-') else {
+          bail!(
+            "[Error] Invalid char_colors key \"{}\": expected a U+XXXX-U+YYYY range!",
+            key
+          );
+        };
+        let (start, end) = (parse_codepoint(start, key)?, parse_codepoint(end, key)?);
+        if start > end {
+          bail!(
+            "[Error] Invalid char_colors key \"{}\": the range is reversed!",
+            key
+          );
+        }
+        colors.ranges.push((start, end, Rgb(*color)));
+      } else if let Some(label) = BLOCK_LABELS.iter().find(|label| *label == key) {
+        colors.blocks.insert(label, Rgb(*color));
+      } else {
+        bail!(
+          "[Error] Invalid char_colors key \"{}\": expected a U+XXXX-U+YYYY range or one of {}!",
+          key,
+          BLOCK_LABELS.join(", ")
+        );
+      }
+    }
+    Ok(colors)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.ranges.is_empty() && self.blocks.is_empty()
+  }
+
+  /// Color of `c`: the first range holding it, else its block's color, `None` when neither is
+  /// configured.
+  pub fn color_of(&self, c: char) -> Option<Rgb<u8>> {
+    let codepoint = c as u32;
+    self
+      .ranges
+      .iter()
+      .find(|(start, end, _)| (*start..=*end).contains(&codepoint))
+      .map(|(_, _, color)| *color)
+      .or_else(|| self.blocks.get(unicode_block_label(c)).copied())
+  }
+
+  /// Every configured color.
+  pub fn colors(&self) -> impl Iterator<Item = Rgb<u8>> + '_ {
+    self
+      .ranges
+      .iter()
+      .map(|(_, _, color)| *color)
+      .chain(self.blocks.values().copied())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn ranges_win_over_blocks() {
+    let table = BTreeMap::from([
+      ("CJK".to_string(), [255, 0, 0]),
+      ("FullW".to_string(), [0, 0, 255]),
+      ("U+4E2D-U+4E2D".to_string(), [0, 255, 0]),
+    ]);
+    let colors = BlockColors::parse(&table).unwrap();
+    assert_eq!(colors.color_of('一'), Some(Rgb([255, 0, 0])));
+    assert_eq!(colors.color_of('中'), Some(Rgb([0, 255, 0])));
+    assert_eq!(colors.color_of('，'), Some(Rgb([0, 0, 255])));
+    assert_eq!(colors.color_of('A'), None);
+  }
+
+  #[test]
+  fn bad_keys_are_rejected() {
+    for key in ["Hangul", "U+9FFF-U+4E00", "U+4E00-9FFF", "U+D800-U+D8FF"] {
+      let table = BTreeMap::from([(key.to_string(), [0, 0, 0])]);
+      assert!(BlockColors::parse(&table).is_err(), "{}", key);
+    }
+  }
+}
@@ -0,0 +1,819 @@
+use std::{collections::BTreeMap, error::Error, fmt};
+
+use ab_glyph::FontVec;
+use image::{Rgb, RgbImage};
+
+use crate::{
+  atlas::{
+    Atlas, CharBackgroundMode, ColorClassTiles, RenderOptions, padded_tile_count, render_atlas,
+  },
+  block_colors::BlockColors,
+  config::{CharGradient, Config},
+  font_check::FontDiagnosis,
+  pack::PackMode,
+  punctuation::vertical_punctuation,
+  render::{CellColors, Coverage, MAX_SLANT, RenderStyle, Rotation, ShadowDirection, ShadowStyle},
+  warning::RenderContext,
+  woff::to_sfnt,
+};
+
+/// Why an [`AtlasBuilder`] could not be built.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+  /// No font was added with [`AtlasBuilder::font_bytes`].
+  NoFont,
+  /// The font at this position in the fallback chain could not be parsed.
+  InvalidFont(usize, FontDiagnosis),
+  /// The font at this position only has embedded bitmaps, no outlines.
+  BitmapOnlyFont(usize, FontDiagnosis),
+  /// The WOFF/WOFF2 container of the font at this position could not be unwrapped.
+  InvalidFontContainer(usize, String),
+  /// Only 10px and 11px cells are supported.
+  UnsupportedSize(u32),
+  /// The point size must be a positive number.
+  InvalidPointSize(f32),
+  /// The coverage gamma must be a positive number.
+  InvalidCoverageGamma(f32),
+  /// A per-character coverage threshold outside 0.0..=1.0.
+  InvalidCharThreshold(char),
+  ZeroCharsPerLine,
+  ZeroMaxRows,
+  /// The shadow width must be 1 to [`MAX_SHADOW_WIDTH`] px.
+  InvalidShadowWidth(u32),
+  /// The slant is limited to [`MAX_SLANT`] px.
+  InvalidSlant(u32),
+  /// Shadow offsets are limited to ±3px on each axis and must not be (0, 0).
+  InvalidShadowOffset((i32, i32)),
+  /// Two options that cannot be combined.
+  IncompatibleOptions(String),
+}
+
+impl fmt::Display for BuildError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      BuildError::NoFont => write!(f, "[Error] At least one font is required!"),
+      BuildError::InvalidFont(index, diagnosis) => {
+        write!(
+          f,
+          "[Error] Font #{} could not be parsed! ({})",
+          index, diagnosis
+        )
+      }
+      BuildError::BitmapOnlyFont(index, diagnosis) => write!(
+        f,
+        "[Error] Font #{} is a bitmap-only font, which is not supported! Use a font with glyph \
+         outlines, or convert the bitmap strikes to an outline font. ({})",
+        index, diagnosis
+      ),
+      BuildError::InvalidFontContainer(index, message) => {
+        write!(f, "{} (font #{})", message, index)
+      }
+      BuildError::UnsupportedSize(_) => write!(f, "[Error] Only support 10px or 11px!"),
+      BuildError::InvalidPointSize(_) => {
+        write!(f, "[Error] Font size(pt) must be a positive number!")
+      }
+      BuildError::InvalidCoverageGamma(_) => {
+        write!(f, "[Error] coverage_gamma must be a positive number!")
+      }
+      BuildError::InvalidCharThreshold(c) => {
+        write!(
+          f,
+          "[Error] The coverage threshold of '{}' must be within 0.0..=1.0!",
+          c
+        )
+      }
+      BuildError::ZeroCharsPerLine => {
+        write!(f, "[Error] chars_per_line must be greater than 0!")
+      }
+      BuildError::ZeroMaxRows => write!(f, "[Error] max_rows must be greater than 0!"),
+      BuildError::InvalidShadowOffset((dx, dy)) => write!(
+        f,
+        "[Error] Shadow offset ({}, {}) must be within ±{}px and not (0, 0)!",
+        dx, dy, MAX_SHADOW_OFFSET
+      ),
+      BuildError::InvalidShadowWidth(width) => write!(
+        f,
+        "[Error] Shadow width {} must be within 1..={}px!",
+        width, MAX_SHADOW_WIDTH
+      ),
+      BuildError::InvalidSlant(slant) => write!(
+        f,
+        "[Error] Slant {} must be at most {}px!",
+        slant, MAX_SLANT
+      ),
+      BuildError::IncompatibleOptions(reason) => write!(f, "[Error] {}", reason),
+    }
+  }
+}
+
+impl Error for BuildError {}
+
+/// Largest shadow offset (px) on each axis.
+pub const MAX_SHADOW_OFFSET: i32 = 3;
+
+/// Largest `--char-shadow-width` (px).
+pub const MAX_SHADOW_WIDTH: u32 = 3;
+
+/// Blank tiles appended after the last character.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Padding {
+  #[default]
+  None,
+  /// Up to a multiple of `chars_per_line`.
+  FullRows,
+  /// Up to exactly this many tiles.
+  Count(usize),
+}
+
+/// Chained configuration for atlas generation, validated by [`AtlasBuilder::build`].
+///
+/// ```no_run
+/// # use chinese_bitmap_font_demo::builder::AtlasBuilder;
+/// # let font_bytes = Vec::new();
+/// let generator = AtlasBuilder::new().font_bytes(font_bytes).size(10).chars_per_line(32).build()?;
+/// let atlas = generator.generate(&['中', '文'])?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct AtlasBuilder {
+  /// Font file bytes and face index within a font collection.
+  fonts: Vec<(Vec<u8>, u32)>,
+  size: u32,
+  font_size_pt: Option<f32>,
+  shadow: Option<ShadowStyle>,
+  shadow_direction: ShadowDirection,
+  shadow_offset: Option<(i32, i32)>,
+  shadow_opacity: u8,
+  shadow_width: u32,
+  vertical_punctuation: bool,
+  style: RenderStyle,
+  rotation: Rotation,
+  tile_rotation: Rotation,
+  bold: bool,
+  slant: u32,
+  coverage_gamma: f32,
+  char_thresholds: BTreeMap<char, f32>,
+  missing_fallback: Option<char>,
+  missing_fallbacks: BTreeMap<char, char>,
+  zh_hant: bool,
+  punctuation_offsets_10px: BTreeMap<char, (u32, u32)>,
+  punctuation_offsets_11px: BTreeMap<char, (u32, u32)>,
+  baseline_offset: i32,
+  chars_per_line: usize,
+  max_rows: Option<usize>,
+  padding: Padding,
+  max_image_width: u32,
+  max_image_height: u32,
+  bg_color: [u8; 3],
+  background_mode: CharBackgroundMode,
+  alt_bg_color: [u8; 3],
+  background_image: Option<RgbImage>,
+  cell_border_color: Option<[u8; 3]>,
+  group_spacing: u32,
+  line_separator: bool,
+  char_color: [u8; 3],
+  shadow_color: [u8; 3],
+  char_gradient: Option<CharGradient>,
+  block_colors: Option<BlockColors>,
+  color_key: Option<[u8; 3]>,
+  pack: PackMode,
+}
+
+impl Default for AtlasBuilder {
+  fn default() -> Self {
+    AtlasBuilder::from_config(&Config::default())
+  }
+}
+
+impl AtlasBuilder {
+  pub fn new() -> Self {
+    AtlasBuilder::default()
+  }
+
+  /// Start from the colors and limits of a config file.
+  pub fn from_config(config: &Config) -> Self {
+    AtlasBuilder {
+      fonts: Vec::new(),
+      size: 10,
+      font_size_pt: None,
+      shadow: None,
+      shadow_direction: config.shadow_direction,
+      shadow_offset: config.shadow_offset.map(|[dx, dy]| (dx, dy)),
+      shadow_opacity: config.shadow_opacity,
+      shadow_width: 1,
+      vertical_punctuation: false,
+      style: RenderStyle::Filled,
+      rotation: Rotation::None,
+      tile_rotation: Rotation::None,
+      bold: false,
+      slant: 0,
+      coverage_gamma: config.coverage_gamma,
+      char_thresholds: config.char_thresholds.clone(),
+      missing_fallback: config.missing_fallback_char,
+      missing_fallbacks: config.missing_fallback_chars.clone(),
+      zh_hant: false,
+      punctuation_offsets_10px: config.punctuation_offsets(10),
+      punctuation_offsets_11px: config.punctuation_offsets(11),
+      baseline_offset: 0,
+      chars_per_line: config.chars_per_line,
+      max_rows: None,
+      padding: Padding::None,
+      max_image_width: config.max_image_width,
+      max_image_height: config.max_image_height,
+      bg_color: config.img_bg_color,
+      background_mode: CharBackgroundMode::None,
+      alt_bg_color: config.alt_bg_color(),
+      background_image: None,
+      cell_border_color: None,
+      group_spacing: 0,
+      line_separator: false,
+      char_color: config.char_color,
+      shadow_color: config.char_shadow_color,
+      char_gradient: config.char_gradient,
+      block_colors: None,
+      color_key: None,
+      pack: PackMode::Grid,
+    }
+  }
+
+  /// Append a font to the fallback chain.
+  pub fn font_bytes(self, font_bytes: Vec<u8>) -> Self {
+    self.font_collection_bytes(font_bytes, 0)
+  }
+
+  /// Append face `index` of a font collection (`.ttc`) to the fallback chain.
+  pub fn font_collection_bytes(mut self, font_bytes: Vec<u8>, index: u32) -> Self {
+    self.fonts.push((font_bytes, index));
+    self
+  }
+
+  /// Cell size (px), 10 or 11.
+  pub fn size(mut self, size: u32) -> Self {
+    self.size = size;
+    self
+  }
+
+  /// Font size in points, overriding the size derived from the cell size.
+  pub fn font_size_pt(mut self, font_size_pt: f32) -> Self {
+    self.font_size_pt = Some(font_size_pt);
+    self
+  }
+
+  /// Rasterizer scale (px), e.g. one found by [`calibrate_scale`](crate::atlas::calibrate_scale).
+  /// Same as `font_size_pt(scale / 2.0)`.
+  pub fn font_scale(self, scale: f32) -> Self {
+    self.font_size_pt(scale / 2.0)
+  }
+
+  /// Shadow style; defaults to the style matching the cell size.
+  pub fn shadow(mut self, shadow: ShadowStyle) -> Self {
+    self.shadow = Some(shadow);
+    self
+  }
+
+  /// Direction of the 10px drop shadow.
+  pub fn shadow_direction(mut self, direction: ShadowDirection) -> Self {
+    self.shadow_direction = direction;
+    self
+  }
+
+  /// Replace the style's shadow with a single copy of the glyph moved by (`dx`, `dy`).
+  pub fn shadow_offset(mut self, dx: i32, dy: i32) -> Self {
+    self.shadow_offset = Some((dx, dy));
+    self
+  }
+
+  /// Shadow opacity (0-255), blended over the background, checkerboard or background image
+  /// below the shadow.
+  pub fn shadow_opacity(mut self, opacity: u8) -> Self {
+    self.shadow_opacity = opacity;
+    self
+  }
+
+  /// Shadow spread (px, 1 to 3): the drop shadow extends further, the outline gets thicker.
+  pub fn shadow_width(mut self, width: u32) -> Self {
+    self.shadow_width = width;
+    self
+  }
+
+  /// How the glyphs themselves are drawn.
+  pub fn style(mut self, style: RenderStyle) -> Self {
+    self.style = style;
+    self
+  }
+
+  /// Clockwise rotation of every glyph within its cell.
+  pub fn rotation(mut self, rotation: Rotation) -> Self {
+    self.rotation = rotation;
+    self
+  }
+
+  /// Clockwise rotation of every finished cell (glyph and shadow) within the unchanged grid.
+  pub fn tile_rotation(mut self, rotation: Rotation) -> Self {
+    self.tile_rotation = rotation;
+    self
+  }
+
+  /// Fake bold: draw every glyph twice, at x and x+1, widening the strokes by 1px.
+  pub fn bold(mut self, bold: bool) -> Self {
+    self.bold = bold;
+    self
+  }
+
+  /// Append a tile with the vertical presentation form of every punctuation mark, rotating the
+  /// horizontal mark when no font has the form.
+  pub fn vertical_punctuation(mut self, vertical_punctuation: bool) -> Self {
+    self.vertical_punctuation = vertical_punctuation;
+    self
+  }
+
+  /// Fake italic: shear every glyph right by up to `slant` px (0-2) at the top.
+  pub fn slant(mut self, slant: u32) -> Self {
+    self.slant = slant;
+    self
+  }
+
+  /// Gamma applied to the glyph coverage before thresholding; above 1.0 keeps faint strokes.
+  pub fn coverage_gamma(mut self, gamma: f32) -> Self {
+    self.coverage_gamma = gamma;
+    self
+  }
+
+  /// Coverage threshold used only for `c`, instead of the global one.
+  pub fn char_threshold(mut self, c: char, threshold: f32) -> Self {
+    self.char_thresholds.insert(c, threshold);
+    self
+  }
+
+  /// Draw `fallback` in the tiles of characters no font has. They are still reported missing.
+  pub fn missing_fallback(mut self, fallback: char) -> Self {
+    self.missing_fallback = Some(fallback);
+    self
+  }
+
+  /// Fallback used only for the missing character `c`, instead of the global one.
+  pub fn missing_fallback_for(mut self, c: char, fallback: char) -> Self {
+    self.missing_fallbacks.insert(c, fallback);
+    self
+  }
+
+  /// Use the zh-hant punctuation offsets.
+  pub fn zh_hant(mut self, zh_hant: bool) -> Self {
+    self.zh_hant = zh_hant;
+    self
+  }
+
+  /// Glyph position of `c` in `font_size` cells, replacing the built-in punctuation offset.
+  pub fn punctuation_offset(mut self, font_size: u32, c: char, x: u32, y: u32) -> Self {
+    let table = if font_size == 11 {
+      &mut self.punctuation_offsets_11px
+    } else {
+      &mut self.punctuation_offsets_10px
+    };
+    table.insert(c, (x, y));
+    self
+  }
+
+  /// Shift every glyph down by `offset` px (up when negative).
+  pub fn baseline_offset(mut self, offset: i32) -> Self {
+    self.baseline_offset = offset;
+    self
+  }
+
+  pub fn chars_per_line(mut self, chars_per_line: usize) -> Self {
+    self.chars_per_line = chars_per_line;
+    self
+  }
+
+  /// Split the atlas into pages of at most `max_rows` cell rows.
+  pub fn max_rows(mut self, max_rows: usize) -> Self {
+    self.max_rows = Some(max_rows);
+    self
+  }
+
+  pub fn padding(mut self, padding: Padding) -> Self {
+    self.padding = padding;
+    self
+  }
+
+  pub fn max_image_size(mut self, width: u32, height: u32) -> Self {
+    self.max_image_width = width;
+    self.max_image_height = height;
+    self
+  }
+
+  pub fn bg_color(mut self, color: [u8; 3]) -> Self {
+    self.bg_color = color;
+    self
+  }
+
+  /// Cell background pattern of grid atlases.
+  pub fn background_mode(mut self, mode: CharBackgroundMode) -> Self {
+    self.background_mode = mode;
+    self
+  }
+
+  /// Background of the odd cells in checkerboard mode.
+  pub fn alt_bg_color(mut self, color: [u8; 3]) -> Self {
+    self.alt_bg_color = color;
+    self
+  }
+
+  /// Tile this image across the atlas instead of the flat background color.
+  pub fn background_image(mut self, image: RgbImage) -> Self {
+    self.background_image = Some(image);
+    self
+  }
+
+  /// Draw a 1px grid line around every cell, under the glyphs.
+  pub fn cell_border_color(mut self, color: [u8; 3]) -> Self {
+    self.cell_border_color = Some(color);
+    self
+  }
+
+  /// Start a new row, `spacing` px lower, whenever the Unicode block (`c / 256`) changes.
+  pub fn group_spacing(mut self, spacing: u32) -> Self {
+    self.group_spacing = spacing;
+    self
+  }
+
+  /// Leave a blank row of cells between Unicode blocks (`c / 256`): a group spacing of one cell,
+  /// which keeps the grid aligned.
+  pub fn line_separator(mut self, enabled: bool) -> Self {
+    self.line_separator = enabled;
+    self
+  }
+
+  pub fn char_color(mut self, color: [u8; 3]) -> Self {
+    self.char_color = color;
+    self
+  }
+
+  pub fn shadow_color(mut self, color: [u8; 3]) -> Self {
+    self.shadow_color = color;
+    self
+  }
+
+  /// Fill the character pixels with a vertical gradient instead of the flat char color.
+  pub fn char_gradient(mut self, gradient: CharGradient) -> Self {
+    self.char_gradient = Some(gradient);
+    self
+  }
+
+  /// Color the regular tiles by Unicode block; characters without a block color keep
+  /// `char_color`.
+  pub fn block_colors(mut self, block_colors: BlockColors) -> Self {
+    self.block_colors = Some(block_colors);
+    self
+  }
+
+  /// Use `color` as a transparency color key: it becomes the background color, and glyphs that
+  /// render pixels in it are reported.
+  pub fn color_key(mut self, color: [u8; 3]) -> Self {
+    self.color_key = Some(color);
+    self
+  }
+
+  /// Layout of the glyphs in the atlas.
+  pub fn pack(mut self, pack: PackMode) -> Self {
+    self.pack = pack;
+    self
+  }
+
+  /// Validate the option combination and parse the fonts.
+  pub fn build(mut self) -> Result<AtlasGenerator, BuildError> {
+    if self.fonts.is_empty() {
+      return Err(BuildError::NoFont);
+    }
+    if self.size != 10 && self.size != 11 {
+      return Err(BuildError::UnsupportedSize(self.size));
+    }
+    if let Some(font_size_pt) = self.font_size_pt
+      && !(font_size_pt.is_finite() && font_size_pt > 0.0)
+    {
+      return Err(BuildError::InvalidPointSize(font_size_pt));
+    }
+    if !(self.coverage_gamma.is_finite() && self.coverage_gamma > 0.0) {
+      return Err(BuildError::InvalidCoverageGamma(self.coverage_gamma));
+    }
+    if let Some((c, _)) = self
+      .char_thresholds
+      .iter()
+      .find(|(_, threshold)| !(0.0..=1.0).contains(*threshold))
+    {
+      return Err(BuildError::InvalidCharThreshold(*c));
+    }
+    if self.chars_per_line == 0 {
+      return Err(BuildError::ZeroCharsPerLine);
+    }
+    if !(1..=MAX_SHADOW_WIDTH).contains(&self.shadow_width) {
+      return Err(BuildError::InvalidShadowWidth(self.shadow_width));
+    }
+    if self.slant > MAX_SLANT {
+      return Err(BuildError::InvalidSlant(self.slant));
+    }
+    if self.max_rows == Some(0) {
+      return Err(BuildError::ZeroMaxRows);
+    }
+    if self.max_rows.is_some() && self.pack == PackMode::Tight {
+      return Err(BuildError::IncompatibleOptions(
+        "Pages of max_rows rows need the grid layout!".to_string(),
+      ));
+    }
+    if let Some((dx, dy)) = self.shadow_offset
+      && (dx.abs() > MAX_SHADOW_OFFSET || dy.abs() > MAX_SHADOW_OFFSET || (dx, dy) == (0, 0))
+    {
+      return Err(BuildError::InvalidShadowOffset((dx, dy)));
+    }
+    let shadow = self
+      .shadow
+      .unwrap_or_else(|| ShadowStyle::for_font_size(self.size));
+    if shadow != ShadowStyle::for_font_size(self.size) {
+      return Err(BuildError::IncompatibleOptions(format!(
+        "{:?} shadow style does not fit a {}px cell!",
+        shadow, self.size
+      )));
+    }
+    if let Some(color_key) = self.color_key
+      && (self.char_color == color_key || self.shadow_color == color_key)
+    {
+      return Err(BuildError::IncompatibleOptions(format!(
+        "The color key {:?} matches char_color or char_shadow_color, the characters would be \
+         treated as transparent!",
+        color_key
+      )));
+    }
+    if let Some(block_colors) = &self.block_colors {
+      if self.char_gradient.is_some() {
+        return Err(BuildError::IncompatibleOptions(
+          "Per-block character colors cannot be combined with char_gradient!".to_string(),
+        ));
+      }
+      if let Some(color_key) = self.color_key
+        && block_colors.colors().any(|color| color.0 == color_key)
+      {
+        return Err(BuildError::IncompatibleOptions(format!(
+          "The color key {:?} matches a char_colors color, those characters would be treated \
+           as transparent!",
+          color_key
+        )));
+      }
+    }
+    if self.background_mode == CharBackgroundMode::Checkerboard {
+      if self.pack == PackMode::Tight {
+        return Err(BuildError::IncompatibleOptions(
+          "The checkerboard background needs the grid layout!".to_string(),
+        ));
+      }
+      if self.color_key.is_some() {
+        return Err(BuildError::IncompatibleOptions(
+          "The checkerboard background cannot be combined with a color key!".to_string(),
+        ));
+      }
+    }
+
+    if let Some(background) = &self.background_image {
+      if background.width() == 0 || background.height() == 0 {
+        return Err(BuildError::IncompatibleOptions(
+          "The background image is empty!".to_string(),
+        ));
+      }
+      if self.background_mode == CharBackgroundMode::Checkerboard || self.color_key.is_some() {
+        return Err(BuildError::IncompatibleOptions(
+          "The background image cannot be combined with the checkerboard background or a color \
+           key!"
+            .to_string(),
+        ));
+      }
+    }
+
+    if self.cell_border_color.is_some() && self.pack == PackMode::Tight {
+      return Err(BuildError::IncompatibleOptions(
+        "Cell borders need the grid layout!".to_string(),
+      ));
+    }
+
+    if self.line_separator {
+      if self.group_spacing > 0 {
+        return Err(BuildError::IncompatibleOptions(
+          "A line separator cannot be combined with group spacing!".to_string(),
+        ));
+      }
+      self.group_spacing = self.size;
+    }
+    // Whole-cell spacing keeps every cell on the grid that checkerboards and borders follow.
+    let off_grid = !self.group_spacing.is_multiple_of(self.size);
+    if self.group_spacing > 0
+      && (self.pack == PackMode::Tight
+        || self.max_rows.is_some()
+        || (off_grid
+          && (self.background_mode == CharBackgroundMode::Checkerboard
+            || self.cell_border_color.is_some())))
+    {
+      return Err(BuildError::IncompatibleOptions(
+        "Group spacing cannot be combined with tight packing, pages, the checkerboard \
+         background or cell borders!"
+          .to_string(),
+      ));
+    }
+
+    let fonts = self
+      .fonts
+      .into_iter()
+      .enumerate()
+      .map(|(index, (bytes, face_index))| {
+        let sfnt = to_sfnt(bytes.clone())
+          .map_err(|e| BuildError::InvalidFontContainer(index, e.to_string()))?;
+        let diagnosis = FontDiagnosis::new(&bytes, &sfnt, face_index);
+        if diagnosis.is_bitmap_only() {
+          return Err(BuildError::BitmapOnlyFont(index, diagnosis));
+        }
+        FontVec::try_from_vec_and_index(sfnt, face_index)
+          .map_err(|_| BuildError::InvalidFont(index, diagnosis))
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(AtlasGenerator {
+      fonts,
+      padding: self.padding,
+      options: RenderOptions {
+        font_size: self.size,
+        shadow,
+        shadow_direction: self.shadow_direction,
+        shadow_offset: self.shadow_offset,
+        shadow_opacity: self.shadow_opacity,
+        shadow_width: self.shadow_width,
+        vertical_punctuation: self.vertical_punctuation,
+        style: self.style,
+        rotation: self.rotation,
+        tile_rotation: self.tile_rotation,
+        bold: self.bold,
+        slant: self.slant,
+        coverage: Coverage {
+          gamma: self.coverage_gamma,
+          ..Coverage::default()
+        },
+        char_thresholds: self.char_thresholds,
+        missing_fallback: self.missing_fallback,
+        missing_fallbacks: self.missing_fallbacks,
+        font_size_pt: self.font_size_pt,
+        is_zh_hant: self.zh_hant,
+        punctuation_offsets: if self.size == 11 {
+          self.punctuation_offsets_11px
+        } else {
+          self.punctuation_offsets_10px
+        },
+        baseline_offset: self.baseline_offset,
+        chars_per_line: self.chars_per_line,
+        max_rows: self.max_rows,
+        tile_count: 0,
+        max_image_width: self.max_image_width,
+        max_image_height: self.max_image_height,
+        bg_color: Rgb(self.color_key.unwrap_or(self.bg_color)),
+        background_mode: self.background_mode,
+        alt_bg_color: Rgb(self.alt_bg_color),
+        background_image: self.background_image,
+        group_spacing: self.group_spacing,
+        cell_border_color: self.cell_border_color.map(Rgb),
+        colors: CellColors {
+          char_color: Rgb(
+            self
+              .char_gradient
+              .map_or(self.char_color, |gradient| gradient.top),
+          ),
+          shadow_color: Rgb(self.shadow_color),
+          gradient_bottom: self.char_gradient.map(|gradient| Rgb(gradient.bottom)),
+        },
+        block_colors: self.block_colors,
+        color_key: self.color_key.map(Rgb),
+        pack: self.pack,
+      },
+    })
+  }
+}
+
+/// A validated [`AtlasBuilder`], ready to render character sets.
+pub struct AtlasGenerator {
+  fonts: Vec<FontVec>,
+  padding: Padding,
+  options: RenderOptions,
+}
+
+impl AtlasGenerator {
+  /// The parsed fallback chain, in the order the fonts were added.
+  pub fn fonts(&self) -> &[FontVec] {
+    &self.fonts
+  }
+
+  /// Render options shared by every [`AtlasGenerator::generate`] call.
+  pub fn options(&self) -> &RenderOptions {
+    &self.options
+  }
+
+  /// Render `chars` into an atlas.
+  pub fn generate(&self, chars: &[char]) -> anyhow::Result<Atlas> {
+    self.generate_with_classes(chars, &[])
+  }
+
+  /// Render `chars` into an atlas, followed by the duplicated tiles of each color class.
+  pub fn generate_with_classes(
+    &self,
+    chars: &[char],
+    classes: &[ColorClassTiles],
+  ) -> anyhow::Result<Atlas> {
+    self.generate_with_context(chars, classes, &mut RenderContext::default())
+  }
+
+  /// [`AtlasGenerator::generate_with_classes`], collecting the rendering warnings in `context`.
+  pub fn generate_with_context(
+    &self,
+    chars: &[char],
+    classes: &[ColorClassTiles],
+    context: &mut RenderContext,
+  ) -> anyhow::Result<Atlas> {
+    let (pad_to_full_rows, pad_to_count) = match self.padding {
+      Padding::None => (false, None),
+      Padding::FullRows => (true, None),
+      Padding::Count(count) => (false, Some(count)),
+    };
+    let vertical_count = if self.options.vertical_punctuation {
+      vertical_punctuation(chars).len()
+    } else {
+      0
+    };
+    let options = RenderOptions {
+      tile_count: padded_tile_count(
+        chars.len() + vertical_count + classes.iter().map(|class| class.chars.len()).sum::<usize>(),
+        self.options.chars_per_line,
+        pad_to_full_rows,
+        pad_to_count,
+      )?,
+      ..self.options.clone()
+    };
+    render_atlas(&self.fonts, chars, classes, &options, context)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn builder() -> AtlasBuilder {
+    AtlasBuilder::new().font_bytes(Vec::new())
+  }
+
+  #[test]
+  fn validation_errors_are_typed() {
+    assert_eq!(AtlasBuilder::new().build().err(), Some(BuildError::NoFont));
+    assert_eq!(
+      builder().size(12).build().err(),
+      Some(BuildError::UnsupportedSize(12))
+    );
+    assert_eq!(
+      builder().chars_per_line(0).build().err(),
+      Some(BuildError::ZeroCharsPerLine)
+    );
+    assert!(matches!(
+      builder().size(10).shadow(ShadowStyle::Outline).build(),
+      Err(BuildError::IncompatibleOptions(_))
+    ));
+    assert!(matches!(
+      builder().color_key(Config::default().char_color).build(),
+      Err(BuildError::IncompatibleOptions(_))
+    ));
+    assert!(matches!(
+      builder()
+        .background_mode(CharBackgroundMode::Checkerboard)
+        .pack(PackMode::Tight)
+        .build(),
+      Err(BuildError::IncompatibleOptions(_))
+    ));
+    assert_eq!(
+      builder().shadow_offset(0, 4).build().err(),
+      Some(BuildError::InvalidShadowOffset((0, 4)))
+    );
+    assert!(matches!(
+      builder().line_separator(true).group_spacing(2).build(),
+      Err(BuildError::IncompatibleOptions(_))
+    ));
+    assert!(matches!(
+      builder()
+        .group_spacing(2)
+        .background_mode(CharBackgroundMode::Checkerboard)
+        .build(),
+      Err(BuildError::IncompatibleOptions(_))
+    ));
+    // A whole blank row keeps the checkerboard on the grid: only the empty font fails.
+    assert!(matches!(
+      builder()
+        .line_separator(true)
+        .background_mode(CharBackgroundMode::Checkerboard)
+        .build(),
+      Err(BuildError::InvalidFont(..))
+    ));
+    assert!(matches!(
+      builder().build(),
+      Err(BuildError::InvalidFont(0, FontDiagnosis { size: 0, .. }))
+    ));
+  }
+}
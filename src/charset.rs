@@ -0,0 +1,143 @@
+use std::{
+  collections::{BTreeMap, HashMap, HashSet},
+  fs,
+  path::Path,
+};
+
+use anyhow::{Context, Result};
+
+use crate::script::{
+  ScriptFormat, SortOrder, get_unique_chinese_chars, preprocess_script, read_script,
+  sort_by_frequency, sort_by_legacy_code,
+};
+
+/// An ordered set of characters to render. Set operations keep the order of `self`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CharSet(pub Vec<char>);
+
+impl CharSet {
+  /// Sorted, de-duplicated Chinese characters and punctuation marks found in `text`.
+  pub fn from_text(text: &str) -> CharSet {
+    CharSet(get_unique_chinese_chars(text))
+  }
+
+  /// [`CharSet::from_text`] of a plain UTF-8 text file.
+  pub fn from_file(path: &Path) -> Result<CharSet> {
+    let bytes =
+      fs::read(path).with_context(|| format!("[Error] Could not read {}", path.display()))?;
+    let text = read_script(&bytes, ScriptFormat::Plain)?;
+    Ok(CharSet::from_text(&preprocess_script(&text)))
+  }
+
+  pub fn chars(&self) -> &[char] {
+    &self.0
+  }
+
+  pub fn into_vec(self) -> Vec<char> {
+    self.0
+  }
+
+  pub fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+
+  pub fn contains(&self, c: char) -> bool {
+    self.0.contains(&c)
+  }
+
+  /// Characters of `self`, then those of `other` not already included.
+  pub fn union(&self, other: &CharSet) -> CharSet {
+    let mut seen = self.0.iter().copied().collect::<HashSet<_>>();
+    let mut chars = self.0.clone();
+    chars.extend(other.0.iter().filter(|c| seen.insert(**c)));
+    CharSet(chars)
+  }
+
+  /// Characters of `self` that are also in `other`.
+  pub fn intersection(&self, other: &CharSet) -> CharSet {
+    let other = other.0.iter().collect::<HashSet<_>>();
+    self.retain(|c| other.contains(&c))
+  }
+
+  /// Characters of `self` that are not in `other`.
+  pub fn difference(&self, other: &CharSet) -> CharSet {
+    let other = other.0.iter().collect::<HashSet<_>>();
+    self.retain(|c| !other.contains(&c))
+  }
+
+  /// Characters within `start..=end`, e.g. a Unicode block.
+  pub fn filter_by_block(&self, start: char, end: char) -> CharSet {
+    self.retain(|c| (start..=end).contains(&c))
+  }
+
+  /// The characters grouped by Unicode block (256-codepoint range, keyed by `codepoint >> 8`),
+  /// in block order. Each group keeps the order of `self`.
+  pub fn split_by_block(&self) -> Vec<(u32, CharSet)> {
+    let mut blocks = BTreeMap::<u32, CharSet>::new();
+    for c in &self.0 {
+      blocks.entry(*c as u32 >> 8).or_default().0.push(*c);
+    }
+    blocks.into_iter().collect()
+  }
+
+  /// Sorted copy; [`SortOrder::Frequency`] uses `frequencies` (see
+  /// [`char_frequencies`](crate::script::char_frequencies)).
+  pub fn sort_by(&self, order: SortOrder, frequencies: &HashMap<char, usize>) -> CharSet {
+    let mut chars = self.0.clone();
+    match order {
+      SortOrder::Codepoint => chars.sort_unstable(),
+      SortOrder::Frequency => sort_by_frequency(&mut chars, frequencies),
+      SortOrder::Gb2312 | SortOrder::Big5 => {
+        sort_by_legacy_code(&mut chars, order);
+      }
+    }
+    CharSet(chars)
+  }
+
+  fn retain(&self, keep: impl Fn(char) -> bool) -> CharSet {
+    CharSet(self.0.iter().copied().filter(|c| keep(*c)).collect())
+  }
+}
+
+impl From<Vec<char>> for CharSet {
+  fn from(chars: Vec<char>) -> Self {
+    CharSet(chars)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::script::char_frequencies;
+
+  #[test]
+  fn set_operations_keep_order() {
+    let script = CharSet::from_text("中文口中");
+    let names = CharSet::from(vec!['口', '人', '中']);
+    assert_eq!(script.chars(), ['中', '口', '文']);
+    assert_eq!(script.union(&names).chars(), ['中', '口', '文', '人']);
+    assert_eq!(script.intersection(&names).chars(), ['中', '口']);
+    assert_eq!(script.difference(&names).chars(), ['文']);
+    assert_eq!(
+      CharSet::from(vec!['口', '，', '中']).split_by_block(),
+      [
+        (0x4e, CharSet::from(vec!['中'])),
+        (0x53, CharSet::from(vec!['口'])),
+        (0xff, CharSet::from(vec!['，']))
+      ]
+    );
+    assert_eq!(
+      script.filter_by_block('\u{4e00}', '\u{5fff}').chars(),
+      ['中', '口']
+    );
+    let frequencies = char_frequencies("文文口");
+    assert_eq!(
+      script.sort_by(SortOrder::Frequency, &frequencies).chars(),
+      ['文', '口', '中']
+    );
+  }
+}
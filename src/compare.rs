@@ -0,0 +1,152 @@
+use std::fmt;
+
+use image::{Rgb, RgbImage};
+
+use crate::{atlas::PixelRect, mapping::Mapping, render::blend};
+
+/// Color of the changed pixels in the `compare` diff image.
+const DIFF_COLOR: Rgb<u8> = Rgb([255, 0, 0]);
+/// Opacity of the [`DIFF_COLOR`] tint over the unchanged pixels of a changed tile.
+const DIFF_TINT_OPACITY: u8 = 96;
+
+/// A mapped tile whose pixels differ between two versions of the atlas.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TileDiff {
+  pub index: usize,
+  pub char: String,
+  pub codepoint: String,
+  pub rect: PixelRect,
+  pub changed_pixels: u32,
+}
+
+impl fmt::Display for TileDiff {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "Tile {} '{}' ({}): {} of {} pixels changed",
+      self.index,
+      self.char,
+      self.codepoint,
+      self.changed_pixels,
+      self.rect.width * self.rect.height
+    )
+  }
+}
+
+/// Tile-by-tile difference of two atlas images, as found by [`compare_atlases`].
+#[derive(Debug, Default, PartialEq)]
+pub struct AtlasDiff {
+  pub changed: Vec<TileDiff>,
+  /// Number of tiles inside both images, which were compared.
+  pub compared: usize,
+  /// Number of tiles reaching past the area both images share, which were not compared.
+  pub skipped: usize,
+}
+
+/// Compare the mapped tiles of `page` between the `old` and `new` atlas. Images of different
+/// sizes are compared on the area they share.
+pub fn compare_atlases(
+  old: &RgbImage,
+  new: &RgbImage,
+  mapping: &Mapping,
+  page: usize,
+) -> AtlasDiff {
+  let width = old.width().min(new.width()) as u64;
+  let height = old.height().min(new.height()) as u64;
+  let mut diff = AtlasDiff::default();
+  for entry in mapping.chars.iter().filter(|entry| entry.page == page) {
+    if entry.x as u64 + entry.width as u64 > width || entry.y as u64 + entry.height as u64 > height
+    {
+      diff.skipped += 1;
+      continue;
+    }
+    diff.compared += 1;
+    let changed_pixels = (entry.y..entry.y + entry.height)
+      .flat_map(|y| (entry.x..entry.x + entry.width).map(move |x| (x, y)))
+      .filter(|(x, y)| old.get_pixel(*x, *y) != new.get_pixel(*x, *y))
+      .count() as u32;
+    if changed_pixels > 0 {
+      diff.changed.push(TileDiff {
+        index: entry.index,
+        char: entry.char.clone(),
+        codepoint: entry.codepoint.clone(),
+        rect: PixelRect {
+          x: entry.x,
+          y: entry.y,
+          width: entry.width,
+          height: entry.height,
+        },
+        changed_pixels,
+      });
+    }
+  }
+  diff
+}
+
+/// The `new` atlas with the changed tiles of `diff` tinted red and their changed pixels in
+/// solid red.
+pub fn diff_image(old: &RgbImage, new: &RgbImage, diff: &AtlasDiff) -> RgbImage {
+  let mut image = new.clone();
+  for tile in &diff.changed {
+    let rect = tile.rect;
+    for y in rect.y..rect.y + rect.height {
+      for x in rect.x..rect.x + rect.width {
+        let pixel = image.get_pixel_mut(x, y);
+        *pixel = if old.get_pixel(x, y) != pixel {
+          DIFF_COLOR
+        } else {
+          blend(DIFF_COLOR, *pixel, DIFF_TINT_OPACITY)
+        };
+      }
+    }
+  }
+  image
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{atlas::GlyphPlacement, pack::PackMode};
+
+  #[test]
+  fn changed_tiles_are_counted_on_the_shared_area() {
+    let bg = Rgb([0, 0, 0]);
+    let old = RgbImage::from_pixel(30, 10, bg);
+    let mut new = RgbImage::from_pixel(20, 10, bg);
+    new.put_pixel(12, 3, Rgb([255, 255, 255]));
+    new.put_pixel(13, 3, Rgb([255, 255, 255]));
+    let placements = ['一', '中', '口']
+      .iter()
+      .enumerate()
+      .map(|(i, c)| GlyphPlacement {
+        char: *c,
+        tile_index: i,
+        cell_x: i as u32 * 10,
+        ..Default::default()
+      })
+      .collect::<Vec<_>>();
+    let mapping = Mapping::from_placements(
+      "font.png".to_string(),
+      10,
+      3,
+      PackMode::Grid,
+      3,
+      &placements,
+    );
+    let diff = compare_atlases(&old, &new, &mapping, 0);
+    assert_eq!((diff.compared, diff.skipped), (2, 1));
+    assert_eq!(diff.changed.len(), 1);
+    assert_eq!(
+      diff.changed[0].to_string(),
+      "Tile 1 '中' (U+4E2D): 2 of 100 pixels changed"
+    );
+
+    let image = diff_image(&old, &new, &diff);
+    assert_eq!(image.get_pixel(12, 3), &DIFF_COLOR);
+    assert_eq!(
+      image.get_pixel(10, 0),
+      &blend(DIFF_COLOR, bg, DIFF_TINT_OPACITY)
+    );
+    assert_eq!(image.get_pixel(0, 0), &bg);
+  }
+}
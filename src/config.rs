@@ -1,20 +1,915 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Result, anyhow, bail};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+  block_colors::BlockColors,
+  encode::{MAX_PALETTE_SIZE, PaletteRole},
+  render::{MAX_SLANT, ShadowDirection},
+};
+
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
+  /// Format version of the file, see [`CONFIG_VERSION`]. Files without one are version 1.
+  pub version: u32,
   pub img_bg_color: [u8; 3],
   pub char_color: [u8; 3],
   pub char_shadow_color: [u8; 3],
+  /// Gamma applied to the glyph coverage before thresholding (`v.powf(1.0 / gamma)`); above
+  /// 1.0 rescues thin strokes that would vanish.
+  pub coverage_gamma: f32,
+  /// Coverage threshold overrides for single dense or thin characters, e.g. `"齉" = 0.7`.
+  pub char_thresholds: BTreeMap<char, f32>,
+  /// Character rendered into the tile of a character no font has, e.g. `"〓"`, instead of an
+  /// empty tile. The tile still counts as missing.
+  pub missing_fallback_char: Option<char>,
+  /// Per-character fallbacks taking precedence over `missing_fallback_char`, e.g. `"戸" = "户"`.
+  pub missing_fallback_chars: BTreeMap<char, char>,
+  /// Cell-local glyph position `[x, y]` of characters (usually punctuation marks) in 10px
+  /// cells, replacing the built-in punctuation offsets. Keys are single characters.
+  pub punctuation_offsets_10px: BTreeMap<String, [u32; 2]>,
+  /// Like `punctuation_offsets_10px`, for 11px cells.
+  pub punctuation_offsets_11px: BTreeMap<String, [u32; 2]>,
+  /// Direction of the 10px drop shadow, e.g. `"bottom_left"`. The 11px outline is unaffected.
+  pub shadow_direction: ShadowDirection,
+  /// Single shadow offset `[dx, dy]` (±3px) replacing the fixed shadow of the cell size.
+  pub shadow_offset: Option<[i32; 2]>,
+  /// Shadow opacity 0-255, blended over the background below the shadow.
+  pub shadow_opacity: u8,
+  /// Vertical gradient over each glyph box, replacing the flat `char_color`.
+  pub char_gradient: Option<CharGradient>,
   pub chars_per_line: usize,
+  /// Largest atlas width (px) the tool is allowed to generate.
+  pub max_image_width: u32,
+  /// Largest atlas height (px) the tool is allowed to generate.
+  pub max_image_height: u32,
+  /// Background of the odd cells in `--char-background-mode checkerboard`. Derived from
+  /// `img_bg_color` when unset.
+  pub img_alt_bg_color: Option<[u8; 3]>,
+  /// Colors `--png-indexed` output is reduced to (with `--dither`), e.g. a console's 4-color
+  /// tile palette.
+  pub target_palette: Option<Vec<[u8; 3]>>,
+  /// Index order of the `--png-indexed` palette by color role, e.g.
+  /// `["transparent", "char", "shadow", "bg"]`; `transparent` is the `--color-key` color.
+  pub palette_order: Option<Vec<PaletteRole>>,
+  /// Color of `--cell-border-color` when it is given without a value.
+  pub grid_line_color: Option<[u8; 3]>,
+  /// Named colors for `--class-chars`, which duplicates characters as extra tiles in the class
+  /// color.
+  pub color_classes: BTreeMap<String, ColorClass>,
+  /// Character colors for `--char-color-per-block`, keyed by Unicode block label (`"CJK"`,
+  /// `"FullW"`, ...) or codepoint range (`"U+4E00-U+9FFF"`). Other characters keep `char_color`.
+  pub char_colors: BTreeMap<String, [u8; 3]>,
 }
 
+/// `[char_gradient]` colors of the top and bottom glyph rows.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct CharGradient {
+  pub top: [u8; 3],
+  pub bottom: [u8; 3],
+}
+
+/// A `[color_classes.<name>]` entry.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ColorClass {
+  pub color: [u8; 3],
+  /// Defaults to `char_shadow_color`.
+  #[serde(default)]
+  pub shadow_color: Option<[u8; 3]>,
+  /// Emit the class tiles for characters that do not appear in the script too.
+  #[serde(default)]
+  pub always_include: bool,
+  /// Draw the class tiles in fake bold (double-struck 1px apart).
+  #[serde(default)]
+  pub bold: bool,
+  /// Fake italic shear (px, 0-2) of the class tiles.
+  #[serde(default)]
+  pub slant: u32,
+}
+
+/// Older environment variables overriding single config fields, kept as aliases of their
+/// `CBFD_*` variable. Colors are `R,G,B`.
+pub const ENV_BG_COLOR: &str = "CBF_BG_COLOR";
+pub const ENV_CHAR_COLOR: &str = "CBF_CHAR_COLOR";
+pub const ENV_SHADOW_COLOR: &str = "CBF_SHADOW_COLOR";
+pub const ENV_CHARS_PER_LINE: &str = "CBF_CHARS_PER_LINE";
+/// Prefix of the variables overriding any config field by name, e.g. `CBFD_CHARS_PER_LINE=64`.
+/// `__` separates the keys of nested tables: `CBFD_CHAR_GRADIENT__TOP`.
+pub const ENV_FIELD_PREFIX: &str = "CBFD_";
+/// The `CBF_*` aliases and the `CBFD_*` variable each stands for; the `CBFD_*` variable wins when
+/// both are set.
+const ENV_ALIASES: [(&str, &str); 4] = [
+  (ENV_BG_COLOR, "CBFD_IMG_BG_COLOR"),
+  (ENV_CHAR_COLOR, "CBFD_CHAR_COLOR"),
+  (ENV_SHADOW_COLOR, "CBFD_CHAR_SHADOW_COLOR"),
+  (ENV_CHARS_PER_LINE, "CBFD_CHARS_PER_LINE"),
+];
+
+/// Channel distance between `img_bg_color` and the derived checkerboard color.
+const ALT_BG_COLOR_DELTA: u8 = 20;
+
 impl Default for Config {
   fn default() -> Self {
     Config {
+      version: CONFIG_VERSION,
       img_bg_color: [45, 45, 45],
       char_color: [250, 250, 245],
       char_shadow_color: [110, 110, 110],
+      coverage_gamma: 1.0,
+      char_thresholds: BTreeMap::new(),
+      missing_fallback_char: None,
+      missing_fallback_chars: BTreeMap::new(),
+      punctuation_offsets_10px: BTreeMap::new(),
+      punctuation_offsets_11px: BTreeMap::new(),
+      shadow_direction: ShadowDirection::BottomRight,
+      shadow_offset: None,
+      shadow_opacity: 255,
+      char_gradient: None,
       chars_per_line: 32,
+      max_image_width: 16384,
+      max_image_height: 16384,
+      img_alt_bg_color: None,
+      target_palette: None,
+      palette_order: None,
+      grid_line_color: None,
+      color_classes: BTreeMap::new(),
+      char_colors: BTreeMap::new(),
+    }
+  }
+}
+
+impl Config {
+  /// Reject values that would make rendering impossible.
+  pub fn validate(&self) -> Result<()> {
+    if self.chars_per_line == 0 {
+      bail!("[Error] Invalid config: chars_per_line must be greater than 0!");
+    }
+    if self.max_image_width == 0 || self.max_image_height == 0 {
+      bail!("[Error] Invalid config: max_image_width and max_image_height must be greater than 0!");
+    }
+    for (name, table) in [
+      ("punctuation_offsets_10px", &self.punctuation_offsets_10px),
+      ("punctuation_offsets_11px", &self.punctuation_offsets_11px),
+    ] {
+      if let Some(key) = table.keys().find(|key| key.chars().count() != 1) {
+        bail!(
+          "[Error] Invalid config: {} key \"{}\" must be a single character!",
+          name,
+          key
+        );
+      }
+    }
+    if let Some(palette) = &self.target_palette
+      && (palette.is_empty() || palette.len() > MAX_PALETTE_SIZE)
+    {
+      bail!(
+        "[Error] Invalid config: target_palette must have 1 to {} colors!",
+        MAX_PALETTE_SIZE
+      );
+    }
+    if let Some(order) = &self.palette_order {
+      if order.len() != PaletteRole::ALL.len()
+        || PaletteRole::ALL.iter().any(|role| !order.contains(role))
+      {
+        bail!(
+          "[Error] Invalid config: palette_order must name transparent, char, shadow and bg \
+           exactly once!"
+        );
+      }
+      if self.target_palette.is_some() {
+        bail!("[Error] Invalid config: palette_order cannot be combined with target_palette!");
+      }
+    }
+    if let Some((name, _)) = self
+      .color_classes
+      .iter()
+      .find(|(_, class)| class.slant > MAX_SLANT)
+    {
+      bail!(
+        "[Error] Invalid config: the slant of color class '{}' must be at most {}!",
+        name,
+        MAX_SLANT
+      );
+    }
+    BlockColors::parse(&self.char_colors)?;
+    Ok(())
+  }
+
+  /// Config built from the `CBF_*` environment variables on top of the defaults, `None` when
+  /// none is set.
+  pub fn from_env() -> Result<Option<Config>> {
+    Config::from_lookup(|name| std::env::var(name).ok())
+  }
+
+  /// [`Config::from_env`] with the variables looked up by name.
+  fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Option<Config>> {
+    let vars = alias_vars(lookup);
+    if vars.is_empty() {
+      return Ok(None);
+    }
+    apply_env_fields(toml::Table::new(), vars).map(Some)
+  }
+
+  /// Apply the `CBF_*` environment variables on top of this config (file or defaults).
+  /// Returns whether any variable was set.
+  pub fn override_from_env(&mut self) -> Result<bool> {
+    self.override_from(|name| std::env::var(name).ok())
+  }
+
+  /// Apply overrides looked up by environment variable name, through the `CBFD_*` field each
+  /// one is an alias of. Malformed values are errors.
+  pub fn override_from(&mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<bool> {
+    let vars = alias_vars(lookup);
+    if vars.is_empty() {
+      return Ok(false);
+    }
+    *self = apply_env_fields(toml::Table::try_from(&*self)?, vars)?;
+    Ok(true)
+  }
+
+  /// The `punctuation_offsets_*` table of a cell size, keyed by character.
+  pub fn punctuation_offsets(&self, font_size: u32) -> BTreeMap<char, (u32, u32)> {
+    let table = if font_size == 11 {
+      &self.punctuation_offsets_11px
+    } else {
+      &self.punctuation_offsets_10px
+    };
+    table
+      .iter()
+      .filter_map(|(key, [x, y])| Some((key.chars().next()?, (*x, *y))))
+      .collect()
+  }
+
+  /// Checkerboard color: `img_alt_bg_color`, or `img_bg_color` with every channel moved by 20
+  /// (lighter for dark channels, darker for light ones).
+  pub fn alt_bg_color(&self) -> [u8; 3] {
+    self.img_alt_bg_color.unwrap_or_else(|| {
+      self.img_bg_color.map(|channel| {
+        if channel < 128 {
+          channel.saturating_add(ALT_BG_COLOR_DELTA)
+        } else {
+          channel.saturating_sub(ALT_BG_COLOR_DELTA)
+        }
+      })
+    })
+  }
+}
+
+/// Widest atlas (px) `--verify-config` accepts, the limit of 16-bit texture sizes.
+pub const MAX_VERIFIED_WIDTH: u64 = 65535;
+
+/// Problems `--verify-config` found in the config combined with the command line, as
+/// `[Error]`/`[Warning]` lines naming the field.
+#[derive(Debug, Default, PartialEq)]
+pub struct ConfigCheck {
+  pub errors: Vec<String>,
+  pub warnings: Vec<String>,
+}
+
+impl Config {
+  /// Check the config against the `font_size` cell size and `--color-key` of the command line.
+  pub fn check_with(&self, font_size: u32, color_key: Option<[u8; 3]>) -> ConfigCheck {
+    let mut check = ConfigCheck::default();
+    let width = self.chars_per_line as u64 * font_size as u64;
+    if width > MAX_VERIFIED_WIDTH {
+      check.errors.push(format!(
+        "[Error] chars_per_line: {} cells of {}px make a {}px wide atlas, wider than {}px!",
+        self.chars_per_line, font_size, width, MAX_VERIFIED_WIDTH
+      ));
+    }
+    let (bg_field, bg_color) = match color_key {
+      Some(color_key) => ("--color-key", color_key),
+      None => ("img_bg_color", self.img_bg_color),
+    };
+    if bg_color == self.char_color {
+      check.errors.push(format!(
+        "[Error] {}: {:?} is the same as char_color, the characters would be invisible!",
+        bg_field, bg_color
+      ));
+    }
+    if self.char_shadow_color == self.char_color {
+      check.warnings.push(format!(
+        "[Warning] char_shadow_color: {:?} is the same as char_color, the shadow cannot be told \
+         apart from the characters.",
+        self.char_shadow_color
+      ));
+    } else if self.char_shadow_color == bg_color {
+      check.warnings.push(format!(
+        "[Warning] char_shadow_color: {:?} is the same as {}, the shadow is invisible.",
+        self.char_shadow_color, bg_field
+      ));
+    }
+    check
+  }
+}
+
+/// Config format written by this version of the tool. Version 2 added the `version` key itself.
+pub const CONFIG_VERSION: u32 = 2;
+
+/// Keys renamed since version 1, as `(old, new)`; none so far.
+const RENAMED_KEYS: &[(&str, &str)] = &[];
+
+/// Error for a config file written by a newer version of the tool.
+fn newer_version_error(version: impl std::fmt::Display) -> anyhow::Error {
+  anyhow!(
+    "[Error] config.toml is version {}, but this tool only knows versions up to {}! Please \
+     upgrade chinese_bitmap_font_demo.",
+    version,
+    CONFIG_VERSION
+  )
+}
+
+/// Format version of a parsed config file. Versions too large for a `u32` are newer than any
+/// this tool knows.
+fn table_version(table: &toml::Table) -> Result<u32> {
+  match table.get("version") {
+    None => Ok(1),
+    Some(toml::Value::Integer(version)) if *version >= 1 => {
+      u32::try_from(*version).map_err(|_| newer_version_error(version))
+    }
+    Some(value) => bail!(
+      "[Error] Invalid config: version {} is not a version number!",
+      value
+    ),
+  }
+}
+
+/// Rename `renames` keys of `table`, its `[default]` table and its profiles. Returns the old
+/// names found.
+fn rename_keys(table: &mut toml::Table, renames: &[(&str, &str)]) -> Vec<String> {
+  let mut tables = vec![table];
+  let mut found = Vec::new();
+  while let Some(table) = tables.pop() {
+    for (old, new) in renames {
+      if let Some(value) = table.remove(*old) {
+        table.entry(*new).or_insert(value);
+        found.push(old.to_string());
+      }
+    }
+    for (key, value) in table.iter_mut() {
+      match (key.as_str(), value) {
+        ("default", toml::Value::Table(inner)) => tables.push(inner),
+        ("profile", toml::Value::Table(profiles)) => tables.extend(
+          profiles
+            .iter_mut()
+            .filter_map(|(_, profile)| profile.as_table_mut()),
+        ),
+        _ => {}
+      }
     }
   }
+  found
+}
+
+/// Bring a parsed config file of an older version up to [`CONFIG_VERSION`] in memory, renaming
+/// moved keys; new fields get their defaults when deserializing. Returns the `[Warning]`/`[Note]`
+/// lines to print. Files of a newer version are errors.
+pub fn migrate_table(table: &mut toml::Table) -> Result<Vec<String>> {
+  if table.is_empty() {
+    return Ok(Vec::new());
+  }
+  let version = table_version(table)?;
+  if version > CONFIG_VERSION {
+    return Err(newer_version_error(version));
+  }
+  if version == CONFIG_VERSION {
+    return Ok(Vec::new());
+  }
+  let mut messages = rename_keys(table, RENAMED_KEYS)
+    .into_iter()
+    .map(|old| {
+      let new = RENAMED_KEYS
+        .iter()
+        .find(|(name, _)| *name == old)
+        .unwrap()
+        .1;
+      format!(
+        "[Warning] Config key '{}' is deprecated, use '{}' instead.",
+        old, new
+      )
+    })
+    .collect::<Vec<_>>();
+  table.insert(
+    "version".to_string(),
+    toml::Value::Integer(CONFIG_VERSION.into()),
+  );
+  messages.push(format!(
+    "[Note] config.toml is version {}, migrated to version {} for this run. Use \
+     --migrate-config to update the file.",
+    version, CONFIG_VERSION
+  ));
+  Ok(messages)
+}
+
+/// `--migrate-config`: the config file text at [`CONFIG_VERSION`], editing only the renamed key
+/// lines and the `version` line so values and comments are kept. `None` when it is up to date.
+pub fn migrate_text(text: &str) -> Result<Option<String>> {
+  let table: toml::Table = toml::from_str(text)?;
+  if table_version(&table)? == CONFIG_VERSION {
+    return Ok(None);
+  }
+  // Checks the version and the renames.
+  migrate_table(&mut table.clone())?;
+  let key_of = |line: &str| {
+    let line = line.trim_start();
+    let end = line.find(|c: char| c == '=' || c.is_whitespace())?;
+    line[end..]
+      .trim_start()
+      .starts_with('=')
+      .then(|| line[..end].to_string())
+  };
+  let mut lines = Vec::new();
+  let mut top_level = true;
+  let mut has_version = false;
+  for line in text.lines() {
+    if line.trim_start().starts_with('[') {
+      top_level = false;
+    }
+    match key_of(line) {
+      Some(key) if top_level && key == "version" => {
+        lines.push(format!("version = {}", CONFIG_VERSION));
+        has_version = true;
+      }
+      Some(key) => match RENAMED_KEYS.iter().find(|(old, _)| *old == key) {
+        Some((old, new)) => lines.push(line.replacen(old, new, 1)),
+        None => lines.push(line.to_string()),
+      },
+      None => lines.push(line.to_string()),
+    }
+  }
+  if !has_version {
+    lines.insert(0, format!("version = {}", CONFIG_VERSION));
+  }
+  Ok(Some(lines.join("\n") + "\n"))
+}
+
+/// Commented `[profile.<name>]` example appended to the default config file.
+pub const EXAMPLE_PROFILE: &str = "
+# Named profiles override the values above (or those of a [default] table) and are selected
+# with --profile <NAME>. Values a profile leaves out keep the base value.
+# [profile.example]
+# img_bg_color = [0, 0, 0]
+# chars_per_line = 16
+";
+
+/// Content of the config file written on the first run.
+pub fn default_config_file() -> Result<String> {
+  Ok(toml::to_string_pretty(&Config::default())? + EXAMPLE_PROFILE)
+}
+
+/// Merge `overlay` into `base`, table by table.
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+  for (key, value) in overlay {
+    match (base.get_mut(&key), value) {
+      (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge_tables(base, overlay),
+      (_, value) => {
+        base.insert(key, value);
+      }
+    }
+  }
+}
+
+/// The config table of `profile`: the top-level values and the `[default]` table, overridden by
+/// the `[profile.<name>]` table. Unknown profiles are errors listing the available ones.
+pub fn select_profile(mut table: toml::Table, profile: Option<&str>) -> Result<toml::Table> {
+  let profiles = match table.remove("profile") {
+    Some(toml::Value::Table(profiles)) => profiles,
+    Some(_) => bail!("[Error] Invalid config: profile must be a table of [profile.<name>] tables!"),
+    None => toml::Table::new(),
+  };
+  if let Some(toml::Value::Table(default)) = table.remove("default") {
+    merge_tables(&mut table, default);
+  }
+  if let Some(name) = profile {
+    match profiles.get(name) {
+      Some(toml::Value::Table(overlay)) => merge_tables(&mut table, overlay.clone()),
+      Some(_) => bail!("[Error] Invalid config: profile.{} must be a table!", name),
+      None => {
+        let available = profiles.keys().cloned().collect::<Vec<_>>();
+        bail!(
+          "[Error] Unknown config profile '{}'! Available profiles: {}",
+          name,
+          if available.is_empty() {
+            "(none)".to_string()
+          } else {
+            available.join(", ")
+          }
+        );
+      }
+    }
+  }
+  Ok(table)
+}
+
+/// TOML value of a `CBFD_*` variable: `#RRGGBB` is a color array, anything else that is not a
+/// TOML value (`64`, `[1, 2, 3]`, `true`, `"text"`) is taken as a plain string.
+fn env_field_value(value: &str) -> toml::Value {
+  if let Some(hex) = value.trim().strip_prefix('#')
+    && hex.len() == 6
+    && let Ok(rgb) = u32::from_str_radix(hex, 16)
+  {
+    return toml::Value::Array(
+      [16, 8, 0]
+        .iter()
+        .map(|shift| toml::Value::Integer(i64::from((rgb >> shift) & 0xFF)))
+        .collect(),
+    );
+  }
+  toml::from_str::<toml::Table>(&format!("value = {}", value))
+    .ok()
+    .and_then(|mut table| table.remove("value"))
+    .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
+/// Whether the nested `path` of keys exists in `table`.
+fn has_path(table: &toml::Table, path: &[String]) -> bool {
+  match path {
+    [] => true,
+    [key, rest @ ..] => match table.get(key) {
+      Some(toml::Value::Table(inner)) => has_path(inner, rest),
+      Some(_) => rest.is_empty(),
+      None => false,
+    },
+  }
+}
+
+/// The config of `table` with the `CBFD_*` variables of `vars` (and their `CBF_*` aliases)
+/// applied in name order. Each variable must name an existing field and hold a valid value for
+/// it.
+pub fn apply_env_fields(
+  mut table: toml::Table,
+  vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<Config> {
+  let vars = vars.into_iter().collect::<BTreeMap<_, _>>();
+  // `CBFD_*` name -> (variable named in errors, value)
+  let mut fields = vars
+    .iter()
+    .filter(|(name, _)| name.starts_with(ENV_FIELD_PREFIX))
+    .map(|(name, value)| (name.clone(), (name.clone(), value.clone())))
+    .collect::<BTreeMap<_, _>>();
+  for (alias, field) in ENV_ALIASES {
+    let Some(value) = vars.get(alias) else {
+      continue;
+    };
+    if fields.contains_key(field) {
+      continue;
+    }
+    let value = if alias == ENV_CHARS_PER_LINE {
+      value.trim().to_string()
+    } else {
+      match parse_rgb(value) {
+        Ok(rgb) => format!("{:?}", rgb),
+        Err(e) => bail!("[Error] Invalid {}: {}", alias, e),
+      }
+    };
+    fields.insert(field.to_string(), (alias.to_string(), value));
+  }
+  let mut paths = Vec::new();
+  // The variable since which the table no longer parses; a later one may complete it, e.g. the
+  // `top` of a gradient whose `bottom` came first.
+  let mut broken_by = None;
+  for (field, (name, value)) in &fields {
+    let path = field[ENV_FIELD_PREFIX.len()..]
+      .split("__")
+      .map(str::to_lowercase)
+      .collect::<Vec<_>>();
+    let (last, parents) = path.split_last().unwrap();
+    let mut inner = &mut table;
+    for key in parents {
+      let entry = inner
+        .entry(key.clone())
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+      inner = match entry {
+        toml::Value::Table(inner) => inner,
+        _ => bail!("[Error] Invalid {}: {} is not a table!", name, key),
+      };
+    }
+    inner.insert(last.clone(), env_field_value(value));
+    match table.clone().try_into::<Config>() {
+      Ok(_) => broken_by = None,
+      Err(_) => broken_by = broken_by.or(Some(name)),
+    }
+    paths.push((name, path));
+  }
+  let config: Config = match table.try_into() {
+    Ok(config) => config,
+    Err(e) => bail!(
+      "[Error] Invalid {}: {}",
+      broken_by.map_or("config", |name| name.as_str()),
+      e.message()
+    ),
+  };
+  // Unknown fields are dropped by the round trip.
+  let round_trip = toml::Table::try_from(&config)?;
+  if let Some((name, _)) = paths.iter().find(|(_, path)| !has_path(&round_trip, path)) {
+    bail!("[Error] Invalid {}: the config has no such field!", name);
+  }
+  Ok(config)
+}
+
+/// The `CBF_*` aliases `lookup` finds, as `(name, value)` variables for [`apply_env_fields`].
+fn alias_vars(lookup: impl Fn(&str) -> Option<String>) -> Vec<(String, String)> {
+  ENV_ALIASES
+    .iter()
+    .filter_map(|(alias, _)| lookup(alias).map(|value| (alias.to_string(), value)))
+    .collect()
+}
+
+/// Parse an `R,G,B` command line value.
+pub fn parse_rgb(value: &str) -> Result<[u8; 3], String> {
+  let channels = value
+    .split(',')
+    .map(|channel| channel.trim().parse::<u8>())
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| format!("invalid color channel: {e}"))?;
+  channels
+    .try_into()
+    .map_err(|_| "expected three comma-separated values: R,G,B".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn profiles_override_the_default_table() {
+    let text = "chars_per_line = 20\n[default]\nimg_bg_color = [1, 2, 3]\n\
+                [default.char_thresholds]\n\"齉\" = 0.7\n\
+                [profile.a]\nchars_per_line = 16\n[profile.a.char_thresholds]\n\"鬱\" = 0.6\n\
+                [profile.b]\nimg_bg_color = [0, 0, 0]\n";
+    let config = |profile| -> Config {
+      select_profile(toml::from_str(text).unwrap(), profile)
+        .unwrap()
+        .try_into()
+        .unwrap()
+    };
+    let base = config(None);
+    assert_eq!((base.chars_per_line, base.img_bg_color), (20, [1, 2, 3]));
+    let a = config(Some("a"));
+    assert_eq!((a.chars_per_line, a.img_bg_color), (16, [1, 2, 3]));
+    assert_eq!(a.char_thresholds.len(), 2);
+    assert_eq!(config(Some("b")).img_bg_color, [0, 0, 0]);
+    let error = select_profile(toml::from_str(text).unwrap(), Some("c")).unwrap_err();
+    assert_eq!(
+      error.to_string(),
+      "[Error] Unknown config profile 'c'! Available profiles: a, b"
+    );
+  }
+
+  #[test]
+  fn env_fields_override_any_config_field() {
+    let vars = |pairs: &[(&str, &str)]| {
+      pairs
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect::<Vec<_>>()
+    };
+    let table = toml::from_str("chars_per_line = 20").unwrap();
+    let config = apply_env_fields(
+      table,
+      vars(&[
+        ("CBFD_CHARS_PER_LINE", "64"),
+        ("CBFD_CHAR_COLOR", "#FF8001"),
+        ("CBFD_IMG_ALT_BG_COLOR", "[1, 2, 3]"),
+        ("CBFD_MISSING_FALLBACK_CHAR", "〓"),
+        ("CBFD_CHAR_GRADIENT__TOP", "#000000"),
+        ("CBFD_CHAR_GRADIENT__BOTTOM", "#FFFFFF"),
+        ("CBF_CHAR_COLOR", "0,0,0"),
+      ]),
+    )
+    .unwrap();
+    assert_eq!(config.chars_per_line, 64);
+    assert_eq!(config.char_color, [255, 128, 1]);
+    assert_eq!(config.img_alt_bg_color, Some([1, 2, 3]));
+    assert_eq!(config.missing_fallback_char, Some('〓'));
+    assert_eq!(
+      config.char_gradient.map(|gradient| gradient.bottom),
+      Some([255; 3])
+    );
+
+    for (name, value) in [
+      ("CBFD_CHARS_PER_LINE", "many"),
+      ("CBFD_CHAR_COLOR", "#FFF"),
+      ("CBFD_CHARS_PER_LIN", "64"),
+    ] {
+      let Err(error) = apply_env_fields(toml::Table::new(), vars(&[(name, value)])) else {
+        panic!("{} = {} was accepted", name, value);
+      };
+      assert!(error.to_string().contains(name), "{}", error);
+    }
+  }
+
+  #[test]
+  fn old_configs_are_migrated_and_newer_ones_rejected() {
+    let mut table: toml::Table = toml::from_str("chars_per_line = 20").unwrap();
+    let messages = migrate_table(&mut table).unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(table["version"].as_integer(), Some(CONFIG_VERSION as i64));
+    assert!(migrate_table(&mut table).unwrap().is_empty());
+    let mut newer: toml::Table = toml::from_str("version = 99").unwrap();
+    assert!(migrate_table(&mut newer).is_err());
+    // 2^32 + 2 must not wrap around to version 2.
+    let mut huge: toml::Table = toml::from_str("version = 4294967298").unwrap();
+    let Err(error) = migrate_table(&mut huge) else {
+      panic!("version 4294967298 was accepted");
+    };
+    assert!(error.to_string().contains("version 4294967298"));
+
+    let text = "# my colors\nchars_per_line = 20 # wide\n\n[profile.a]\nversion = 1\n";
+    assert_eq!(
+      migrate_text(text).unwrap().unwrap(),
+      "version = 2\n# my colors\nchars_per_line = 20 # wide\n\n[profile.a]\nversion = 1\n"
+    );
+    assert_eq!(migrate_text("version = 2\n").unwrap(), None);
+  }
+
+  #[test]
+  fn renamed_keys_move_in_profiles_too() {
+    let mut table: toml::Table =
+      toml::from_str("old = 1\n[default]\nold = 2\n[profile.a]\nold = 3\nnew = 4\n").unwrap();
+    assert_eq!(rename_keys(&mut table, &[("old", "new")]).len(), 3);
+    assert_eq!(table["new"].as_integer(), Some(1));
+    assert_eq!(table["default"]["new"].as_integer(), Some(2));
+    assert_eq!(table["profile"]["a"]["new"].as_integer(), Some(4));
+    assert!(
+      !table["profile"]["a"]
+        .as_table()
+        .unwrap()
+        .contains_key("old")
+    );
+  }
+
+  #[test]
+  fn default_config_file_parses_to_the_defaults() {
+    let table = toml::from_str(&default_config_file().unwrap()).unwrap();
+    let config: Config = select_profile(table, None).unwrap().try_into().unwrap();
+    assert_eq!(config.chars_per_line, Config::default().chars_per_line);
+  }
+
+  #[test]
+  fn zero_chars_per_line_is_rejected() {
+    let config = Config {
+      chars_per_line: 0,
+      ..Config::default()
+    };
+    assert!(config.validate().is_err());
+    assert!(Config::default().validate().is_ok());
+  }
+
+  #[test]
+  fn palette_order_names_every_role_once() {
+    let config =
+      |order: &str| -> Config { toml::from_str(&format!("palette_order = {}", order)).unwrap() };
+    assert!(
+      config(r#"["transparent", "char", "shadow", "bg"]"#)
+        .validate()
+        .is_ok()
+    );
+    assert!(config(r#"["char", "shadow", "bg"]"#).validate().is_err());
+    assert!(
+      config(r#"["char", "char", "shadow", "bg"]"#)
+        .validate()
+        .is_err()
+    );
+    assert!(toml::from_str::<Config>(r#"palette_order = ["outline"]"#).is_err());
+  }
+
+  #[test]
+  fn config_is_checked_against_the_command_line() {
+    assert_eq!(
+      Config::default().check_with(10, None),
+      ConfigCheck::default()
+    );
+    let config = Config {
+      chars_per_line: 6000,
+      char_shadow_color: [250, 250, 245],
+      ..Config::default()
+    };
+    let check = config.check_with(11, Some([250, 250, 245]));
+    assert_eq!(check.errors.len(), 2);
+    assert!(check.errors[0].starts_with("[Error] chars_per_line"));
+    assert!(check.errors[1].starts_with("[Error] --color-key"));
+    assert_eq!(check.warnings.len(), 1);
+  }
+
+  #[test]
+  fn env_overrides_file_values() {
+    let env = |name: &str| match name {
+      ENV_BG_COLOR => Some("1, 2, 3".to_string()),
+      ENV_CHARS_PER_LINE => Some("16".to_string()),
+      _ => None,
+    };
+    let mut config = Config {
+      char_color: [9, 9, 9],
+      ..Config::default()
+    };
+    assert!(config.override_from(env).unwrap());
+    assert_eq!(config.img_bg_color, [1, 2, 3]);
+    assert_eq!(config.char_color, [9, 9, 9]);
+    assert_eq!(config.chars_per_line, 16);
+
+    assert!(!config.override_from(|_| None).unwrap());
+    assert!(
+      config
+        .override_from(|name| (name == ENV_SHADOW_COLOR).then(|| "1,2".to_string()))
+        .is_err()
+    );
+  }
+
+  #[test]
+  fn config_from_env_applies_the_cbf_variables() {
+    assert!(Config::from_lookup(|_| None).unwrap().is_none());
+    let config = Config::from_lookup(|name| (name == ENV_CHAR_COLOR).then(|| "1,2,3".to_string()))
+      .unwrap()
+      .unwrap();
+    assert_eq!(config.char_color, [1, 2, 3]);
+    assert_eq!(config.chars_per_line, Config::default().chars_per_line);
+    assert!(
+      Config::from_lookup(|name| (name == ENV_CHARS_PER_LINE).then(|| "x".to_string())).is_err()
+    );
+  }
+
+  #[test]
+  fn legacy_env_variables_are_aliases() {
+    let vars = |pairs: &[(&str, &str)]| {
+      pairs
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect::<Vec<_>>()
+    };
+    let table: toml::Table = toml::from_str("char_color = [9, 9, 9]").unwrap();
+    let config = apply_env_fields(
+      table.clone(),
+      vars(&[(ENV_BG_COLOR, "1, 2, 3"), (ENV_CHARS_PER_LINE, "16")]),
+    )
+    .unwrap();
+    assert_eq!(config.img_bg_color, [1, 2, 3]);
+    assert_eq!(config.char_color, [9, 9, 9]);
+    assert_eq!(config.chars_per_line, 16);
+
+    let config = apply_env_fields(
+      table.clone(),
+      vars(&[(ENV_CHAR_COLOR, "1,1,1"), ("CBFD_CHAR_COLOR", "#020202")]),
+    )
+    .unwrap();
+    assert_eq!(config.char_color, [2, 2, 2]);
+
+    for (name, value) in [(ENV_SHADOW_COLOR, "1,2"), (ENV_CHARS_PER_LINE, "x")] {
+      let Err(error) = apply_env_fields(table.clone(), vars(&[(name, value)])) else {
+        panic!("{}={} was accepted", name, value);
+      };
+      assert!(
+        error
+          .to_string()
+          .starts_with(&format!("[Error] Invalid {}", name))
+      );
+    }
+  }
+
+  #[test]
+  fn color_classes_are_parsed() {
+    let config: Config =
+      toml::from_str("[color_classes.names]\ncolor = [255, 200, 0]\nalways_include = true\n")
+        .unwrap();
+    let class = &config.color_classes["names"];
+    assert_eq!(class.color, [255, 200, 0]);
+    assert_eq!(class.shadow_color, None);
+    assert!(class.always_include);
+    assert_eq!(config.shadow_direction, ShadowDirection::BottomRight);
+
+    let config: Config = toml::from_str("shadow_direction = \"top_left\"\n").unwrap();
+    assert_eq!(config.shadow_direction, ShadowDirection::TopLeft);
+
+    let config: Config = toml::from_str("[char_thresholds]\n\"齉\" = 0.7\n").unwrap();
+    assert_eq!(config.char_thresholds[&'齉'], 0.7);
+  }
+
+  #[test]
+  fn punctuation_offsets_follow_the_cell_size() {
+    let mut config = Config::default();
+    config
+      .punctuation_offsets_11px
+      .insert("，".to_string(), [1, 6]);
+    assert!(config.validate().is_ok());
+    assert!(config.punctuation_offsets(10).is_empty());
+    assert_eq!(config.punctuation_offsets(11)[&'，'], (1, 6));
+
+    config
+      .punctuation_offsets_10px
+      .insert("，，".to_string(), [0, 0]);
+    assert!(config.validate().is_err());
+  }
+
+  #[test]
+  fn alt_bg_color_is_derived_or_configured() {
+    let mut config = Config {
+      img_bg_color: [45, 250, 128],
+      ..Config::default()
+    };
+    assert_eq!(config.alt_bg_color(), [65, 230, 108]);
+    config.img_alt_bg_color = Some([1, 2, 3]);
+    assert_eq!(config.alt_bg_color(), [1, 2, 3]);
+  }
 }
@@ -0,0 +1,88 @@
+use ab_glyph::Font;
+use image::{Rgb, RgbImage};
+
+/// Codepoints per block of the coverage image.
+pub const BLOCK_SIZE: u32 = 256;
+/// Blocks per row of the coverage image; the 256 BMP blocks make a 16x16 grid.
+pub const BLOCKS_PER_ROW: u32 = 16;
+/// Number of blocks in the Basic Multilingual Plane.
+const BMP_BLOCKS: u32 = 0x10000 / BLOCK_SIZE;
+
+/// How much of a block the fallback chain has glyphs for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockCoverage {
+  Full,
+  Partial,
+  None,
+}
+
+impl BlockCoverage {
+  /// White, gray or black pixel of the coverage image.
+  fn color(self) -> Rgb<u8> {
+    match self {
+      BlockCoverage::Full => Rgb([255, 255, 255]),
+      BlockCoverage::Partial => Rgb([128, 128, 128]),
+      BlockCoverage::None => Rgb([0, 0, 0]),
+    }
+  }
+}
+
+/// Coverage of every BMP block by any of `fonts`. Surrogate codepoints are no characters and
+/// are skipped, so the surrogate blocks count as not covered.
+pub fn bmp_coverage<F: Font>(fonts: &[F]) -> Vec<BlockCoverage> {
+  (0..BMP_BLOCKS)
+    .map(|block| {
+      let (mut covered, mut total) = (0, 0);
+      for c in (block * BLOCK_SIZE..(block + 1) * BLOCK_SIZE).filter_map(char::from_u32) {
+        total += 1;
+        covered += fonts.iter().any(|font| font.glyph_id(c).0 != 0) as u32;
+      }
+      match covered {
+        0 => BlockCoverage::None,
+        _ if covered == total => BlockCoverage::Full,
+        _ => BlockCoverage::Partial,
+      }
+    })
+    .collect()
+}
+
+/// `--output-coverage-image`: one `scale`×`scale` square per block, [`BLOCKS_PER_ROW`] blocks
+/// per row in codepoint order.
+pub fn coverage_image(blocks: &[BlockCoverage], scale: u32) -> RgbImage {
+  let rows = (blocks.len() as u32).div_ceil(BLOCKS_PER_ROW);
+  RgbImage::from_fn(BLOCKS_PER_ROW * scale, rows * scale, |x, y| {
+    let block = (y / scale * BLOCKS_PER_ROW + x / scale) as usize;
+    blocks
+      .get(block)
+      .map_or(BlockCoverage::None, |coverage| *coverage)
+      .color()
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ab_glyph::FontVec;
+
+  #[test]
+  fn blocks_are_partial_where_the_font_has_some_glyphs() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/test_font.ttf");
+    let font = FontVec::try_from_vec(std::fs::read(path).unwrap()).unwrap();
+    let blocks = bmp_coverage(&[font]);
+    assert_eq!(blocks.len(), 256);
+    assert_eq!(blocks[0x4E], BlockCoverage::Partial);
+    assert_eq!(blocks[0xD8], BlockCoverage::None);
+  }
+
+  #[test]
+  fn each_block_is_a_scaled_square() {
+    let mut blocks = vec![BlockCoverage::None; 32];
+    blocks[0] = BlockCoverage::Full;
+    blocks[17] = BlockCoverage::Partial;
+    let image = coverage_image(&blocks, 4);
+    assert_eq!(image.dimensions(), (64, 8));
+    assert_eq!(image.get_pixel(3, 3), &Rgb([255, 255, 255]));
+    assert_eq!(image.get_pixel(4, 0), &Rgb([0, 0, 0]));
+    assert_eq!(image.get_pixel(7, 7), &Rgb([128, 128, 128]));
+  }
+}
@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+
+use ab_glyph::Font;
+use serde::Serialize;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{mapping::codepoint_label, warning::RenderWarning};
+
+/// Normalization forms checked by `--duplicate-check`.
+pub const NORMALIZATION_FORMS: [&str; 4] = ["NFC", "NFD", "NFKC", "NFKD"];
+
+fn normalize(c: char, form: &str) -> String {
+  let text = c.to_string();
+  match form {
+    "NFC" => text.nfc().collect(),
+    "NFD" => text.nfd().collect(),
+    "NFKC" => text.nfkc().collect(),
+    _ => text.nfkd().collect(),
+  }
+}
+
+/// `U+XXXX` labels of every character of `text`, space separated.
+fn codepoint_labels(text: &str) -> String {
+  text
+    .chars()
+    .map(codepoint_label)
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+/// A character that some normalization forms turn into different text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NormalizationChange {
+  pub char: char,
+  /// Forms changing the character, with their result.
+  pub forms: Vec<(&'static str, String)>,
+}
+
+/// Characters of `chars` that are not stable under every normalization form.
+pub fn normalization_changes(chars: &[char]) -> Vec<NormalizationChange> {
+  chars
+    .iter()
+    .filter_map(|c| {
+      let forms = NORMALIZATION_FORMS
+        .into_iter()
+        .map(|form| (form, normalize(*c, form)))
+        .filter(|(_, normalized)| *normalized != c.to_string())
+        .collect::<Vec<_>>();
+      (!forms.is_empty()).then_some(NormalizationChange { char: *c, forms })
+    })
+    .collect()
+}
+
+/// Groups of distinct characters of `chars` sharing the same NFKC form, e.g. a CJK
+/// Compatibility Ideograph next to its canonical ideograph.
+pub fn equivalent_groups(chars: &[char]) -> Vec<Vec<char>> {
+  let mut groups = BTreeMap::<String, Vec<char>>::new();
+  for c in chars {
+    groups.entry(normalize(*c, "NFKC")).or_default().push(*c);
+  }
+  groups
+    .into_values()
+    .filter(|group| group.len() > 1)
+    .collect()
+}
+
+/// `[Warning]` lines of the `--duplicate-check` findings.
+pub fn duplicate_warnings(chars: &[char]) -> Vec<String> {
+  let mut warnings = normalization_changes(chars)
+    .into_iter()
+    .map(|change| {
+      let forms = change
+        .forms
+        .iter()
+        .map(|(form, normalized)| {
+          format!(
+            "{} gives '{}' ({})",
+            form,
+            normalized,
+            codepoint_labels(normalized)
+          )
+        })
+        .collect::<Vec<_>>();
+      format!(
+        "[Warning] '{}' ({}) changes under normalization: {}.",
+        change.char,
+        codepoint_label(change.char),
+        forms.join(", ")
+      )
+    })
+    .collect::<Vec<_>>();
+  for group in equivalent_groups(chars) {
+    let labels = group
+      .iter()
+      .map(|c| format!("'{}' ({})", c, codepoint_label(*c)))
+      .collect::<Vec<_>>();
+    warnings.push(format!(
+      "[Warning] {} are visually identical variants of one character.",
+      labels.join(" and ")
+    ));
+  }
+  warnings
+}
+
+/// Distinct characters drawn with one glyph of a font, e.g. 户/戶/戸 in a font unifying them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SharedGlyph {
+  /// Index into the `--font` list of the first font having the characters.
+  pub font_index: usize,
+  pub glyph_id: u16,
+  pub chars: Vec<char>,
+}
+
+/// Group `chars` by the (font, glyph id) they resolve to, keeping the groups of more than one
+/// character. `glyph_of` is `None` for characters no font has.
+fn group_by_glyph(
+  chars: &[char],
+  glyph_of: impl Fn(char) -> Option<(usize, u16)>,
+) -> Vec<SharedGlyph> {
+  let mut groups = BTreeMap::<(usize, u16), Vec<char>>::new();
+  for c in chars {
+    if let Some(glyph) = glyph_of(*c) {
+      groups.entry(glyph).or_default().push(*c);
+    }
+  }
+  groups
+    .into_iter()
+    .filter(|(_, group)| group.len() > 1)
+    .map(|((font_index, glyph_id), chars)| SharedGlyph {
+      font_index,
+      glyph_id,
+      chars,
+    })
+    .collect()
+}
+
+/// Characters of `chars` sharing a glyph of the first font of the fallback chain having them.
+pub fn shared_glyphs<F: Font>(fonts: &[F], chars: &[char]) -> Vec<SharedGlyph> {
+  group_by_glyph(chars, |c| {
+    fonts.iter().enumerate().find_map(|(font_index, font)| {
+      let glyph_id = font.glyph_id(c).0;
+      (glyph_id != 0).then_some((font_index, glyph_id))
+    })
+  })
+}
+
+/// Warning about a group of characters sharing one glyph.
+pub fn shared_glyph_warning(shared: &SharedGlyph) -> RenderWarning {
+  RenderWarning::SharedGlyph {
+    chars: shared.chars.iter().collect(),
+    codepoint: shared.chars[0] as u32,
+    font_index: shared.font_index,
+    glyph_id: shared.glyph_id,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn compatibility_ideographs_are_reported() {
+    let chars = ['一', '豈', '\u{f900}'];
+    let changes = normalization_changes(&chars);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].char, '\u{f900}');
+    assert_eq!(changes[0].forms.len(), 4);
+    assert_eq!(equivalent_groups(&chars), [vec!['豈', '\u{f900}']]);
+    assert_eq!(duplicate_warnings(&['一', '中']), Vec::<String>::new());
+  }
+
+  #[test]
+  fn chars_sharing_a_glyph_are_grouped_per_font() {
+    let glyphs = BTreeMap::from([
+      ('户', (0, 7)),
+      ('戶', (0, 7)),
+      ('戸', (1, 7)),
+      ('一', (0, 3)),
+    ]);
+    let shared = group_by_glyph(&['户', '戶', '戸', '一', '中'], |c| {
+      glyphs.get(&c).copied()
+    });
+    assert_eq!(
+      shared,
+      [SharedGlyph {
+        font_index: 0,
+        glyph_id: 7,
+        chars: vec!['户', '戶'],
+      }]
+    );
+    assert_eq!(
+      shared_glyph_warning(&shared[0]).to_string(),
+      "[Warning] '户' (U+6237) and '戶' (U+6236) share glyph #7 of font #0, so their tiles are \
+       identical."
+    );
+  }
+}
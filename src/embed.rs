@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::Serialize;
+
+use crate::mapping::Mapping;
+
+/// Atlas rect of one character in the `--base64-json` charset.
+#[derive(Serialize)]
+pub struct CharRect {
+  pub index: usize,
+  pub x: u32,
+  pub y: u32,
+  pub w: u32,
+  pub h: u32,
+}
+
+/// `--base64-json` export: the atlas PNG inlined as base64 next to the character rects, for
+/// HTML5 canvas engines that want a single self-contained file.
+#[derive(Serialize)]
+pub struct EmbeddedAtlas {
+  pub width: u32,
+  pub height: u32,
+  pub format: &'static str,
+  pub data: String,
+  /// Character → rect. Color class tiles are keyed `<char>:<class>`.
+  pub charset: BTreeMap<String, CharRect>,
+}
+
+impl EmbeddedAtlas {
+  pub fn new(png: &[u8], width: u32, height: u32, mapping: &Mapping) -> Self {
+    let charset = mapping
+      .chars
+      .iter()
+      .map(|entry| {
+        let key = match &entry.class {
+          Some(class) => format!("{}:{}", entry.char, class),
+          None => entry.char.clone(),
+        };
+        let rect = CharRect {
+          index: entry.index,
+          x: entry.x,
+          y: entry.y,
+          w: entry.width,
+          h: entry.height,
+        };
+        (key, rect)
+      })
+      .collect();
+    EmbeddedAtlas {
+      width,
+      height,
+      format: "png",
+      data: STANDARD.encode(png),
+      charset,
+    }
+  }
+}
+
+/// Bytes per line of the `--output-rust` array: the most `0x00,` items rustfmt fits in 100
+/// columns after the 4-space indent.
+const RUST_BYTES_PER_LINE: usize = 16;
+
+/// `--output-rust` export: a Rust source file with the atlas PNG as `FONT_ATLAS` and the
+/// `--map` JSON as `FONT_ATLAS_METADATA`, laid out the way rustfmt formats them.
+pub fn rust_module(png: &[u8], metadata_json: &str, image_name: &str) -> String {
+  let mut module = format!(
+    "// Generated by {} {} from {}.\n//\n// Usage:\n//     include!(\"atlas.rs\");\n\n",
+    env!("CARGO_PKG_NAME"),
+    env!("CARGO_PKG_VERSION"),
+    image_name
+  );
+  module.push_str("pub const FONT_ATLAS: &[u8] = &[\n");
+  for line in png.chunks(RUST_BYTES_PER_LINE) {
+    let bytes = line
+      .iter()
+      .map(|byte| format!("0x{:02x},", byte))
+      .collect::<Vec<_>>();
+    module.push_str(&format!("    {}\n", bytes.join(" ")));
+  }
+  module.push_str("];\n\n");
+  // Enough hashes that the raw string cannot end early inside the JSON.
+  let hashes = "#".repeat(
+    (1..)
+      .find(|count| !metadata_json.contains(&format!("\"{}", "#".repeat(*count))))
+      .unwrap(),
+  );
+  module.push_str(&format!(
+    "pub const FONT_ATLAS_METADATA: &str = r{hashes}\"{}\"{hashes};\n",
+    metadata_json
+  ));
+  module
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{atlas::GlyphPlacement, pack::PackMode};
+
+  #[test]
+  fn png_is_inlined_as_base64() {
+    let placements = [GlyphPlacement {
+      char: '中',
+      cell_x: 10,
+      ..Default::default()
+    }];
+    let mapping = Mapping::from_placements(
+      "font.png".to_string(),
+      10,
+      32,
+      PackMode::Grid,
+      1,
+      &placements,
+    );
+    let embedded = EmbeddedAtlas::new(b"\x89PNG", 320, 10, &mapping);
+    assert_eq!(embedded.data, "iVBORw==");
+    assert_eq!(embedded.charset["中"].x, 10);
+  }
+
+  #[test]
+  fn rust_module_holds_the_png_bytes_and_metadata() {
+    let png = (0..17).collect::<Vec<u8>>();
+    let module = rust_module(&png, r##"{"char":"#"}"##, "font.png");
+    assert!(module.contains(
+      "pub const FONT_ATLAS: &[u8] = &[\n    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, \
+       0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,\n    0x10,\n];\n"
+    ));
+    assert!(module.ends_with("pub const FONT_ATLAS_METADATA: &str = r##\"{\"char\":\"#\"}\"##;\n"));
+  }
+}
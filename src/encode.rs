@@ -0,0 +1,290 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use anyhow::{Result, anyhow, bail};
+use clap::ValueEnum;
+use image::{ImageFormat, Rgb, RgbImage, codecs::png::CompressionType};
+use serde::{Deserialize, Serialize};
+
+/// Largest palette an indexed PNG can hold.
+pub const MAX_PALETTE_SIZE: usize = 256;
+
+/// PNG filter type.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "UPPER")]
+pub enum PngFilter {
+  None,
+  Sub,
+  Up,
+  Avg,
+  Paeth,
+  #[default]
+  Adaptive,
+}
+
+impl PngFilter {
+  fn apply<W: std::io::Write>(self, encoder: &mut png::Encoder<W>) {
+    let filter = match self {
+      PngFilter::None => png::FilterType::NoFilter,
+      PngFilter::Sub => png::FilterType::Sub,
+      PngFilter::Up => png::FilterType::Up,
+      PngFilter::Avg => png::FilterType::Avg,
+      PngFilter::Paeth | PngFilter::Adaptive => png::FilterType::Paeth,
+    };
+    encoder.set_filter(filter);
+    encoder.set_adaptive_filter(if self == PngFilter::Adaptive {
+      png::AdaptiveFilterType::Adaptive
+    } else {
+      png::AdaptiveFilterType::NonAdaptive
+    });
+  }
+}
+
+/// Map a zlib-style 0-9 level onto the encoder's presets. The encoder has no uncompressed
+/// preset, so 0 behaves like 1.
+pub fn compression_from_level(level: u8) -> CompressionType {
+  match level {
+    0..=2 => CompressionType::Fast,
+    3..=6 => CompressionType::Default,
+    _ => CompressionType::Best,
+  }
+}
+
+fn png_compression(compression: CompressionType) -> png::Compression {
+  match compression {
+    CompressionType::Fast => png::Compression::Fast,
+    CompressionType::Best => png::Compression::Best,
+    _ => png::Compression::Default,
+  }
+}
+
+/// Color role of a `palette_order` entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaletteRole {
+  /// The `--color-key` color, written fully transparent.
+  Transparent,
+  Char,
+  Shadow,
+  Bg,
+}
+
+impl PaletteRole {
+  pub const ALL: [PaletteRole; 4] = [
+    PaletteRole::Transparent,
+    PaletteRole::Char,
+    PaletteRole::Shadow,
+    PaletteRole::Bg,
+  ];
+}
+
+/// Indexed PNG palette with one index per [`PaletteRole`], in `palette_order`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FixedPalette {
+  /// Color of each index; `None` for the transparent index without a color key.
+  pub colors: [Option<Rgb<u8>>; 4],
+  /// Index written fully transparent in the tRNS chunk.
+  pub transparent: usize,
+}
+
+impl FixedPalette {
+  /// Palette of the roles in `order`, which names each role once.
+  pub fn new(
+    order: &[PaletteRole],
+    char_color: Rgb<u8>,
+    shadow_color: Rgb<u8>,
+    bg_color: Rgb<u8>,
+    color_key: Option<Rgb<u8>>,
+  ) -> Self {
+    let color = |role| match role {
+      PaletteRole::Transparent => color_key,
+      PaletteRole::Char => Some(char_color),
+      PaletteRole::Shadow => Some(shadow_color),
+      PaletteRole::Bg => Some(bg_color),
+    };
+    FixedPalette {
+      colors: std::array::from_fn(|i| color(order[i])),
+      transparent: order
+        .iter()
+        .position(|role| *role == PaletteRole::Transparent)
+        .unwrap(),
+    }
+  }
+
+  /// Index of a pixel color. The color key takes the transparent index even though it is
+  /// also the background color.
+  fn index_of(&self, color: Rgb<u8>) -> Option<u8> {
+    if self.colors[self.transparent] == Some(color) {
+      return Some(self.transparent as u8);
+    }
+    self
+      .colors
+      .iter()
+      .position(|entry| *entry == Some(color))
+      .map(|index| index as u8)
+  }
+}
+
+/// One `palette` index per pixel. Colors without a role are an error.
+pub fn fixed_palette_indices(image: &RgbImage, palette: &FixedPalette) -> Result<Vec<u8>> {
+  image
+    .pixels()
+    .map(|pixel| {
+      palette.index_of(*pixel).ok_or_else(|| {
+        anyhow!(
+          "[Error] The atlas has the color {:?}, which none of the palette_order roles has!",
+          pixel.0
+        )
+      })
+    })
+    .collect()
+}
+
+/// Encoder settings for the atlas PNG.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct PngOptions {
+  pub compression: CompressionType,
+  pub filter: PngFilter,
+  /// Write a palette image with the smallest bit depth that fits the colors.
+  pub indexed: bool,
+  /// Fixed role order of the indexed palette, instead of the colors in first-seen order.
+  pub palette: Option<FixedPalette>,
+}
+
+/// Smallest PNG bit depth (1, 2, 4 or 8 bpp) that can index `palette_size` colors.
+pub fn indexed_bit_depth(palette_size: usize) -> png::BitDepth {
+  match palette_size {
+    0..=2 => png::BitDepth::One,
+    3..=4 => png::BitDepth::Two,
+    5..=16 => png::BitDepth::Four,
+    _ => png::BitDepth::Eight,
+  }
+}
+
+/// Colors of the image in first-seen order, and one palette index per pixel.
+pub fn build_palette(image: &RgbImage) -> Result<(Vec<Rgb<u8>>, Vec<u8>)> {
+  let mut palette = Vec::new();
+  let mut indices = Vec::with_capacity((image.width() * image.height()) as usize);
+  for pixel in image.pixels() {
+    let index = match palette.iter().position(|color| color == pixel) {
+      Some(index) => index,
+      None => {
+        if palette.len() == MAX_PALETTE_SIZE {
+          bail!(
+            "[Error] The atlas has more than {} colors and cannot be written as an indexed PNG!",
+            MAX_PALETTE_SIZE
+          );
+        }
+        palette.push(*pixel);
+        palette.len() - 1
+      }
+    };
+    indices.push(index as u8);
+  }
+  Ok((palette, indices))
+}
+
+/// Pack palette indices into rows of `bits`-bit samples, MSB first, each row padded to a byte.
+pub fn pack_indices(indices: &[u8], width: u32, bits: u8) -> Vec<u8> {
+  let per_byte = (8 / bits) as usize;
+  let row_bytes = (width as usize).div_ceil(per_byte);
+  let mut packed = Vec::with_capacity(row_bytes * indices.len() / width.max(1) as usize);
+  for row in indices.chunks(width as usize) {
+    for samples in row.chunks(per_byte) {
+      let mut byte = 0u8;
+      for (i, sample) in samples.iter().enumerate() {
+        byte |= sample << (8 - bits as usize * (i + 1));
+      }
+      packed.push(byte);
+    }
+  }
+  packed
+}
+
+/// Write the atlas as PNG and return the file size in bytes.
+///
+/// Default options go through `image`'s own PNG writer; anything else uses the `png` encoder
+/// directly.
+pub fn save_png(image: &RgbImage, path: &Path, options: PngOptions) -> Result<u64> {
+  if options == PngOptions::default() {
+    image.save_with_format(path, ImageFormat::Png)?;
+    return Ok(std::fs::metadata(path)?.len());
+  }
+
+  let writer = BufWriter::new(File::create(path)?);
+  let mut encoder = png::Encoder::new(writer, image.width(), image.height());
+  encoder.set_compression(png_compression(options.compression));
+  options.filter.apply(&mut encoder);
+  if let (true, Some(palette)) = (options.indexed, options.palette) {
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Two);
+    encoder.set_palette(
+      palette
+        .colors
+        .iter()
+        .flat_map(|color| color.unwrap_or(Rgb([0, 0, 0])).0)
+        .collect::<Vec<_>>(),
+    );
+    encoder.set_trns(
+      (0..=palette.transparent)
+        .map(|index| if index == palette.transparent { 0 } else { 255 })
+        .collect::<Vec<u8>>(),
+    );
+    let data = pack_indices(&fixed_palette_indices(image, &palette)?, image.width(), 2);
+    encoder.write_header()?.write_image_data(&data)?;
+  } else if options.indexed {
+    let (palette, indices) = build_palette(image)?;
+    let bit_depth = indexed_bit_depth(palette.len());
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(bit_depth);
+    encoder.set_palette(palette.iter().flat_map(|color| color.0).collect::<Vec<_>>());
+    let data = pack_indices(&indices, image.width(), bit_depth as u8);
+    encoder.write_header()?.write_image_data(&data)?;
+  } else {
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(image.as_raw())?;
+  }
+  Ok(std::fs::metadata(path)?.len())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn bit_depth_fits_palette() {
+    assert_eq!(indexed_bit_depth(2), png::BitDepth::One);
+    assert_eq!(indexed_bit_depth(3), png::BitDepth::Two);
+    assert_eq!(indexed_bit_depth(16), png::BitDepth::Four);
+    assert_eq!(indexed_bit_depth(17), png::BitDepth::Eight);
+  }
+
+  #[test]
+  fn indices_are_packed_msb_first_per_row() {
+    assert_eq!(
+      pack_indices(&[1, 0, 1, 1, 0, 1], 3, 1),
+      vec![0b1010_0000, 0b1010_0000]
+    );
+    assert_eq!(pack_indices(&[3, 2, 1], 3, 2), vec![0b1110_0100]);
+  }
+
+  #[test]
+  fn fixed_palette_follows_the_role_order() {
+    let (char_color, shadow, bg) = (Rgb([255, 255, 255]), Rgb([128, 128, 128]), Rgb([0, 0, 0]));
+    let order = [
+      PaletteRole::Transparent,
+      PaletteRole::Char,
+      PaletteRole::Shadow,
+      PaletteRole::Bg,
+    ];
+    let image = RgbImage::from_fn(3, 1, |x, _| [char_color, shadow, bg][x as usize]);
+    let palette = FixedPalette::new(&order, char_color, shadow, bg, None);
+    assert_eq!(palette.colors[0], None);
+    assert_eq!(fixed_palette_indices(&image, &palette).unwrap(), [1, 2, 3]);
+    // The color key is the background color; its pixels take the transparent index.
+    let palette = FixedPalette::new(&order, char_color, shadow, bg, Some(bg));
+    assert_eq!(fixed_palette_indices(&image, &palette).unwrap(), [1, 2, 0]);
+    let palette = FixedPalette::new(&order, char_color, bg, bg, None);
+    assert!(fixed_palette_indices(&image, &palette).is_err());
+  }
+}
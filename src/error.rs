@@ -0,0 +1,69 @@
+use std::{error::Error, fmt};
+
+use crate::builder::BuildError;
+
+/// Failure classes, each with its own process exit code so build scripts can tell them apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureKind {
+  /// Invalid command line arguments or config file (exit code 2).
+  InvalidArguments = 2,
+  /// Missing or unreadable input files (exit code 3).
+  Input = 3,
+  /// A font file could not be parsed (exit code 4).
+  Font = 4,
+  /// Glyphs are missing and `--fail-on-missing` was given (exit code 5).
+  MissingGlyphs = 5,
+  /// The characters do not fit the atlas (exit code 6).
+  Overflow = 6,
+}
+
+/// Exit code of errors that don't belong to any [`FailureKind`].
+pub const GENERIC_EXIT_CODE: u8 = 1;
+
+/// An error tagged with its [`FailureKind`].
+#[derive(Debug)]
+pub struct Failure {
+  pub kind: FailureKind,
+  message: String,
+}
+
+impl Failure {
+  pub fn new(kind: FailureKind, message: impl Into<String>) -> Self {
+    Failure {
+      kind,
+      message: message.into(),
+    }
+  }
+}
+
+impl fmt::Display for Failure {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.message)
+  }
+}
+
+impl Error for Failure {}
+
+impl BuildError {
+  pub fn kind(&self) -> FailureKind {
+    match self {
+      BuildError::InvalidFont(..)
+      | BuildError::BitmapOnlyFont(..)
+      | BuildError::InvalidFontContainer(..) => FailureKind::Font,
+      _ => FailureKind::InvalidArguments,
+    }
+  }
+}
+
+/// Process exit code for an error returned by the pipeline.
+pub fn exit_code(error: &anyhow::Error) -> u8 {
+  error
+    .chain()
+    .find_map(|cause| {
+      cause
+        .downcast_ref::<Failure>()
+        .map(|failure| failure.kind)
+        .or_else(|| cause.downcast_ref::<BuildError>().map(BuildError::kind))
+    })
+    .map_or(GENERIC_EXIT_CODE, |kind| kind as u8)
+}
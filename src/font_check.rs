@@ -0,0 +1,171 @@
+use std::fmt;
+
+use crate::woff::{read_u16, read_u32};
+
+/// Tables every font needs besides the glyph outlines.
+const REQUIRED_TABLES: [&str; 5] = ["cmap", "head", "hhea", "hmtx", "maxp"];
+/// Glyph outline tables; a font needs one of them.
+const OUTLINE_TABLES: [&str; 3] = ["glyf", "CFF ", "CFF2"];
+/// Embedded bitmap tables.
+const BITMAP_TABLES: [&str; 4] = ["EBDT", "CBDT", "sbix", "bdat"];
+
+/// Font file format, detected by its magic bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FontKind {
+  Ttf,
+  Otf,
+  Ttc,
+  Woff,
+  Woff2,
+  Zip,
+  Unknown,
+}
+
+impl FontKind {
+  pub fn detect(bytes: &[u8]) -> FontKind {
+    match bytes.get(..4) {
+      Some([0x00, 0x01, 0x00, 0x00] | b"true") => FontKind::Ttf,
+      Some(b"OTTO") => FontKind::Otf,
+      Some(b"ttcf") => FontKind::Ttc,
+      Some(b"wOFF") => FontKind::Woff,
+      Some(b"wOF2") => FontKind::Woff2,
+      Some([b'P', b'K', 0x03, 0x04]) => FontKind::Zip,
+      _ => FontKind::Unknown,
+    }
+  }
+}
+
+impl fmt::Display for FontKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      FontKind::Ttf => "TTF",
+      FontKind::Otf => "OTF",
+      FontKind::Ttc => "TTC",
+      FontKind::Woff => "WOFF",
+      FontKind::Woff2 => "WOFF2",
+      FontKind::Zip => "ZIP archive, not a font",
+      FontKind::Unknown => "unknown format",
+    })
+  }
+}
+
+/// What a font file looks like, to explain why it could not be used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FontDiagnosis {
+  /// Format of the file as given, before any WOFF unwrapping.
+  pub kind: FontKind,
+  pub size: usize,
+  /// Table tags of the face, `None` when there is no readable table directory.
+  pub tables: Option<Vec<String>>,
+}
+
+impl FontDiagnosis {
+  /// Diagnose face `face_index` of `sfnt`, the font unwrapped from the `original` file.
+  pub fn new(original: &[u8], sfnt: &[u8], face_index: u32) -> FontDiagnosis {
+    FontDiagnosis {
+      kind: FontKind::detect(original),
+      size: original.len(),
+      tables: table_tags(sfnt, face_index),
+    }
+  }
+
+  fn has(&self, tag: &str) -> bool {
+    self
+      .tables
+      .as_ref()
+      .is_some_and(|tables| tables.iter().any(|table| table == tag))
+  }
+
+  /// Required tables the face lacks; `glyf/CFF/CFF2` stands for the missing outlines.
+  pub fn missing_tables(&self) -> Vec<&'static str> {
+    if self.tables.is_none() {
+      return Vec::new();
+    }
+    let mut missing = REQUIRED_TABLES
+      .into_iter()
+      .filter(|tag| !self.has(tag))
+      .collect::<Vec<_>>();
+    if !OUTLINE_TABLES.iter().any(|tag| self.has(tag)) {
+      missing.push("glyf/CFF/CFF2");
+    }
+    missing
+  }
+
+  /// The face has embedded bitmaps but no outlines to rasterize.
+  pub fn is_bitmap_only(&self) -> bool {
+    BITMAP_TABLES.iter().any(|tag| self.has(tag)) && !OUTLINE_TABLES.iter().any(|tag| self.has(tag))
+  }
+}
+
+impl fmt::Display for FontDiagnosis {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}, {} bytes", self.kind, self.size)?;
+    match &self.tables {
+      Some(tables) => {
+        write!(f, ", tables: {}", tables.join(" "))?;
+        let missing = self.missing_tables();
+        if !missing.is_empty() {
+          write!(f, ", missing: {}", missing.join(" "))?;
+        }
+        Ok(())
+      }
+      None => write!(f, ", no table directory"),
+    }
+  }
+}
+
+/// Table tags listed in the directory of face `face_index`.
+fn table_tags(sfnt: &[u8], face_index: u32) -> Option<Vec<String>> {
+  let directory = match FontKind::detect(sfnt) {
+    FontKind::Ttf | FontKind::Otf => 0,
+    FontKind::Ttc => read_u32(sfnt, 12 + 4 * face_index as usize)? as usize,
+    _ => return None,
+  };
+  let num_tables = read_u16(sfnt, directory + 4)? as usize;
+  (0..num_tables)
+    .map(|table| {
+      let record = directory + 12 + table * 16;
+      sfnt
+        .get(record..record + 4)
+        .map(|tag| String::from_utf8_lossy(tag).into_owned())
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn sfnt(tags: &[&str]) -> Vec<u8> {
+    let mut bytes = b"OTTO".to_vec();
+    bytes.extend_from_slice(&(tags.len() as u16).to_be_bytes());
+    bytes.resize(12, 0);
+    for tag in tags {
+      bytes.extend_from_slice(tag.as_bytes());
+      bytes.extend_from_slice(&[0; 12]);
+    }
+    bytes
+  }
+
+  #[test]
+  fn diagnosis_lists_missing_tables() {
+    let font = sfnt(&["cmap", "head", "hmtx", "maxp"]);
+    let diagnosis = FontDiagnosis::new(&font, &font, 0);
+    assert_eq!(diagnosis.kind, FontKind::Otf);
+    assert_eq!(diagnosis.missing_tables(), ["hhea", "glyf/CFF/CFF2"]);
+    assert!(!diagnosis.is_bitmap_only());
+    assert_eq!(
+      diagnosis.to_string(),
+      "OTF, 76 bytes, tables: cmap head hmtx maxp, missing: hhea glyf/CFF/CFF2"
+    );
+
+    let bitmap = sfnt(&["EBDT", "EBLC", "cmap", "head", "hhea", "hmtx", "maxp"]);
+    assert!(FontDiagnosis::new(&bitmap, &bitmap, 0).is_bitmap_only());
+
+    let zip = b"PK\x03\x04rest of the archive";
+    assert_eq!(
+      FontDiagnosis::new(zip, zip, 0).to_string(),
+      "ZIP archive, not a font, 23 bytes, no table directory"
+    );
+  }
+}
@@ -0,0 +1,62 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{
+  atlas::GlyphPlacement,
+  render::{CellBuffer, CellPixel},
+};
+
+/// Unifont glyphs are 16 rows of 16 (or 8) pixels.
+const HEX_SIZE: u32 = 16;
+
+/// 16×16 Unifont hex digits of the char pixels of `cell` (shadow excluded), anchored at the
+/// top-left and cropped to 16×16. `None` gives a blank glyph.
+fn hex_bitmap(cell: Option<&CellBuffer>) -> String {
+  let mut hex = String::with_capacity((HEX_SIZE * HEX_SIZE / 4) as usize);
+  for y in 0..HEX_SIZE {
+    let mut row = 0u16;
+    if let Some(cell) = cell {
+      for x in 0..HEX_SIZE.min(cell.size()) {
+        if y < cell.size() && cell.get(x, y) == CellPixel::Char {
+          row |= 0x8000 >> x;
+        }
+      }
+    }
+    write!(hex, "{:04X}", row).unwrap();
+  }
+  hex
+}
+
+/// GNU Unifont `.hex` lines (`4E2D:<64 hex digits>`) of every rendered character, sorted by
+/// codepoint. Missing glyphs and the duplicated color class tiles are left out.
+pub fn unifont_hex(placements: &[GlyphPlacement], cells: &[Option<CellBuffer>]) -> String {
+  let glyphs = placements
+    .iter()
+    .zip(cells)
+    .filter(|(placement, _)| !placement.missing && placement.class.is_none())
+    .map(|(placement, cell)| (placement.char, hex_bitmap(cell.as_ref())))
+    .collect::<BTreeMap<_, _>>();
+  let mut hex = String::new();
+  for (c, bitmap) in glyphs {
+    writeln!(hex, "{:04X}:{}", c as u32, bitmap).unwrap();
+  }
+  hex
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn hex_rows_exclude_the_shadow() {
+    let mut cell = CellBuffer::new(10);
+    cell.set(0, 0, CellPixel::Char);
+    cell.set(9, 0, CellPixel::Char);
+    cell.set(1, 1, CellPixel::Shadow);
+    cell.set(2, 9, CellPixel::Char);
+    let hex = hex_bitmap(Some(&cell));
+    assert_eq!(hex.len(), 64);
+    assert_eq!(&hex[..8], "80400000");
+    assert_eq!(&hex[36..40], "2000");
+    assert_eq!(hex_bitmap(None), "0".repeat(64));
+  }
+}
@@ -0,0 +1,95 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+
+use crate::{atlas::GlyphPlacement, mapping::codepoint_label};
+
+/// Zoom factor of the tiles in the contact sheet.
+pub const HTML_ZOOM: u32 = 4;
+
+const STYLE: &str = "body{font-family:sans-serif;background:#222;color:#ddd}\
+#tiles{display:flex;flex-wrap:wrap;gap:6px}\
+.tile{display:flex;flex-direction:column;align-items:center;padding:4px;border:2px solid #444}\
+.tile.warn{border-color:#e5a000;background:#4a3500}\
+.tile.found{outline:3px solid #3af}\
+.glyph{image-rendering:pixelated;background-repeat:no-repeat}\
+.label{font-size:11px;text-align:center;margin-top:2px}";
+
+const SCRIPT: &str = "const search=document.getElementById('search');\
+search.addEventListener('keydown',e=>{if(e.key!=='Enter')return;\
+const q=search.value.trim();if(!q)return;\
+const t=[...document.querySelectorAll('.tile')].find(t=>t.dataset.char===q\
+||t.dataset.codepoint.toLowerCase()===q.toLowerCase()||t.dataset.index===q);\
+document.querySelectorAll('.found').forEach(f=>f.classList.remove('found'));\
+if(t){t.classList.add('found');t.scrollIntoView({block:'center'});}});";
+
+pub(crate) fn escape_html(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+/// Self-contained HTML page showing every tile of the atlas at [`HTML_ZOOM`]× zoom, with
+/// missing, substituted and clipped tiles highlighted.
+///
+/// `png` is the encoded atlas exactly as written to disk, so the page always shows the same
+/// pixels as the image file.
+pub fn contact_sheet(
+  png: &[u8],
+  image_width: u32,
+  image_height: u32,
+  cell_size: u32,
+  placements: &[GlyphPlacement],
+) -> String {
+  let data_uri = format!("data:image/png;base64,{}", STANDARD.encode(png));
+  let mut tiles = String::new();
+  for placement in placements {
+    let rect = placement.atlas_rect(cell_size);
+    let mut problems = Vec::new();
+    if placement.missing {
+      problems.push("missing");
+    }
+    if placement.substituted {
+      problems.push("substituted");
+    }
+    if placement.clipped {
+      problems.push("clipped");
+    }
+    let char = escape_html(&placement.char.to_string());
+    let codepoint = codepoint_label(placement.char);
+    tiles.push_str(&format!(
+      "<div class=\"tile{}\" data-char=\"{}\" data-codepoint=\"{}\" data-index=\"{}\" \
+       title=\"{}\"><div class=\"glyph\" style=\"width:{}px;height:{}px;\
+       background-image:var(--atlas);background-size:{}px {}px;background-position:-{}px -{}px\">\
+       </div><div class=\"label\">{}<br>{}<br>#{}</div></div>\n",
+      if problems.is_empty() { "" } else { " warn" },
+      char,
+      codepoint,
+      placement.tile_index,
+      problems.join(", "),
+      rect.width * HTML_ZOOM,
+      rect.height * HTML_ZOOM,
+      image_width * HTML_ZOOM,
+      image_height * HTML_ZOOM,
+      rect.x * HTML_ZOOM,
+      rect.y * HTML_ZOOM,
+      char,
+      codepoint,
+      placement.tile_index
+    ));
+  }
+
+  format!(
+    "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Atlas contact sheet</title>\n\
+     <style>:root{{--atlas:url({})}}{}</style>\n</head>\n<body>\n\
+     <p>{} tiles, {}x{} px. <input id=\"search\" placeholder=\"Character, U+XXXX or index\"></p>\n\
+     <div id=\"tiles\">\n{}</div>\n<script>{}</script>\n</body>\n</html>\n",
+    data_uri,
+    STYLE,
+    placements.len(),
+    image_width,
+    image_height,
+    tiles,
+    SCRIPT
+  )
+}
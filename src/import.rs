@@ -0,0 +1,130 @@
+use anyhow::{Result, bail};
+use image::{Rgb, RgbImage};
+
+use crate::{
+  atlas::GlyphPlacement,
+  render::{CellBuffer, CellPixel},
+};
+
+/// Colors telling the pixels of an existing atlas apart.
+pub struct ImportColors {
+  pub char_color: Rgb<u8>,
+  /// Background colors; any other non-character color counts as shadow.
+  pub bg_colors: Vec<Rgb<u8>>,
+}
+
+/// Cell buffer of the `cell_size` tile at (`x`, `y`) of `image`.
+fn read_cell(
+  image: &RgbImage,
+  x: u32,
+  y: u32,
+  cell_size: u32,
+  colors: &ImportColors,
+) -> CellBuffer {
+  let mut cell = CellBuffer::new(cell_size);
+  for dy in 0..cell_size {
+    for dx in 0..cell_size {
+      let pixel = *image.get_pixel(x + dx, y + dy);
+      let kind = if pixel == colors.char_color {
+        CellPixel::Char
+      } else if colors.bg_colors.contains(&pixel) {
+        CellPixel::Background
+      } else {
+        CellPixel::Shadow
+      };
+      cell.set(dx as i32, dy as i32, kind);
+    }
+  }
+  cell
+}
+
+/// Rebuild the placement records of a grid atlas from its image and the (tile index, character)
+/// pairs it is known to hold. Tiles that are entirely background are marked missing.
+pub fn import_placements(
+  image: &RgbImage,
+  cell_size: u32,
+  tiles: &[(usize, char)],
+  colors: &ImportColors,
+) -> Result<(Vec<GlyphPlacement>, Vec<Option<CellBuffer>>)> {
+  let columns = (image.width() / cell_size) as usize;
+  let rows = (image.height() / cell_size) as usize;
+  if columns == 0 || rows == 0 {
+    bail!(
+      "[Error] The {}x{} image is smaller than one {}px tile!",
+      image.width(),
+      image.height(),
+      cell_size
+    );
+  }
+  let mut placements = Vec::with_capacity(tiles.len());
+  let mut cells = Vec::with_capacity(tiles.len());
+  for &(index, c) in tiles {
+    if index >= columns * rows {
+      bail!(
+        "[Error] Tile {} ('{}') is beyond the {} tiles of the image!",
+        index,
+        c,
+        columns * rows
+      );
+    }
+    let (cell_x, cell_y) = (
+      (index % columns) as u32 * cell_size,
+      (index / columns) as u32 * cell_size,
+    );
+    let cell = read_cell(image, cell_x, cell_y, cell_size, colors);
+    let ink_bbox = cell.ink_bbox();
+    placements.push(GlyphPlacement {
+      char: c,
+      tile_index: index,
+      cell_x,
+      cell_y,
+      glyph_bbox: cell.char_bbox(),
+      ink_bbox,
+      advance: cell_size as f32,
+      missing: ink_bbox.is_none(),
+      font_index: ink_bbox.map(|_| 0),
+      ..Default::default()
+    });
+    cells.push(ink_bbox.map(|_| cell));
+  }
+  Ok((placements, cells))
+}
+
+/// (tile index, character) pairs of a plain charset file: every non-whitespace character in
+/// order, starting at tile 0.
+pub fn charset_tiles(text: &str) -> Vec<(usize, char)> {
+  text
+    .chars()
+    .filter(|c| !c.is_whitespace())
+    .enumerate()
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn blank_tiles_are_marked_missing() {
+    let (bg, ink, shadow) = (Rgb([0, 0, 0]), Rgb([255, 255, 255]), Rgb([99, 99, 99]));
+    let mut image = RgbImage::from_pixel(20, 20, bg);
+    image.put_pixel(12, 3, ink);
+    image.put_pixel(13, 4, shadow);
+    let colors = ImportColors {
+      char_color: ink,
+      bg_colors: vec![bg],
+    };
+    let tiles = charset_tiles("一 中\n口");
+    assert_eq!(tiles, [(0, '一'), (1, '中'), (2, '口')]);
+    let (placements, cells) = import_placements(&image, 10, &tiles, &colors).unwrap();
+    assert!(placements[0].missing && cells[0].is_none());
+    assert!(!placements[1].missing);
+    assert_eq!(
+      placements[1].glyph_bbox.map(|rect| (rect.x, rect.y)),
+      Some((2, 3))
+    );
+    assert_eq!(placements[1].ink_bbox.map(|rect| rect.width), Some(2));
+    assert_eq!(placements[2].cell_y, 10);
+    assert!(import_placements(&image, 10, &[(4, '大')], &colors).is_err());
+  }
+}
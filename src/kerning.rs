@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Result, anyhow, bail};
+
+/// Pixel adjustments between character pairs, first character → following character → px.
+pub type KerningTable = BTreeMap<char, BTreeMap<char, i32>>;
+
+/// A kerning file key: the character itself or its `U+XXXX` codepoint.
+fn parse_char_key(key: &str) -> Result<char> {
+  if let Some(hex) = key.strip_prefix("U+").or_else(|| key.strip_prefix("u+")) {
+    return u32::from_str_radix(hex, 16)
+      .ok()
+      .and_then(char::from_u32)
+      .ok_or_else(|| {
+        anyhow!(
+          "[Error] '{}' in the kerning file is not a valid codepoint!",
+          key
+        )
+      });
+  }
+  let mut chars = key.chars();
+  match (chars.next(), chars.next()) {
+    (Some(c), None) => Ok(c),
+    _ => bail!(
+      "[Error] '{}' in the kerning file is not a single character or U+XXXX codepoint!",
+      key
+    ),
+  }
+}
+
+/// Parse a `--kerning-file`: one table per first character holding the adjustment of each
+/// following character, e.g. `["我"]` then `"是" = -1`. Adjustments must be within
+/// ±`font_size`; zero adjustments are dropped.
+pub fn parse_kerning(text: &str, font_size: u32) -> Result<KerningTable> {
+  let raw = toml::from_str::<BTreeMap<String, BTreeMap<String, i32>>>(text)
+    .map_err(|e| anyhow!("[Error] Cannot parse the kerning file: {}", e))?;
+  let limit = font_size as i32;
+  let mut table = KerningTable::new();
+  for (first, pairs) in raw {
+    let first_char = parse_char_key(&first)?;
+    for (second, adjustment) in pairs {
+      let second_char = parse_char_key(&second)?;
+      if !(-limit..=limit).contains(&adjustment) {
+        bail!(
+          "[Error] Kerning {} for '{}{}' is outside [-{}, {}]!",
+          adjustment,
+          first_char,
+          second_char,
+          limit,
+          limit
+        );
+      }
+      if adjustment != 0 {
+        table
+          .entry(first_char)
+          .or_default()
+          .insert(second_char, adjustment);
+      }
+    }
+  }
+  Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn keys_are_chars_or_codepoints() {
+    let table = parse_kerning(
+      "[\"我\"]\n\"是\" = -1\n\"U+4E2D\" = 0\n[\"U+4E00\"]\n\"口\" = 2\n",
+      10,
+    )
+    .unwrap();
+    assert_eq!(table[&'我'], BTreeMap::from([('是', -1)]));
+    assert_eq!(table[&'一'], BTreeMap::from([('口', 2)]));
+    assert!(parse_kerning("[\"我\"]\n\"是\" = -11\n", 10).is_err());
+    assert!(parse_kerning("[\"我们\"]\n\"是\" = 1\n", 10).is_err());
+  }
+}
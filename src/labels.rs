@@ -0,0 +1,301 @@
+use ab_glyph::{Font, PxScale, ScaleFont, point};
+use image::{Rgb, RgbImage, imageops};
+
+use crate::{atlas::GlyphPlacement, render::COVERAGE_THRESHOLD};
+
+/// Width of the row label column, in cells.
+pub const LABEL_CELLS: usize = 3;
+
+/// Every label [`unicode_block_label`] returns.
+pub const BLOCK_LABELS: [&str; 13] = [
+  "Punct", "Compat", "CJK-A", "CJK", "Cmp-Id", "CmpFrm", "FullW", "Ext-B", "Ext-C", "Ext-D",
+  "Ext-E", "Cmp-Sp", "Other",
+];
+
+/// Short ASCII name of the Unicode block holding `c`.
+pub fn unicode_block_label(c: char) -> &'static str {
+  match c as u32 {
+    0x3000..=0x303F => "Punct",
+    0x3300..=0x33FF => "Compat",
+    0x3400..=0x4DBF => "CJK-A",
+    0x4E00..=0x9FFF => "CJK",
+    0xF900..=0xFAFF => "Cmp-Id",
+    0xFE30..=0xFE4F => "CmpFrm",
+    0xFF00..=0xFFEF => "FullW",
+    0x20000..=0x2A6DF => "Ext-B",
+    0x2A700..=0x2B73F => "Ext-C",
+    0x2B740..=0x2B81F => "Ext-D",
+    0x2B820..=0x2CEAF => "Ext-E",
+    0x2F800..=0x2FA1F => "Cmp-Sp",
+    _ => "Other",
+  }
+}
+
+/// Draw `text` with its top-left corner at (`x`, `y`), clipped to `width × height`.
+pub fn draw_label<F: Font>(
+  image: &mut RgbImage,
+  font: &F,
+  text: &str,
+  (x, y): (u32, u32),
+  (width, height): (u32, u32),
+  color: Rgb<u8>,
+) {
+  let scaled_font = font.as_scaled(PxScale::from(height as f32));
+  let mut caret = 0.0;
+  for c in text.chars() {
+    let glyph_id = font.glyph_id(c);
+    let glyph =
+      glyph_id.with_scale_and_position(scaled_font.scale(), point(caret, scaled_font.ascent()));
+    caret += scaled_font.h_advance(glyph_id);
+    if let Some(outlined_glyph) = scaled_font.outline_glyph(glyph) {
+      let bounds = outlined_glyph.px_bounds();
+      outlined_glyph.draw(|gx, gy, v| {
+        let px = bounds.min.x as i32 + gx as i32;
+        let py = bounds.min.y as i32 + gy as i32;
+        if v > COVERAGE_THRESHOLD
+          && px >= 0
+          && py >= 0
+          && (px as u32) < width
+          && (py as u32) < height
+        {
+          image.put_pixel(x + px as u32, y + py as u32, color);
+        }
+      });
+    }
+  }
+}
+
+/// Width (px) of a character of the built-in label font, spacing included.
+pub const BUILTIN_CHAR_WIDTH: u32 = 5;
+
+/// Characters of a `--line-numbers` label, e.g. `0042: U+4E42`, with room for 5-digit
+/// codepoints.
+pub const LINE_NUMBER_CHARS: usize = 13;
+
+/// Rows of the 4x7 built-in label glyphs, most significant of the low 4 bits on the left.
+const BUILTIN_GLYPHS: [(char, [u8; 7]); 20] = [
+  (
+    '0',
+    [0b0110, 0b1001, 0b1001, 0b1001, 0b1001, 0b1001, 0b0110],
+  ),
+  (
+    '1',
+    [0b0010, 0b0110, 0b0010, 0b0010, 0b0010, 0b0010, 0b0111],
+  ),
+  (
+    '2',
+    [0b0110, 0b1001, 0b0001, 0b0010, 0b0100, 0b1000, 0b1111],
+  ),
+  (
+    '3',
+    [0b1110, 0b0001, 0b0001, 0b0110, 0b0001, 0b0001, 0b1110],
+  ),
+  (
+    '4',
+    [0b0010, 0b0110, 0b1010, 0b1010, 0b1111, 0b0010, 0b0010],
+  ),
+  (
+    '5',
+    [0b1111, 0b1000, 0b1110, 0b0001, 0b0001, 0b1001, 0b0110],
+  ),
+  (
+    '6',
+    [0b0110, 0b1000, 0b1000, 0b1110, 0b1001, 0b1001, 0b0110],
+  ),
+  (
+    '7',
+    [0b1111, 0b0001, 0b0010, 0b0010, 0b0100, 0b0100, 0b0100],
+  ),
+  (
+    '8',
+    [0b0110, 0b1001, 0b1001, 0b0110, 0b1001, 0b1001, 0b0110],
+  ),
+  (
+    '9',
+    [0b0110, 0b1001, 0b1001, 0b0111, 0b0001, 0b0001, 0b0110],
+  ),
+  (
+    'A',
+    [0b0110, 0b1001, 0b1001, 0b1111, 0b1001, 0b1001, 0b1001],
+  ),
+  (
+    'B',
+    [0b1110, 0b1001, 0b1001, 0b1110, 0b1001, 0b1001, 0b1110],
+  ),
+  (
+    'C',
+    [0b0110, 0b1001, 0b1000, 0b1000, 0b1000, 0b1001, 0b0110],
+  ),
+  (
+    'D',
+    [0b1110, 0b1001, 0b1001, 0b1001, 0b1001, 0b1001, 0b1110],
+  ),
+  (
+    'E',
+    [0b1111, 0b1000, 0b1000, 0b1110, 0b1000, 0b1000, 0b1111],
+  ),
+  (
+    'F',
+    [0b1111, 0b1000, 0b1000, 0b1110, 0b1000, 0b1000, 0b1000],
+  ),
+  (
+    'U',
+    [0b1001, 0b1001, 0b1001, 0b1001, 0b1001, 0b1001, 0b0110],
+  ),
+  (
+    '+',
+    [0b0000, 0b0000, 0b0100, 0b1110, 0b0100, 0b0000, 0b0000],
+  ),
+  (
+    ':',
+    [0b0000, 0b0100, 0b0000, 0b0000, 0b0000, 0b0100, 0b0000],
+  ),
+  (' ', [0; 7]),
+];
+
+/// Draw `text` with the built-in 4x7 font (hex digits, `U`, `+`, `:` and space), vertically
+/// centered in `height`, clipped to `width`. Other characters are skipped.
+pub fn draw_builtin_label(
+  image: &mut RgbImage,
+  text: &str,
+  (x, y): (u32, u32),
+  (width, height): (u32, u32),
+  color: Rgb<u8>,
+) {
+  let top = height.saturating_sub(7) / 2;
+  for (i, c) in text.chars().enumerate() {
+    let Some((_, rows)) = BUILTIN_GLYPHS.iter().find(|(glyph, _)| *glyph == c) else {
+      continue;
+    };
+    for (row, bits) in rows.iter().enumerate() {
+      for column in 0..4 {
+        let (px, py) = (i as u32 * BUILTIN_CHAR_WIDTH + column, top + row as u32);
+        if bits & (0b1000 >> column) != 0 && px < width && py < height {
+          image.put_pixel(x + px, y + py, color);
+        }
+      }
+    }
+  }
+}
+
+/// `--line-numbers` label of a row: its 0-based index and the codepoint of its first character.
+pub fn line_number_label(row: u32, first: char) -> String {
+  format!("{:04}: U+{:04X}", row, first as u32)
+}
+
+/// Cells the `--line-numbers` column takes in a `cell_size` grid.
+pub fn line_number_cells(cell_size: u32) -> usize {
+  (LINE_NUMBER_CHARS as u32 * BUILTIN_CHAR_WIDTH).div_ceil(cell_size) as usize
+}
+
+/// Prepend a `label_cells` wide column, shifting the placements right accordingly, and call
+/// `draw_row` with the first placement of each row and the label area's size.
+fn prepend_label_column(
+  image: &RgbImage,
+  placements: &mut [GlyphPlacement],
+  label_cells: usize,
+  cell_size: u32,
+  bg_color: Rgb<u8>,
+  mut draw_row: impl FnMut(&mut RgbImage, &GlyphPlacement, (u32, u32)),
+) -> RgbImage {
+  let label_width = label_cells as u32 * cell_size;
+  let mut labeled = RgbImage::from_pixel(image.width() + label_width, image.height(), bg_color);
+  imageops::replace(&mut labeled, image, label_width as i64, 0);
+  let mut labeled_row = None;
+  for placement in placements.iter_mut() {
+    placement.cell_x += label_width;
+    if labeled_row != Some(placement.cell_y) {
+      labeled_row = Some(placement.cell_y);
+      draw_row(&mut labeled, placement, (label_width, cell_size));
+    }
+  }
+  labeled
+}
+
+/// Prepend a [`LABEL_CELLS`] wide column naming the Unicode block of the first character of
+/// each row, shifting the placements right accordingly.
+pub fn add_row_labels<F: Font>(
+  image: &RgbImage,
+  placements: &mut [GlyphPlacement],
+  font: &F,
+  cell_size: u32,
+  bg_color: Rgb<u8>,
+  label_color: Rgb<u8>,
+) -> RgbImage {
+  prepend_label_column(
+    image,
+    placements,
+    LABEL_CELLS,
+    cell_size,
+    bg_color,
+    |labeled, placement, size| {
+      draw_label(
+        labeled,
+        font,
+        unicode_block_label(placement.char),
+        (0, placement.cell_y),
+        size,
+        label_color,
+      );
+    },
+  )
+}
+
+/// Prepend a [`line_number_cells`] wide column labeling each row with its index and the
+/// codepoint of its first character, drawn with `font` or the built-in 4x7 font.
+pub fn add_line_numbers<F: Font>(
+  image: &RgbImage,
+  placements: &mut [GlyphPlacement],
+  font: Option<&F>,
+  cell_size: u32,
+  bg_color: Rgb<u8>,
+  label_color: Rgb<u8>,
+) -> RgbImage {
+  prepend_label_column(
+    image,
+    placements,
+    line_number_cells(cell_size),
+    cell_size,
+    bg_color,
+    |labeled, placement, size| {
+      let label = line_number_label(placement.cell_y / cell_size, placement.char);
+      let origin = (0, placement.cell_y);
+      match font {
+        Some(font) => draw_label(labeled, font, &label, origin, size, label_color),
+        None => draw_builtin_label(labeled, &label, origin, size, label_color),
+      }
+    },
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn block_labels() {
+    assert_eq!(unicode_block_label('中'), "CJK");
+    assert_eq!(unicode_block_label('㐀'), "CJK-A");
+    assert_eq!(unicode_block_label('\u{20000}'), "Ext-B");
+    assert_eq!(unicode_block_label('，'), "FullW");
+  }
+
+  #[test]
+  fn builtin_labels_cover_line_numbers() {
+    let label = line_number_label(42, '\u{20000}');
+    assert_eq!(label, "0042: U+20000");
+    assert_eq!(label.len(), LINE_NUMBER_CHARS);
+    assert!(
+      label
+        .chars()
+        .all(|c| BUILTIN_GLYPHS.iter().any(|(glyph, _)| *glyph == c))
+    );
+    assert_eq!(line_number_cells(10), 7);
+    let (bg, ink) = (Rgb([0, 0, 0]), Rgb([255, 255, 255]));
+    let mut image = RgbImage::from_pixel(10, 10, bg);
+    draw_builtin_label(&mut image, "1", (0, 0), (10, 10), ink);
+    // Column 2 of '1' is inked on every row of the glyph, starting one row down.
+    assert!((1..8).all(|y| *image.get_pixel(2, y) == ink));
+    assert_eq!(*image.get_pixel(2, 0), bg);
+  }
+}
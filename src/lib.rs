@@ -1 +1,45 @@
+pub mod atlas;
+pub mod block_colors;
+pub mod builder;
+pub mod charset;
+pub mod compare;
 pub mod config;
+pub mod coverage;
+pub mod duplicates;
+pub mod embed;
+pub mod encode;
+pub mod error;
+pub mod font_check;
+pub mod hex;
+pub mod html;
+pub mod import;
+pub mod kerning;
+pub mod labels;
+pub mod lua;
+pub mod mapping;
+pub mod normalize;
+pub mod pack;
+pub mod pages;
+pub mod pixel_count;
+pub mod placement;
+#[cfg(feature = "preview")]
+pub mod preview;
+pub mod punctuation;
+pub mod quantize;
+pub mod render;
+pub mod rle;
+pub mod script;
+pub mod stats;
+#[cfg(feature = "system-fonts")]
+pub mod system_fonts;
+pub mod tbl;
+pub mod texturepacker;
+pub mod tsx;
+pub mod verify;
+pub mod warning;
+pub mod woff;
+
+pub use atlas::{FontMetrics, measure_font};
+pub use punctuation::CHINESE_PUNCTUATION_MARKS;
+pub use rle::{decode_rle, encode_rle};
+pub use script::{get_unique_chinese_chars, is_supported_char};
@@ -0,0 +1,85 @@
+use std::{fmt::Write, path::PathBuf};
+
+use clap::ValueEnum;
+
+use crate::mapping::Mapping;
+
+/// How the `--lua` table keys its character entries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum LuaKey {
+  /// `["中"]`
+  #[default]
+  Char,
+  /// `[20013]`
+  Codepoint,
+}
+
+/// Lua string literal of `text`, quotes, backslashes and control characters escaped.
+fn lua_string(text: &str) -> String {
+  let mut literal = String::with_capacity(text.len() + 2);
+  literal.push('"');
+  for c in text.chars() {
+    match c {
+      '"' => literal.push_str("\\\""),
+      '\\' => literal.push_str("\\\\"),
+      '\n' => literal.push_str("\\n"),
+      '\r' => literal.push_str("\\r"),
+      '\t' => literal.push_str("\\t"),
+      c if c.is_control() => {
+        let mut buf = [0; 4];
+        for byte in c.encode_utf8(&mut buf).bytes() {
+          write!(literal, "\\{:03}", byte).unwrap();
+        }
+      }
+      c => literal.push(c),
+    }
+  }
+  literal.push('"');
+  literal
+}
+
+/// Lua module (`return { ... }`) of the mapping: the atlas metadata first, then one
+/// `{index, page, x, y, w, h}` entry per character. Color class tiles are keyed
+/// `"<char or codepoint>:<class>"`.
+pub fn lua_module(mapping: &Mapping, page_files: &[PathBuf], key: LuaKey) -> String {
+  let mut lua = String::from("return {\n");
+  writeln!(lua, "  image = {},", lua_string(&mapping.image)).unwrap();
+  writeln!(lua, "  cell_size = {},", mapping.cell_size).unwrap();
+  writeln!(lua, "  chars_per_line = {},", mapping.chars_per_line).unwrap();
+  let pages = page_files
+    .iter()
+    .map(|file| lua_string(&file.display().to_string()))
+    .collect::<Vec<_>>();
+  writeln!(lua, "  pages = {{ {} }},", pages.join(", ")).unwrap();
+  for entry in &mapping.chars {
+    let char_key = match key {
+      LuaKey::Char => entry.char.clone(),
+      LuaKey::Codepoint => entry.char.chars().next().map_or(0, u32::from).to_string(),
+    };
+    let table_key = match (&entry.class, key) {
+      (Some(class), _) => lua_string(&format!("{}:{}", char_key, class)),
+      (None, LuaKey::Char) => lua_string(&char_key),
+      (None, LuaKey::Codepoint) => char_key,
+    };
+    writeln!(
+      lua,
+      "  [{}] = {{index={},page={},x={},y={},w={},h={}}},",
+      table_key, entry.index, entry.page, entry.x, entry.y, entry.width, entry.height
+    )
+    .unwrap();
+  }
+  lua.push_str("}\n");
+  lua
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn lua_strings_are_escaped() {
+    assert_eq!(lua_string("中"), "\"中\"");
+    assert_eq!(lua_string("a\"b\\c"), "\"a\\\"b\\\\c\"");
+    assert_eq!(lua_string("\n\u{7}"), "\"\\n\\007\"");
+  }
+}
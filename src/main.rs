@@ -1,319 +1,2100 @@
 #![warn(clippy::all)]
 
 use std::{
-  collections::HashSet,
+  collections::BTreeMap,
   fs::{self},
-  path::PathBuf,
+  path::{Path, PathBuf},
+  process::ExitCode,
+  time::Instant,
 };
 
-use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+use ab_glyph::FontVec;
 use anyhow::{Result, bail};
-use chinese_bitmap_font_demo::config::Config;
-use clap::Parser;
+use chinese_bitmap_font_demo::{
+  atlas::{
+    Atlas, CALIBRATION_CHAR, CharBackgroundMode, ColorClassTiles, Flip, GlyphPlacement,
+    calibrate_scale, closest_scale, debug_checker, flatten_on_white, glyph_scale, measure_font,
+    optimal_chars_per_line, strip_unused_cells,
+  },
+  block_colors::BlockColors,
+  builder::{AtlasBuilder, MAX_SHADOW_WIDTH, Padding},
+  charset::CharSet,
+  compare::{compare_atlases, diff_image},
+  config::{
+    CONFIG_VERSION, Config, apply_env_fields, default_config_file, migrate_table, migrate_text,
+    parse_rgb, select_profile,
+  },
+  coverage::{BLOCK_SIZE, BlockCoverage, bmp_coverage, coverage_image},
+  duplicates::{duplicate_warnings, shared_glyph_warning, shared_glyphs},
+  embed::{EmbeddedAtlas, rust_module},
+  encode::{FixedPalette, PngFilter, PngOptions, compression_from_level, save_png},
+  error::{Failure, FailureKind, exit_code},
+  font_check::FontDiagnosis,
+  hex::unifont_hex,
+  html::contact_sheet,
+  import::{ImportColors, charset_tiles, import_placements},
+  kerning::parse_kerning,
+  labels::{LABEL_CELLS, add_line_numbers, add_row_labels, line_number_cells},
+  lua::{LuaKey, lua_module},
+  mapping::{MapEncoding, Mapping, codepoint_label},
+  normalize::{WidthNormalization, normalize_width},
+  pack::PackMode,
+  pages::{
+    block_path, combine_pages, debug_path, page_index, page_path, split_pages, texture_page_layout,
+  },
+  pixel_count::verify_pixel_counts,
+  punctuation::{CHINESE_PUNCTUATION_MARKS, default_punctuation_offsets, vertical_punctuation},
+  quantize::{Dither, quantize},
+  render::{CellColors, CellPixel, RenderStyle, Rotation, blend},
+  rle::{encode_rle, rle_pixels},
+  script::{
+    IDEOGRAPHIC_SPACE, ScriptFormat, SortOrder, char_frequencies, frequency_report,
+    get_unique_chinese_chars, is_compat_ideograph, preprocess_script, read_script,
+    sort_by_frequency, sort_by_legacy_code,
+  },
+  stats::{PlacementCounts, RunStats, StageTimings},
+  tbl::{Tbl, parse_hex_code},
+  texturepacker::{FrameName, TexturePackerSheet, sprite_sheet_xml},
+  tsx::{relative_path, tiled_tileset},
+  verify::{VerifyColors, verify_atlas},
+  warning::{RenderContext, RenderWarning},
+  woff::to_sfnt,
+};
+use clap::{Args, Parser, Subcommand};
 use image::{Rgb, RgbImage};
 
-/// Fusion Pixel Font 10px = 9px + 1px padding
-const CHAR_SIZE: f32 = 9.0;
-/// https://baike.baidu.com/item/%E6%A0%87%E7%82%B9%E7%AC%A6%E5%8F%B7/588793
-/// https://zh.wikipedia.org/wiki/%E6%A0%87%E7%82%B9%E7%AC%A6%E5%8F%B7
-const CHINESE_PUNCTUATION_MARKS: [char; 32] = [
-  '·', '—', '‘', '’', '“', '”', '…', '、', '。', '〈', '〉', '《', '》', '「', '」', '『', '』',
-  '【', '】', '〔', '〕', '︰', '！', '（', '）', '，', '．', '：', '；', '？', '［', '］',
-];
 const CONFIG_FILE_NAME: &str = "config.toml";
 
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
+#[command(
+  version,
+  about,
+  long_about = None,
+  subcommand_negates_reqs = true,
+  args_conflicts_with_subcommands = true
+)]
 struct Cli {
+  #[command(subcommand)]
+  command: Option<Command>,
+  /// Use the `[profile.<NAME>]` table of the config file on top of its base values.
+  #[arg(long, value_name = "NAME", global = true)]
+  profile: Option<String>,
   /// Game script/text file for generating bitmap font image.
+  #[arg(
+    short,
+    long,
+    value_name = "FILE",
+    required_unless_present_any = [
+      "test_render", "print_punctuation_offsets", "font_size_detection", "migrate_config",
+      "output_coverage_image"
+    ]
+  )]
+  text: Option<PathBuf>,
+  /// Format of the game script file.
+  #[arg(long, value_enum, default_value_t = ScriptFormat::Plain)]
+  script_format: ScriptFormat,
+  /// Also render CJK Compatibility Ideographs (U+F900-U+FAFF), which are otherwise skipped and
+  /// listed in a warning.
+  #[arg(long, default_value_t = false)]
+  include_compat: bool,
+  /// Keep only the Chinese punctuation marks, for a separate punctuation atlas.
+  #[arg(long, default_value_t = false, conflicts_with = "exclude_punctuation")]
+  include_punctuation_only: bool,
+  /// Leave out the Chinese punctuation marks, for engines drawing them from another atlas.
+  #[arg(long, default_value_t = false)]
+  exclude_punctuation: bool,
+  /// Also emit a tile with the vertical presentation form (U+FE10-FE19, U+FE30-FE4F) of every
+  /// punctuation mark, after the regular tiles. Marks whose form no font has are rotated 90°.
+  /// The --map entries link the two tiles with `vertical_index` / `vertical_of`.
+  #[arg(long, default_value_t = false)]
+  vertical_punctuation: bool,
+  /// Warn about characters that change under NFC/NFD/NFKC/NFKD normalization and about distinct
+  /// characters of the script that normalize to the same one (e.g. CJK Compatibility Ideographs).
+  #[arg(long, default_value_t = false)]
+  duplicate_check: bool,
+  /// Keep U+3000 ideographic space as a blank tile.
+  #[arg(long, default_value_t = false)]
+  include_ideographic_space: bool,
+  /// Normalize punctuation width before extraction: `fullwidth` widens halfwidth marks next
+  /// to CJK text (! → ！), `halfwidth` does the opposite.
+  #[arg(long, value_enum, default_value_t = WidthNormalization::Fullwidth)]
+  normalize_width: WidthNormalization,
+  /// Character order in the atlas; `frequency` puts the most used characters first, `gb2312` and
+  /// `big5` follow the legacy encoding's code order.
+  #[arg(long, visible_alias = "order", value_enum, default_value_t = SortOrder::Codepoint)]
+  sort: SortOrder,
+  /// Write a CSV (character,codepoint,frequency) of every character, most frequent first.
+  #[arg(long, value_name = "FILE")]
+  freq_report: Option<PathBuf>,
+  /// Place the characters of this .tbl (HEX=char lines, UTF-8) at the tile index implied by their
+  /// code, `(code - --tbl-base) / --tbl-stride`, and the script's other characters into the free
+  /// tiles.
+  #[arg(
+    long,
+    value_name = "FILE",
+    conflicts_with_all = ["skip_chars", "split_by_block", "vertical_punctuation"]
+  )]
+  tbl_in: Option<PathBuf>,
+  /// Code of tile 0 for --tbl-in, in hex.
+  #[arg(
+    long,
+    value_name = "HEX",
+    default_value = "0",
+    value_parser = parse_hex_code,
+    requires = "tbl_in"
+  )]
+  tbl_base: u32,
+  /// Code distance between neighboring tiles for --tbl-in.
+  #[arg(
+    long,
+    value_name = "N",
+    default_value_t = 1,
+    value_parser = clap::value_parser!(u32).range(1..),
+    requires = "tbl_in"
+  )]
+  tbl_stride: u32,
+  /// Write the --tbl-in table with the codes of the added characters appended.
+  #[arg(long, value_name = "FILE", requires = "tbl_in")]
+  tbl_out: Option<PathBuf>,
+  /// Skip the first N characters of the sorted list; exported indices stay global.
+  #[arg(long, value_name = "N", default_value_t = 0)]
+  skip_chars: usize,
+  /// Render at most N characters (after sorting and --skip-chars), e.g. the N most common ones
+  /// with --sort frequency.
+  #[arg(
+    long,
+    visible_alias = "char-limit",
+    value_name = "N",
+    value_parser = clap::value_parser!(u64).range(1..)
+  )]
+  max_chars: Option<u64>,
+  /// Font file for generating bitmap font image (TTF/OTF/TTC or WOFF; WOFF2 needs the `woff2`
+  /// feature). Repeat to add fallback fonts, which are tried in order for each character.
+  #[cfg_attr(
+    not(feature = "system-fonts"),
+    arg(required_unless_present_any = ["print_punctuation_offsets", "migrate_config"])
+  )]
+  #[cfg_attr(
+    feature = "system-fonts",
+    arg(required_unless_present_any = ["print_punctuation_offsets", "migrate_config", "font_family"])
+  )]
   #[arg(short, long, value_name = "FILE")]
-  text: PathBuf,
-  /// Font file for generating bitmap font image.
-  #[arg(short, long, value_name = "FILE")]
-  font: PathBuf,
-  /// Font size(px), only support 10px or 11px.
-  #[arg(short, long, default_value_t = 10)]
-  size: u32,
+  font: Vec<PathBuf>,
+  /// Look the font up by family name in the OS font directories instead of passing --font.
+  #[cfg(feature = "system-fonts")]
+  #[arg(long, value_name = "NAME", conflicts_with = "font")]
+  font_family: Option<String>,
+  /// Font size(px), only support 10px or 11px. Defaults to 10px.
+  #[arg(short, long, visible_alias = "font-size-px", group = "font_size")]
+  size: Option<u32>,
+  /// Font size(pt), scales the glyphs directly instead of converting from px
+  /// (1px = 0.75pt at 96 DPI). Uses the 10px cell layout.
+  #[arg(long, value_name = "FLOAT", group = "font_size")]
+  font_size_pt: Option<f32>,
+  /// Rasterizer scale (px) instead of the one derived from the cell size; see
+  /// --font-size-detection.
+  #[arg(long, value_name = "FLOAT", conflicts_with = "font_size_pt")]
+  font_scale: Option<f32>,
+  /// Render '中' at scales 8.0 to 20.0 and print the --font-scale whose glyph best fills the
+  /// glyph area of --size, then exit.
+  #[arg(long, default_value_t = false, conflicts_with_all = ["font_size_pt", "font_scale"])]
+  font_size_detection: bool,
+  /// Write a map of the Unicode coverage of the fonts, then exit: one square per BMP block
+  /// (256 codepoints), 16 blocks per row, white when every codepoint has a glyph, gray when
+  /// some do, black when none do.
+  #[arg(long, value_name = "FILE", conflicts_with = "font_size_detection")]
+  output_coverage_image: Option<PathBuf>,
+  /// Side (px) of each block square of --output-coverage-image.
+  #[arg(
+    long,
+    value_name = "PIXELS",
+    default_value_t = 4,
+    requires = "output_coverage_image",
+    value_parser = clap::value_parser!(u32).range(1..=64)
+  )]
+  coverage_scale: u32,
+  /// Glyph drawing style; `hollow` draws only a 1px outline around each glyph, without shadow.
+  #[arg(long, value_enum, default_value_t = RenderStyle::Filled)]
+  render_style: RenderStyle,
+  /// Rotate every glyph (and its shadow) clockwise within its cell, for vertical text.
+  #[arg(long, value_name = "DEGREES", value_enum, default_value_t = Rotation::None)]
+  char_rotation: Rotation,
+  /// Rotate every finished cell (glyph and shadow) clockwise within the unchanged grid, for
+  /// engines that store their font texture rotated. Recorded as `tile_rotation` in --map.
+  #[arg(long, value_name = "DEGREES", value_enum, default_value_t = Rotation::None)]
+  rotate_tiles: Rotation,
+  /// Fake bold: draw every glyph twice, 1px apart horizontally, before the shadow. Glyphs that
+  /// no longer fit the glyph area are clipped with a warning. Color classes can be bold alone
+  /// with `bold = true`.
+  #[arg(long, default_value_t = false)]
+  bold: bool,
+  /// Fake italic: shear every glyph right by up to N px at the top (the top third by N, the
+  /// middle third by N/2). Color classes can be slanted alone with `slant = N`.
+  #[arg(
+    long,
+    value_name = "N",
+    default_value_t = 0,
+    value_parser = clap::value_parser!(u32).range(0..=2)
+  )]
+  slant: u32,
+  /// Shadow spread (px): every pixel within N px (Manhattan distance) of the glyph in the shadow
+  /// direction, so the drop shadow extends N px bottom-right and the 11px outline becomes N px
+  /// thick. Pixels spreading past the cell are cut off with a warning.
+  #[arg(
+    long,
+    value_name = "PIXELS",
+    default_value_t = 1,
+    value_parser = clap::value_parser!(u32).range(1..=MAX_SHADOW_WIDTH as i64)
+  )]
+  char_shadow_width: u32,
   /// Whether the font is zh-hant or zh-hans, for punctuation marks offset.
   #[arg(short, long, default_value_t = false)]
   is_zh_hant: bool,
+  /// Shift every glyph vertically by this many pixels after all other offsets; negative values
+  /// move glyphs up.
+  #[arg(
+    long,
+    value_name = "PIXELS",
+    default_value_t = 0,
+    allow_negative_numbers = true
+  )]
+  font_baseline_offset: i32,
   /// Output bitmap font image file (PNG only)
+  #[arg(
+    short,
+    long,
+    value_name = "FILE",
+    required_unless_present_any = [
+      "test_render", "print_punctuation_offsets", "font_size_detection", "migrate_config",
+      "output_coverage_image"
+    ]
+  )]
+  output: Option<PathBuf>,
+  /// Also write the atlas as a run-length encoded stream (for embedded systems), 1 byte per
+  /// pixel when the atlas has only the background and character colors, RGB otherwise.
+  #[arg(long, value_name = "FILE")]
+  encode_rle: Option<PathBuf>,
+  /// Trim trailing cell rows/columns that contain no characters.
+  #[arg(long, default_value_t = false)]
+  strip_unused_cells: bool,
+  /// Print extra notes about glyph placement.
+  #[arg(short, long, default_value_t = false)]
+  verbose: bool,
+  /// Check the config against these options before rendering: the atlas width stays within
+  /// 65535px, img_bg_color (or --color-key) differs from char_color, and char_shadow_color can
+  /// be told apart. Problems are printed with their field names.
+  #[arg(long, default_value_t = false)]
+  verify_config: bool,
+  /// Write a JSON mapping of every character to its atlas cell.
+  #[arg(short, long, value_name = "FILE")]
+  map: Option<PathBuf>,
+  /// Add the hex bytes of every character in this codec to the --map entries as `bytes` (`null`
+  /// when it cannot be encoded).
+  #[arg(long, value_enum, requires = "map")]
+  map_encoding: Option<MapEncoding>,
+  /// Read character pair adjustments (px) from this TOML file (`["我"]` then `"是" = -1`, keys as
+  /// characters or U+XXXX) into the --map entries' `kerning`.
+  #[arg(long, value_name = "FILE", requires = "map")]
+  kerning_file: Option<PathBuf>,
+  /// Write a single JSON file with the atlas PNG inlined as base64 and the rect of every
+  /// character, for HTML5 canvas engines.
+  #[arg(long, value_name = "FILE", conflicts_with_all = ["max_rows", "split_by_block"])]
+  base64_json: Option<PathBuf>,
+  /// Pretty-print the --base64-json file instead of minifying it.
+  #[arg(long, default_value_t = false, requires = "base64_json")]
+  pretty_json: bool,
+  /// Write a Rust source file with the atlas PNG as `FONT_ATLAS: &[u8]` and the --map JSON as
+  /// `FONT_ATLAS_METADATA: &str`, for games embedding the atlas with `include!`.
+  #[arg(
+    long,
+    value_name = "FILE",
+    conflicts_with_all = ["max_rows", "split_by_block", "texture_page_size"]
+  )]
+  output_rust: Option<PathBuf>,
+  /// Write the mapping as a Lua module returning a table keyed by character.
+  #[arg(long, value_name = "FILE")]
+  lua: Option<PathBuf>,
+  /// Key the --lua entries by character string or by codepoint integer.
+  #[arg(long, value_enum, default_value_t = LuaKey::Char, requires = "lua")]
+  lua_key: LuaKey,
+  /// Write the rendered char pixels (shadow excluded) of every character as GNU Unifont .hex
+  /// lines, 16x16, sorted by codepoint.
+  #[arg(long, value_name = "FILE")]
+  hex: Option<PathBuf>,
+  /// Check the pixel count of every rendered cell, warn about glyphs that rendered nothing or
+  /// only shadow, and print min/max/mean statistics.
+  #[arg(long, default_value_t = false)]
+  verify_pixel_count: bool,
+  /// Write a TexturePacker JSON (hash) sheet of the atlas. Paged atlases get one sheet per page.
+  #[arg(long, value_name = "FILE")]
+  texturepacker: Option<PathBuf>,
+  /// Name the --texturepacker frames by character or by U+XXXX codepoint.
+  #[arg(long, value_enum, default_value_t = FrameName::Codepoint, requires = "texturepacker")]
+  texturepacker_names: FrameName,
+  /// Write a TexturePacker XML (<TextureAtlas>) sprite sheet of the atlas, with U+XXXX sprite
+  /// names, for Unity and other engines importing that format. Paged atlases get one per page.
+  #[arg(long, value_name = "FILE")]
+  sprite_sheet_xml: Option<PathBuf>,
+  /// Write a Tiled tileset (.tsx) of the atlas, with the character and codepoint of every tile as
+  /// tile properties. Paged atlases get one tileset per page.
+  #[arg(long, value_name = "FILE", conflicts_with = "char_group_spacing")]
+  tsx: Option<PathBuf>,
+  /// Start a new row, N px lower, whenever the Unicode block of the characters changes
+  /// (approximated as codepoint / 256).
+  #[arg(
+    long,
+    value_name = "PIXELS",
+    default_value_t = 0,
+    conflicts_with_all = ["row_label", "line_numbers", "debug_checker", "strip_unused_cells"]
+  )]
+  char_group_spacing: u32,
+  /// Leave a blank row (background only) between Unicode blocks (approximated as codepoint /
+  /// 256). The atlas grows by one row per separator; --map row positions skip the blank rows.
+  #[arg(
+    long,
+    conflicts_with_all = ["char_group_spacing", "tsx", "row_label", "line_numbers", "debug_checker", "strip_unused_cells"]
+  )]
+  line_separator: bool,
+  /// Split the atlas into pages of at most N cell rows, written as <stem>_000.png, ...
+  #[arg(
+    long,
+    value_name = "N",
+    conflicts_with_all = ["html", "encode_rle", "strip_unused_cells"]
+  )]
+  max_rows: Option<usize>,
+  /// Fit the atlas into texture pages of W x H px (powers of two): chars_per_line and the rows
+  /// per page are computed from the cell size, and extra pages are written like --max-rows.
+  #[arg(
+    long,
+    num_args = 2,
+    value_names = ["W", "H"],
+    conflicts_with_all = [
+      "max_rows", "optimize_atlas", "split_by_block", "base64_json", "html", "encode_rle",
+      "strip_unused_cells", "row_label", "line_numbers"
+    ]
+  )]
+  texture_page_size: Option<Vec<u32>>,
+  /// Write one atlas per Unicode block (256-codepoint range) as <stem>_block_4E.png, ...; the
+  /// --map entries get the `file` of their atlas, their `index` counts on across the atlases.
+  #[arg(
+    long,
+    default_value_t = false,
+    conflicts_with_all = [
+      "max_rows", "html", "encode_rle", "strip_unused_cells", "row_label", "line_numbers",
+      "class_chars", "pad_to_count"
+    ]
+  )]
+  split_by_block: bool,
+  /// Write a JSON index of the page, position and file of every character.
+  #[arg(long, value_name = "FILE")]
+  page_index_file: Option<PathBuf>,
+  /// Exit with code 5 when any glyph is missing from the fonts.
+  #[arg(long, default_value_t = false)]
+  fail_on_missing: bool,
+  /// Exit with code 6 when the characters don't fit a single atlas page.
+  #[arg(long, default_value_t = false)]
+  fail_on_overflow: bool,
+  /// Write the end-of-run summary statistics as JSON.
+  #[arg(long, value_name = "FILE")]
+  stats_json: Option<PathBuf>,
+  /// Prefix each row with the Unicode block of its first character, drawn with this (ASCII)
+  /// font. The label takes 3 cells, so fewer characters fit on each row.
+  #[arg(long, value_name = "FONT")]
+  row_label: Option<PathBuf>,
+  /// Prefix each row with its 0-based index and the codepoint of its first character, e.g.
+  /// "0042: U+4E42", drawn with a built-in digit font. The label takes 65px worth of cells.
+  #[arg(long, default_value_t = false, conflicts_with = "row_label")]
+  line_numbers: bool,
+  /// Draw the --line-numbers labels with this (ASCII) font instead of the built-in one.
+  #[arg(long, value_name = "FONT", requires = "line_numbers")]
+  label_font: Option<PathBuf>,
+  /// Write an HTML contact sheet showing every tile at 4x zoom, with problem tiles highlighted.
+  #[arg(long, value_name = "FILE")]
+  html: Option<PathBuf>,
+  /// Transparency color key (R,G,B) used as the background color, e.g. 255,0,255.
+  #[arg(long, value_name = "R,G,B", value_parser = parse_rgb)]
+  color_key: Option<[u8; 3]>,
+  /// Pick the chars_per_line giving the smallest near-square atlas (at most one more row than
+  /// columns or column than rows) instead of the configured one. The character order is
+  /// unchanged.
+  #[arg(long, default_value_t = false, conflicts_with_all = ["row_label", "line_numbers"])]
+  optimize_atlas: bool,
+  /// Pad the atlas with blank tiles up to a multiple of chars_per_line.
+  #[arg(long, default_value_t = false, conflicts_with = "pad_to_count")]
+  pad_to_full_rows: bool,
+  /// Pad the atlas with blank tiles up to exactly N tiles.
+  #[arg(long, value_name = "N")]
+  pad_to_count: Option<usize>,
+  /// PNG compression level 0-9 (0-2 fastest, 3-6 default, 7-9 best).
+  #[arg(long, value_name = "LEVEL", value_parser = clap::value_parser!(u8).range(0..=9))]
+  png_compression: Option<u8>,
+  /// PNG filter type; SUB usually compresses font atlases best.
+  #[arg(long, value_enum, default_value_t = PngFilter::Adaptive)]
+  png_filter: PngFilter,
+  /// Write an indexed (palette) PNG with the smallest bit depth (1/2/4/8 bpp) that fits the
+  /// atlas colors.
+  #[arg(long, default_value_t = false)]
+  png_indexed: bool,
+  /// Ordered dithering used when --png-indexed reduces the atlas to the config's
+  /// target_palette; `none` maps each pixel to the nearest palette color.
+  #[arg(long, value_enum, default_value_t = Dither::None)]
+  dither: Dither,
+  /// Atlas layout. `tight` packs each glyph's ink rect instead of a uniform grid, so it needs
+  /// --map to be usable.
+  #[arg(long, value_enum, default_value_t = PackMode::Grid, requires_if("tight", "map"))]
+  pack: PackMode,
+  /// Cell background pattern; `checkerboard` alternates img_bg_color and img_alt_bg_color to
+  /// check cell alignment.
+  #[arg(
+    long,
+    value_enum,
+    default_value_t = CharBackgroundMode::None,
+    conflicts_with = "strip_unused_cells"
+  )]
+  char_background_mode: CharBackgroundMode,
+  /// Tile this PNG across the atlas instead of the solid img_bg_color; transparent pixels are
+  /// flattened against white.
+  #[arg(
+    long,
+    value_name = "FILE",
+    conflicts_with_all = ["strip_unused_cells", "debug_checker", "row_label", "line_numbers"]
+  )]
+  background_image: Option<PathBuf>,
+  /// Color the characters by the char_colors table of the config (Unicode block labels or
+  /// U+XXXX-U+YYYY ranges); unlisted characters keep char_color.
+  #[arg(long, default_value_t = false)]
+  char_color_per_block: bool,
+  /// Draw a 1px grid line (R,G,B) on the inner edge of every cell, under the glyphs. Without a
+  /// value the config's grid_line_color is used.
+  #[arg(long, value_name = "R,G,B", num_args = 0..=1, value_parser = parse_rgb)]
+  cell_border_color: Option<Option<[u8; 3]>>,
+  /// Also write <stem>_debug.png with alternating cell backgrounds (img_bg_color and
+  /// img_alt_bg_color); the atlas itself is unchanged.
+  #[arg(long, default_value_t = false)]
+  debug_checker: bool,
+  /// Flip the atlas vertically (bottom-left texture origin); exported coordinates follow.
+  /// Cannot be combined with --strip-unused-cells, whose cropped-off tiles have no mirrored
+  /// position.
+  #[arg(long, default_value_t = false, conflicts_with = "strip_unused_cells")]
+  flip_y: bool,
+  /// Flip the atlas horizontally; exported coordinates follow. Cannot be combined with
+  /// --strip-unused-cells, whose cropped-off tiles have no mirrored position.
+  #[arg(long, default_value_t = false, conflicts_with = "strip_unused_cells")]
+  flip_x: bool,
+  /// Duplicate the characters of FILE as extra tiles in the color of the `[color_classes]`
+  /// entry NAME. Repeatable.
+  #[arg(long, value_name = "NAME=FILE", value_parser = parse_class_chars)]
+  class_chars: Vec<(String, PathBuf)>,
+  /// Render only this character and print its cell as ASCII art (`#` glyph, `o` shadow, `.`
+  /// background) instead of generating an atlas.
+  #[arg(long, value_name = "CHAR")]
+  test_render: Option<char>,
+  /// Print the built-in punctuation offsets (zh-hant ones with --is-zh-hant) as
+  /// punctuation_offsets_10px/11px config tables, ready to paste into config.toml, and exit.
+  #[arg(long, default_value_t = false)]
+  print_punctuation_offsets: bool,
+  /// Rewrite config.toml in the current config format (see its `version`), keeping the values
+  /// and comments, and exit.
+  #[arg(long, default_value_t = false)]
+  migrate_config: bool,
+  /// Open the atlas (first page) in a zoomed preview window; Esc closes it.
+  #[cfg(feature = "preview")]
+  #[arg(long, default_value_t = false)]
+  show: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+  /// Check a (hand-edited) atlas against its --map export: tiles inside the image and on the
+  /// grid, character pixels in every found glyph, none in missing ones, no overlapping tiles.
+  Verify(VerifyArgs),
+  /// Rebuild the placement records of an existing grid atlas from its image and a .tbl or
+  /// ordered charset file, and write the metadata exports. Tiles that are entirely background
+  /// are marked missing.
+  Import(ImportArgs),
+  /// Diff two versions of an atlas tile by tile and list the characters whose tiles changed.
+  /// Exits with 1 when any tile differs or the image size changed.
+  Compare(CompareArgs),
+}
+
+#[derive(Args)]
+struct ImportArgs {
+  /// Atlas image to import.
+  #[arg(long, value_name = "FILE")]
+  image: PathBuf,
+  /// Tile size (px) of the atlas.
+  #[arg(
+    short,
+    long,
+    default_value_t = 10,
+    value_parser = clap::value_parser!(u32).range(1..)
+  )]
+  size: u32,
+  /// .tbl (HEX=char lines, UTF-8) giving the character of each tile by its code.
+  #[arg(
+    long,
+    value_name = "FILE",
+    required_unless_present = "charset",
+    conflicts_with = "charset"
+  )]
+  tbl: Option<PathBuf>,
+  /// Code of tile 0 for --tbl, in hex.
+  #[arg(long, value_name = "HEX", default_value = "0", value_parser = parse_hex_code)]
+  tbl_base: u32,
+  /// Code distance between neighboring tiles for --tbl.
+  #[arg(
+    long,
+    value_name = "N",
+    default_value_t = 1,
+    value_parser = clap::value_parser!(u32).range(1..)
+  )]
+  tbl_stride: u32,
+  /// Text file listing the characters of the tiles in order (whitespace is skipped).
+  #[arg(long, value_name = "FILE")]
+  charset: Option<PathBuf>,
+  /// Write the JSON mapping.
   #[arg(short, long, value_name = "FILE")]
-  output: PathBuf,
+  map: Option<PathBuf>,
+  /// Write the mapping as a Lua module.
+  #[arg(long, value_name = "FILE")]
+  lua: Option<PathBuf>,
+  /// Key the --lua entries by character string or by codepoint integer.
+  #[arg(long, value_enum, default_value_t = LuaKey::Char, requires = "lua")]
+  lua_key: LuaKey,
+  /// Write the character pixels as GNU Unifont .hex lines.
+  #[arg(long, value_name = "FILE")]
+  hex: Option<PathBuf>,
+  /// Write a Tiled tileset.
+  #[arg(long, value_name = "FILE")]
+  tsx: Option<PathBuf>,
+  /// Write a TexturePacker JSON (hash) sheet.
+  #[arg(long, value_name = "FILE")]
+  texturepacker: Option<PathBuf>,
+  /// Name the --texturepacker frames by character or by U+XXXX codepoint.
+  #[arg(long, value_enum, default_value_t = FrameName::Codepoint, requires = "texturepacker")]
+  texturepacker_names: FrameName,
+  /// Write a TexturePacker XML (<TextureAtlas>) sprite sheet.
+  #[arg(long, value_name = "FILE")]
+  sprite_sheet_xml: Option<PathBuf>,
 }
 
-fn main() -> Result<()> {
+#[derive(Args)]
+struct VerifyArgs {
+  /// Atlas image (one page) to check.
+  #[arg(long, value_name = "FILE")]
+  image: PathBuf,
+  /// Mapping JSON written with --map.
+  #[arg(short, long, value_name = "FILE")]
+  map: PathBuf,
+  /// Cell size (px) of the atlas.
+  #[arg(
+    short,
+    long,
+    default_value_t = 10,
+    value_parser = clap::value_parser!(u32).range(1..)
+  )]
+  size: u32,
+  /// Page of a multi-page mapping that the image holds.
+  #[arg(long, value_name = "N", default_value_t = 0)]
+  page: usize,
+}
+
+#[derive(Args)]
+struct CompareArgs {
+  /// Atlas image (one page) before the change.
+  #[arg(long, value_name = "FILE")]
+  old: PathBuf,
+  /// Atlas image (one page) after the change.
+  #[arg(long, value_name = "FILE")]
+  new: PathBuf,
+  /// Mapping JSON written with --map, giving the tiles to compare.
+  #[arg(short, long, value_name = "FILE")]
+  map: PathBuf,
+  /// Cell size (px) of the atlas.
+  #[arg(short, long, default_value_t = 10)]
+  size: u32,
+  /// Page of a multi-page mapping that the images hold.
+  #[arg(long, value_name = "N", default_value_t = 0)]
+  page: usize,
+  /// Write the new atlas with the changed tiles tinted red and their changed pixels in red.
+  #[arg(long, value_name = "FILE")]
+  diff_image: Option<PathBuf>,
+}
+
+/// Parse a `--class-chars` value.
+fn parse_class_chars(value: &str) -> Result<(String, PathBuf), String> {
+  match value.split_once('=') {
+    Some((name, file)) if !name.is_empty() && !file.is_empty() => {
+      Ok((name.to_string(), PathBuf::from(file)))
+    }
+    _ => Err("expected NAME=FILE".to_string()),
+  }
+}
+
+fn main() -> ExitCode {
   let cli = Cli::parse();
-  // Check if game script file exists.
-  let game_script = if cli.text.exists() {
-    fs::read_to_string(&cli.text)?
-  } else {
-    bail!("[Error] Game script file not found!");
+  match run(cli) {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(error) => {
+      eprintln!("Error: {:?}", error);
+      ExitCode::from(exit_code(&error))
+    }
+  }
+}
+
+fn read_input(path: &Path) -> Result<Vec<u8>> {
+  fs::read(path).map_err(|e| {
+    Failure::new(
+      FailureKind::Input,
+      format!("[Error] Could not read {}: {}", path.display(), e),
+    )
+    .into()
+  })
+}
+
+/// `--font-family`: the installed font of the family and its face index, `None` without the
+/// option.
+#[cfg(feature = "system-fonts")]
+fn family_font(cli: &Cli) -> Result<Option<(PathBuf, u32)>> {
+  let Some(family) = &cli.font_family else {
+    return Ok(None);
   };
-  // Check if font file exists.
-  let font_file = if cli.font.exists() {
-    fs::read(&cli.font)?
+  let font = chinese_bitmap_font_demo::system_fonts::find_family(family)
+    .map_err(|e| Failure::new(FailureKind::Input, e.to_string()))?;
+  if cli.verbose {
+    println!(
+      "[Note] Font family '{}' resolved to {} (face #{}).",
+      family,
+      font.path.display(),
+      font.index
+    );
+  }
+  Ok(Some((font.path, font.index)))
+}
+
+#[cfg(not(feature = "system-fonts"))]
+fn family_font(_cli: &Cli) -> Result<Option<(PathBuf, u32)>> {
+  Ok(None)
+}
+
+/// Number of tiles the atlas lays out: the characters, their vertical punctuation forms and the
+/// color class tiles, or the `--pad-to-count` total.
+fn tile_count(cli: &Cli, chars: &[char], classes: &[ColorClassTiles]) -> usize {
+  let vertical_count = if cli.vertical_punctuation {
+    vertical_punctuation(chars).len()
   } else {
-    bail!("[Error] Font file not found!");
+    0
+  };
+  cli.pad_to_count.unwrap_or(
+    chars.len() + vertical_count + classes.iter().map(|class| class.chars.len()).sum::<usize>(),
+  )
+}
+
+/// `--test-render`: render a single character and print its cell with a color legend.
+fn test_render(cli: &Cli, font_files: Vec<(Vec<u8>, u32)>, config: &Config, c: char) -> Result<()> {
+  let mut builder = AtlasBuilder::from_config(config)
+    .size(cli.size.unwrap_or(10))
+    .zh_hant(cli.is_zh_hant)
+    .style(cli.render_style)
+    .rotation(cli.char_rotation)
+    .tile_rotation(cli.rotate_tiles)
+    .bold(cli.bold)
+    .slant(cli.slant)
+    .shadow_width(cli.char_shadow_width)
+    .vertical_punctuation(cli.vertical_punctuation)
+    .baseline_offset(cli.font_baseline_offset)
+    .chars_per_line(1);
+  for (font_file, face_index) in font_files {
+    builder = builder.font_collection_bytes(font_file, face_index);
+  }
+  if let Some(font_size_pt) = cli.font_size_pt {
+    builder = builder.font_size_pt(font_size_pt);
+  }
+  if let Some(font_scale) = cli.font_scale {
+    builder = builder.font_scale(font_scale);
+  }
+  let generator = builder.build()?;
+  let mut context = RenderContext::default();
+  let atlas = generator.generate_with_context(&[c], &[], &mut context)?;
+  let options = generator.options();
+
+  // The cell tells the pixels apart; a translucent shadow takes the color of what is below it.
+  let cell = atlas.cells.first().and_then(Option::as_ref);
+  let (mut char_colors, mut shadow_colors) = (Vec::new(), Vec::new());
+  for (y, row) in atlas.image.rows().enumerate() {
+    let line = row
+      .enumerate()
+      .map(|(x, pixel)| {
+        let (symbol, colors) = match cell.map(|cell| cell.get(x as u32, y as u32)) {
+          Some(CellPixel::Char) => ('#', &mut char_colors),
+          Some(CellPixel::Shadow) => ('o', &mut shadow_colors),
+          _ => return '.',
+        };
+        if !colors.contains(pixel) {
+          colors.push(*pixel);
+        }
+        symbol
+      })
+      .collect::<String>();
+    println!("{}", line);
+  }
+  let rgb = |color: Rgb<u8>| format!("{},{},{}", color[0], color[1], color[2]);
+  for (symbol, colors) in [('#', &char_colors), ('o', &shadow_colors)] {
+    if !colors.is_empty() {
+      println!(
+        "{} = {}",
+        symbol,
+        colors
+          .iter()
+          .map(|color| rgb(*color))
+          .collect::<Vec<_>>()
+          .join(" / ")
+      );
+    }
+  }
+  println!(". = {}", rgb(options.bg_color));
+  for warning in context.sorted_warnings() {
+    println!("{}", warning);
+  }
+  Ok(())
+}
+
+/// `--font-size-detection`: measure '中' at the calibration scales of the first font and print
+/// the best --font-scale for the cell size.
+fn font_size_detection(cli: &Cli, font_files: Vec<(Vec<u8>, u32)>, config: &Config) -> Result<()> {
+  let font_size = cli.size.unwrap_or(10);
+  let mut builder = AtlasBuilder::from_config(config).size(font_size);
+  for (font_file, face_index) in font_files {
+    builder = builder.font_collection_bytes(font_file, face_index);
+  }
+  let generator = builder.build()?;
+  let options = generator.options();
+  let target = options.shadow.glyph_area_size(font_size);
+  let samples = calibrate_scale(&generator.fonts()[0], options.coverage);
+  println!("Scale  Size of '{}'", CALIBRATION_CHAR);
+  for (scale, (width, height)) in &samples {
+    println!("{:>5.1}  {}x{}", scale, width, height);
+  }
+  let Some(scale) = closest_scale(&samples, target) else {
+    bail!(Failure::new(
+      FailureKind::MissingGlyphs,
+      format!(
+        "[Error] The font has no visible glyph for '{}'!",
+        CALIBRATION_CHAR
+      )
+    ));
+  };
+  println!(
+    "Recommended: --font-scale {:.1} (target {}x{} px, default scale {:.1})",
+    scale,
+    target,
+    target,
+    glyph_scale(target as f32).x
+  );
+  Ok(())
+}
+
+/// `--output-coverage-image`: write the BMP block coverage of the fallback chain.
+fn output_coverage_image(
+  cli: &Cli,
+  coverage_file: &Path,
+  font_files: Vec<(Vec<u8>, u32)>,
+  config: &Config,
+) -> Result<()> {
+  let mut builder = AtlasBuilder::from_config(config);
+  for (font_file, face_index) in font_files {
+    builder = builder.font_collection_bytes(font_file, face_index);
+  }
+  let generator = builder.build()?;
+  let blocks = bmp_coverage(generator.fonts());
+  let count = |coverage| blocks.iter().filter(|block| **block == coverage).count();
+  println!(
+    "Coverage: {} full, {} partial, {} empty blocks of {} codepoints.",
+    count(BlockCoverage::Full),
+    count(BlockCoverage::Partial),
+    count(BlockCoverage::None),
+    BLOCK_SIZE
+  );
+  save_png(
+    &coverage_image(&blocks, cli.coverage_scale),
+    coverage_file,
+    PngOptions::default(),
+  )?;
+  Ok(())
+}
+
+/// Read a mapping JSON written with --map.
+fn read_mapping(path: &Path) -> Result<Mapping> {
+  let mapping = serde_json::from_slice(&read_input(path)?).map_err(|e| {
+    Failure::new(
+      FailureKind::Input,
+      format!("[Error] Cannot parse {}: {}", path.display(), e),
+    )
+  })?;
+  Ok(mapping)
+}
+
+/// `compare` subcommand: list the tiles that differ between two atlas versions and fail if
+/// there is any.
+fn compare(args: &CompareArgs) -> Result<()> {
+  let old = read_image(&args.old)?;
+  let new = read_image(&args.new)?;
+  let mapping = read_mapping(&args.map)?;
+  if mapping.cell_size != args.size {
+    println!(
+      "[Warning] The mapping is for {}px cells, not {}px!",
+      mapping.cell_size, args.size
+    );
+  }
+  let resized = old.dimensions() != new.dimensions();
+  if resized {
+    println!(
+      "[Warning] The atlas changed size from {}x{} to {}x{}, only the tiles inside both images \
+       are compared.",
+      old.width(),
+      old.height(),
+      new.width(),
+      new.height()
+    );
+  }
+  let diff = compare_atlases(&old, &new, &mapping, args.page);
+  for tile in &diff.changed {
+    println!("{}", tile);
+  }
+  if diff.skipped > 0 {
+    println!(
+      "[Warning] {} tiles lie outside one of the images and were not compared.",
+      diff.skipped
+    );
+  }
+  if let Some(diff_file) = &args.diff_image {
+    save_png(
+      &diff_image(&old, &new, &diff),
+      diff_file,
+      PngOptions::default(),
+    )?;
+  }
+  if diff.changed.is_empty() && resized {
+    bail!(
+      "[Error] {} and {} differ in size!",
+      args.old.display(),
+      args.new.display()
+    );
+  }
+  if !diff.changed.is_empty() {
+    bail!(
+      "[Error] {} of {} tiles differ between {} and {}!",
+      diff.changed.len(),
+      diff.compared,
+      args.old.display(),
+      args.new.display()
+    );
+  }
+  println!("{} tiles compared, no differences found.", diff.compared);
+  Ok(())
+}
+
+/// Read an atlas image as RGB.
+fn read_image(path: &Path) -> Result<RgbImage> {
+  let image = image::load_from_memory(&read_input(path)?).map_err(|e| {
+    Failure::new(
+      FailureKind::Input,
+      format!("[Error] Cannot read {}: {}", path.display(), e),
+    )
+  })?;
+  Ok(image.to_rgb8())
+}
+
+/// Read and parse a UTF-8 `.tbl` file, returning its text too.
+fn read_tbl(path: &Path) -> Result<(Tbl, String)> {
+  let text = String::from_utf8(read_input(path)?).map_err(|_| {
+    Failure::new(
+      FailureKind::Input,
+      format!("[Error] {} is not UTF-8 text!", path.display()),
+    )
+  })?;
+  let tbl = Tbl::parse(&text).map_err(|e| Failure::new(FailureKind::Input, e.to_string()))?;
+  Ok((tbl, text))
+}
+
+/// `import` subcommand: rebuild the placement records of an existing atlas and re-export them.
+fn import(args: &ImportArgs, config: &Config) -> Result<()> {
+  let image = read_image(&args.image)?;
+  let tiles = match (&args.tbl, &args.charset) {
+    (Some(tbl_file), _) => {
+      let (tbl, _) = read_tbl(tbl_file)?;
+      let layout = tbl.layout(&[], args.tbl_base, args.tbl_stride);
+      for problem in &layout.problems {
+        println!("{}", problem);
+      }
+      layout
+        .chars
+        .iter()
+        .zip(&layout.codes)
+        .enumerate()
+        .filter(|(_, (_, code))| code.is_some())
+        .map(|(index, (c, _))| (index, *c))
+        .collect::<Vec<_>>()
+    }
+    (None, Some(charset_file)) => {
+      charset_tiles(&String::from_utf8_lossy(&read_input(charset_file)?))
+    }
+    (None, None) => unreachable!("clap requires --tbl or --charset"),
   };
-  // Check if font size is 10px or 11px.
-  if cli.size != 10 && cli.size != 11 {
-    bail!("[Error] Only support 10px or 11px!");
+  let mut bg_colors = vec![Rgb(config.img_bg_color), Rgb(config.alt_bg_color())];
+  bg_colors.extend(config.grid_line_color.map(Rgb));
+  let colors = ImportColors {
+    char_color: Rgb(config.char_color),
+    bg_colors,
+  };
+  let (placements, cells) = import_placements(&image, args.size, &tiles, &colors)
+    .map_err(|e| Failure::new(FailureKind::InvalidArguments, e.to_string()))?;
+  let chars_per_line = (image.width() / args.size) as usize;
+  let tile_count = chars_per_line * (image.height() / args.size) as usize;
+  println!(
+    "{} tiles imported from {} ({} entirely background, marked missing).",
+    placements.len(),
+    args.image.display(),
+    placements
+      .iter()
+      .filter(|placement| placement.missing)
+      .count()
+  );
+
+  let mapping = Mapping::from_placements(
+    args.image.display().to_string(),
+    args.size,
+    chars_per_line,
+    PackMode::Grid,
+    tile_count,
+    &placements,
+  );
+  if let Some(map_file) = &args.map {
+    fs::write(map_file, serde_json::to_string_pretty(&mapping)?)?;
   }
-  let font_size = cli.size;
+  if let Some(lua_file) = &args.lua {
+    let page_files = [args.image.clone()];
+    fs::write(lua_file, lua_module(&mapping, &page_files, args.lua_key))?;
+  }
+  if let Some(hex_file) = &args.hex {
+    fs::write(hex_file, unifont_hex(&placements, &cells))?;
+  }
+  let placement_refs = placements.iter().collect::<Vec<_>>();
+  if let Some(tsx_file) = &args.tsx {
+    let tsx_dir = tsx_file.parent().unwrap_or(Path::new(""));
+    let name = args
+      .image
+      .file_stem()
+      .map(|stem| stem.to_string_lossy().into_owned())
+      .unwrap_or_default();
+    let tsx = tiled_tileset(
+      &name,
+      &relative_path(tsx_dir, &args.image),
+      image.dimensions(),
+      args.size,
+      &placement_refs,
+    );
+    fs::write(tsx_file, tsx)?;
+  }
+  if let Some(sheet_file) = &args.texturepacker {
+    let sheet_dir = sheet_file.parent().unwrap_or(Path::new(""));
+    let sheet = TexturePackerSheet::new(
+      &relative_path(sheet_dir, &args.image),
+      image.dimensions(),
+      args.size,
+      &placement_refs,
+      args.texturepacker_names,
+    );
+    fs::write(sheet_file, serde_json::to_string_pretty(&sheet)?)?;
+  }
+  if let Some(xml_file) = &args.sprite_sheet_xml {
+    let xml_dir = xml_file.parent().unwrap_or(Path::new(""));
+    let xml = sprite_sheet_xml(
+      &relative_path(xml_dir, &args.image),
+      image.dimensions(),
+      args.size,
+      &placement_refs,
+    );
+    fs::write(xml_file, xml)?;
+  }
+  Ok(())
+}
 
-  let is_zh_hant = cli.is_zh_hant;
-  let output_file = cli.output;
+/// `verify` subcommand: report every problem of the atlas and fail if there is any.
+fn verify(args: &VerifyArgs, config: &Config) -> Result<()> {
+  let image = read_image(&args.image)?;
+  let mapping = read_mapping(&args.map)?;
+  let mut non_char_colors = vec![
+    Rgb(config.img_bg_color),
+    Rgb(config.alt_bg_color()),
+    Rgb(config.char_shadow_color),
+  ];
+  non_char_colors.extend(config.grid_line_color.map(Rgb));
+  non_char_colors.extend(
+    config
+      .color_classes
+      .values()
+      .filter_map(|class| class.shadow_color.map(Rgb)),
+  );
+  let colors = VerifyColors {
+    char_color: Rgb(config.char_color),
+    class_colors: config
+      .color_classes
+      .iter()
+      .map(|(name, class)| (name.clone(), Rgb(class.color)))
+      .collect(),
+    gradient: config.char_gradient.is_some(),
+    non_char_colors,
+  };
+  let problems = verify_atlas(&image, &mapping, args.page, args.size, &colors);
+  for problem in &problems {
+    println!("{}", problem);
+  }
+  let checked = mapping
+    .chars
+    .iter()
+    .filter(|entry| entry.page == args.page)
+    .count();
+  if !problems.is_empty() {
+    bail!(
+      "[Error] {} problems found in {} ({} tiles checked)!",
+      problems.len(),
+      args.image.display(),
+      checked
+    );
+  }
+  println!("{} tiles checked, no problems found.", checked);
+  Ok(())
+}
 
-  // Load config file.
+/// `config.toml` next to the executable.
+fn config_path() -> Result<PathBuf> {
   let exe_dir = std::env::current_exe()?.parent().unwrap().to_path_buf();
-  let config_file = exe_dir.join(CONFIG_FILE_NAME);
-  let config = if config_file.exists() {
-    if let Ok(config) = toml::from_str(&fs::read_to_string(&config_file)?) {
-      config
+  Ok(exe_dir.join(CONFIG_FILE_NAME))
+}
+
+/// Load config.toml next to the executable (writing the default one on first run) and apply the
+/// environment overrides.
+fn load_config(profile: Option<&str>) -> Result<Config> {
+  let config_file = config_path()?;
+  let mut table = if config_file.exists() {
+    if let Ok(table) = toml::from_str(&fs::read_to_string(&config_file)?) {
+      table
     } else {
       println!("[Warning] Invalid config file, using default config.");
-      Config::default()
+      toml::Table::new()
     }
   } else {
     println!("[Warning] Config file not found, writing and using default config.");
-    fs::write(&config_file, toml::to_string_pretty(&Config::default())?)?;
-    Config::default()
+    fs::write(&config_file, default_config_file()?)?;
+    toml::Table::new()
   };
+  let migration = migrate_table(&mut table)
+    .map_err(|e| Failure::new(FailureKind::InvalidArguments, e.to_string()))?;
+  for message in migration {
+    println!("{}", message);
+  }
+  let mut table = select_profile(table, profile)
+    .map_err(|e| Failure::new(FailureKind::InvalidArguments, e.to_string()))?;
+  if let Err(e) = table.clone().try_into::<Config>() {
+    // The profile was asked for by name, so falling back to the defaults would drop it silently.
+    if let Some(profile) = profile {
+      bail!(Failure::new(
+        FailureKind::InvalidArguments,
+        format!(
+          "[Error] Invalid config for profile '{}': {}",
+          profile,
+          e.message()
+        )
+      ));
+    }
+    println!("[Warning] Invalid config file, using default config.");
+    table = toml::Table::new();
+  }
+  // Priority: command line > environment variables > config file > defaults.
+  let config = apply_env_fields(table, std::env::vars())
+    .and_then(|config| config.validate().map(|_| config))
+    .map_err(|e| Failure::new(FailureKind::InvalidArguments, e.to_string()))?;
+  Ok(config)
+}
+
+fn run(cli: Cli) -> Result<()> {
+  match &cli.command {
+    Some(Command::Verify(args)) => return verify(args, &load_config(cli.profile.as_deref())?),
+    Some(Command::Import(args)) => return import(args, &load_config(cli.profile.as_deref())?),
+    Some(Command::Compare(args)) => return compare(args),
+    None => {}
+  }
+  if cli.migrate_config {
+    let config_file = config_path()?;
+    if !config_file.exists() {
+      bail!(Failure::new(
+        FailureKind::Input,
+        "[Error] Config file not found, nothing to migrate!"
+      ));
+    }
+    let migrated = migrate_text(&fs::read_to_string(&config_file)?)
+      .map_err(|e| Failure::new(FailureKind::InvalidArguments, e.to_string()))?;
+    match migrated {
+      Some(text) => {
+        fs::write(&config_file, text)?;
+        println!(
+          "[Note] Migrated {} to version {}.",
+          config_file.display(),
+          CONFIG_VERSION
+        );
+      }
+      None => println!("[Note] {} is up to date.", config_file.display()),
+    }
+    return Ok(());
+  }
+  if cli.print_punctuation_offsets {
+    let offsets = default_punctuation_offsets(cli.is_zh_hant);
+    let tables = BTreeMap::from([
+      ("punctuation_offsets_10px", &offsets),
+      ("punctuation_offsets_11px", &offsets),
+    ]);
+    print!("{}", toml::to_string(&tables)?);
+    return Ok(());
+  }
+  // Check if game script file exists.
+  let game_script = match &cli.text {
+    Some(text) if text.exists() => read_script(&read_input(text)?, cli.script_format)
+      .map_err(|e| Failure::new(FailureKind::Input, e.to_string()))?,
+    Some(_) => bail!(Failure::new(
+      FailureKind::Input,
+      "[Error] Game script file not found!"
+    )),
+    // Only --test-render runs without a script.
+    None => String::new(),
+  };
+  // Check if font files exist.
+  let font_paths = cli
+    .font
+    .iter()
+    .map(|path| (path.clone(), 0))
+    .chain(family_font(&cli)?)
+    .collect::<Vec<_>>();
+  let mut font_files = Vec::with_capacity(font_paths.len());
+  for (font_path, face_index) in &font_paths {
+    if font_path.exists() {
+      font_files.push((read_input(font_path)?, *face_index));
+    } else {
+      bail!(Failure::new(
+        FailureKind::Input,
+        format!("[Error] Font file not found: {}", font_path.display())
+      ));
+    }
+  }
+  let font_size = cli.size.unwrap_or(10);
+
+  let mut config = load_config(cli.profile.as_deref())?;
+  if cli.verify_config {
+    let check = config.check_with(font_size, cli.color_key);
+    for line in check.warnings.iter().chain(&check.errors) {
+      println!("{}", line);
+    }
+    if !check.errors.is_empty() {
+      bail!(Failure::new(
+        FailureKind::InvalidArguments,
+        format!(
+          "[Error] {} config problem(s) with these options, see above!",
+          check.errors.len()
+        )
+      ));
+    }
+  }
+  if let Some(c) = cli.test_render {
+    return test_render(&cli, font_files, &config, c);
+  }
+  if cli.font_size_detection {
+    return font_size_detection(&cli, font_files, &config);
+  }
+  if let Some(coverage_file) = &cli.output_coverage_image {
+    return output_coverage_image(&cli, coverage_file, font_files, &config);
+  }
+  let output_file = cli
+    .output
+    .clone()
+    .expect("--output is required without --test-render");
+  let target_palette = config
+    .target_palette
+    .as_ref()
+    .filter(|_| cli.png_indexed)
+    .map(|palette| palette.iter().copied().map(Rgb).collect::<Vec<_>>());
+  if cli.png_indexed && config.char_gradient.is_some() && target_palette.is_none() {
+    bail!(Failure::new(
+      FailureKind::InvalidArguments,
+      "[Error] --png-indexed cannot be combined with char_gradient unless a target_palette is \
+       configured!"
+    ));
+  }
+  if cli.dither != Dither::None && target_palette.is_none() {
+    bail!(Failure::new(
+      FailureKind::InvalidArguments,
+      "[Error] --dither needs --png-indexed and a target_palette in the config!"
+    ));
+  }
 
-  let chars = get_unique_chinese_chars(&game_script);
+  let extraction_start = Instant::now();
+  // Checked before preprocessing, which trims indentation.
+  let has_ideographic_space =
+    cli.include_ideographic_space && game_script.contains(IDEOGRAPHIC_SPACE);
+  let game_script = preprocess_script(&game_script);
+  let (game_script, width_mappings) = normalize_width(&game_script, cli.normalize_width);
+  for mapping in &width_mappings {
+    println!(
+      "[Note] Normalized '{}' to '{}' ({} times).",
+      mapping.from, mapping.to, mapping.count
+    );
+  }
+  let mut chars = get_unique_chinese_chars(&game_script);
+  if cli.normalize_width == WidthNormalization::Halfwidth {
+    // Halfwidth marks are not picked up by the Chinese character filter.
+    chars.extend(width_mappings.iter().map(|mapping| mapping.to));
+  }
+  if has_ideographic_space {
+    chars.push(IDEOGRAPHIC_SPACE);
+  }
+  chars.sort_unstable();
+  chars.dedup();
+  if cli.duplicate_check {
+    for warning in duplicate_warnings(&chars) {
+      println!("{}", warning);
+    }
+  }
+  let mut context = RenderContext::default();
+  if !cli.include_compat {
+    let skipped = chars
+      .iter()
+      .filter(|c| is_compat_ideograph(**c))
+      .collect::<String>();
+    if !skipped.is_empty() {
+      chars.retain(|c| !is_compat_ideograph(*c));
+      context.warn(RenderWarning::CompatSkipped { chars: skipped });
+    }
+  }
+  if cli.include_punctuation_only {
+    chars.retain(|c| CHINESE_PUNCTUATION_MARKS.contains(c));
+    if chars.is_empty() {
+      bail!(Failure::new(
+        FailureKind::Input,
+        "[Error] No punctuation marks found in game script!"
+      ));
+    }
+  } else if cli.exclude_punctuation {
+    chars.retain(|c| !CHINESE_PUNCTUATION_MARKS.contains(c));
+  }
   if chars.is_empty() {
-    bail!("[Error] No chinese characters found in game script!");
+    bail!(Failure::new(
+      FailureKind::Input,
+      "[Error] No chinese characters found in game script!"
+    ));
+  }
+  if cli.sort == SortOrder::Frequency || cli.freq_report.is_some() {
+    let frequencies = char_frequencies(&game_script);
+    if cli.sort == SortOrder::Frequency {
+      sort_by_frequency(&mut chars, &frequencies);
+    }
+    if let Some(freq_report_file) = &cli.freq_report {
+      fs::write(freq_report_file, frequency_report(&chars, &frequencies))?;
+    }
+  }
+  if let Some(encoding) = cli.sort.legacy_encoding() {
+    let outside = sort_by_legacy_code(&mut chars, cli.sort);
+    if outside > 0 {
+      println!(
+        "[Note] {} characters are not in {}, placed after the others by codepoint.",
+        outside, encoding
+      );
+    }
+  }
+
+  let script_chars = CharSet::from(chars.clone());
+  let mut classes = Vec::with_capacity(cli.class_chars.len());
+  for (name, class_file) in &cli.class_chars {
+    let Some(class) = config.color_classes.get(name) else {
+      bail!(Failure::new(
+        FailureKind::InvalidArguments,
+        format!(
+          "[Error] Color class '{}' is not defined in [color_classes]!",
+          name
+        )
+      ));
+    };
+    let class_text = read_script(&read_input(class_file)?, ScriptFormat::Plain)
+      .map_err(|e| Failure::new(FailureKind::Input, e.to_string()))?;
+    let mut class_chars = CharSet::from_text(&preprocess_script(&class_text));
+    if !class.always_include {
+      class_chars = class_chars.intersection(&script_chars);
+    }
+    if class_chars.is_empty() {
+      context.warn(RenderWarning::ColorClassSkipped {
+        class: name.clone(),
+        file: class_file.display().to_string(),
+      });
+      continue;
+    }
+    classes.push(ColorClassTiles {
+      name: name.clone(),
+      colors: CellColors {
+        char_color: Rgb(class.color),
+        shadow_color: Rgb(class.shadow_color.unwrap_or(config.char_shadow_color)),
+        gradient_bottom: None,
+      },
+      bold: class.bold,
+      slant: class.slant,
+      chars: class_chars.into_vec(),
+    });
+  }
+
+  if cli.skip_chars > 0 || cli.max_chars.is_some() {
+    if cli.skip_chars >= chars.len() {
+      bail!(Failure::new(
+        FailureKind::InvalidArguments,
+        format!(
+          "[Error] --skip-chars {} skips all {} characters!",
+          cli.skip_chars,
+          chars.len()
+        )
+      ));
+    }
+    let end = cli.max_chars.map_or(chars.len(), |max_chars| {
+      chars
+        .len()
+        .min(cli.skip_chars.saturating_add(max_chars as usize))
+    });
+    if cli.skip_chars > 0 {
+      println!(
+        "[Note] Rendering characters {}..{} of {}.",
+        cli.skip_chars,
+        end,
+        chars.len()
+      );
+    } else if end < chars.len() {
+      println!(
+        "[Note] Truncated to {} characters ({} were in the source script).",
+        end,
+        chars.len()
+      );
+    }
+    chars = chars[cli.skip_chars..end].to_vec();
   }
 
-  let img_height = if chars.len() % config.chars_per_line == 0 {
-    (chars.len() / config.chars_per_line) as u32 * font_size
+  let mut tbl_layout = None;
+  if let Some(tbl_file) = &cli.tbl_in {
+    let (tbl, tbl_text) = read_tbl(tbl_file)?;
+    let layout = tbl.layout(&chars, cli.tbl_base, cli.tbl_stride);
+    for problem in &layout.problems {
+      println!("{}", problem);
+    }
+    println!(
+      "[Note] {} tiles from {}, {} new characters added.",
+      layout.codes.iter().flatten().count() - layout.additions.len(),
+      tbl_file.display(),
+      layout.additions.len()
+    );
+    chars = layout.chars.clone();
+    tbl_layout = Some((tbl, tbl_text, layout));
+  }
+
+  if cli.optimize_atlas {
+    let tile_count = tile_count(&cli, &chars, &classes);
+    let Some(chars_per_line) = optimal_chars_per_line(
+      tile_count,
+      font_size,
+      config.max_image_width,
+      config.max_image_height,
+    ) else {
+      bail!(Failure::new(
+        FailureKind::Overflow,
+        format!(
+          "[Error] No chars_per_line fits {} characters into {}x{} px!",
+          tile_count, config.max_image_width, config.max_image_height
+        )
+      ));
+    };
+    config.chars_per_line = chars_per_line;
+    println!(
+      "Optimized layout: {} chars per line, {}x{} px.",
+      chars_per_line,
+      chars_per_line as u32 * font_size,
+      tile_count.div_ceil(chars_per_line) as u32 * font_size
+    );
+  }
+
+  let mut max_rows = cli.max_rows;
+  let mut texture_pages = None;
+  if let Some(page_size) = &cli.texture_page_size {
+    let (chars_per_line, rows) = texture_page_layout(page_size[0], page_size[1], font_size)
+      .map_err(|e| Failure::new(FailureKind::InvalidArguments, e.to_string()))?;
+    let tile_count = tile_count(&cli, &chars, &classes);
+    config.chars_per_line = chars_per_line;
+    max_rows = Some(rows);
+    texture_pages = Some((
+      chars_per_line,
+      rows,
+      tile_count.div_ceil(chars_per_line * rows),
+    ));
+  }
+
+  let kerning = match &cli.kerning_file {
+    Some(kerning_file) => {
+      let text = String::from_utf8_lossy(&read_input(kerning_file)?).into_owned();
+      Some(
+        parse_kerning(&text, font_size)
+          .map_err(|e| Failure::new(FailureKind::InvalidArguments, e.to_string()))?,
+      )
+    }
+    None => None,
+  };
+
+  let extraction_time = extraction_start.elapsed();
+  let rendering_start = Instant::now();
+
+  // Build the atlas generator from Cli + Config.
+  let mut builder = AtlasBuilder::from_config(&config)
+    .size(font_size)
+    .zh_hant(cli.is_zh_hant)
+    .style(cli.render_style)
+    .rotation(cli.char_rotation)
+    .tile_rotation(cli.rotate_tiles)
+    .bold(cli.bold)
+    .slant(cli.slant)
+    .shadow_width(cli.char_shadow_width)
+    .vertical_punctuation(cli.vertical_punctuation)
+    .baseline_offset(cli.font_baseline_offset);
+  for (font_file, face_index) in font_files {
+    builder = builder.font_collection_bytes(font_file, face_index);
+  }
+  if let Some(font_size_pt) = cli.font_size_pt {
+    builder = builder.font_size_pt(font_size_pt);
+  }
+  if let Some(font_scale) = cli.font_scale {
+    builder = builder.font_scale(font_scale);
+  }
+  if let Some(color_key) = cli.color_key {
+    builder = builder.color_key(color_key);
+    config.img_bg_color = color_key;
+  }
+  if let Some(count) = cli.pad_to_count {
+    builder = builder.padding(Padding::Count(count));
+  } else if cli.pad_to_full_rows {
+    builder = builder.padding(Padding::FullRows);
+  }
+  if let Some(max_rows) = max_rows {
+    builder = builder.max_rows(max_rows);
+  }
+  if cli.debug_checker && cli.pack == PackMode::Tight {
+    bail!(Failure::new(
+      FailureKind::InvalidArguments,
+      "[Error] --debug-checker needs the grid layout!"
+    ));
+  }
+  if cli.tsx.is_some() && cli.pack == PackMode::Tight {
+    bail!(Failure::new(
+      FailureKind::InvalidArguments,
+      "[Error] --tsx needs the grid layout!"
+    ));
+  }
+  let label_cells = if cli.row_label.is_some() {
+    LABEL_CELLS
+  } else if cli.line_numbers {
+    line_number_cells(font_size)
   } else {
-    (chars.len() / config.chars_per_line + 1) as u32 * font_size
+    0
   };
-  let mut image: RgbImage = image::ImageBuffer::from_pixel(
-    config.chars_per_line as u32 * font_size,
-    img_height,
-    Rgb(config.img_bg_color),
-  );
-  let font = FontRef::try_from_slice(&font_file)?;
-
-  // 6.75 pt = 9 px
-  // 6.75 * 2 = 13.5
-  let scale = PxScale::from(CHAR_SIZE * 0.75 * 2.0);
-  let scaled_font = font.as_scaled(scale);
-
-  let loop_count = if font_size == 10 { 1 } else { 2 };
-
-  for i in 0..loop_count {
-    let mut x_offset = 0;
-    let mut y_offset = 0;
-
-    for (j, c) in chars.iter().enumerate() {
-      let glyph_id = font.glyph_id(*c);
-      if glyph_id.0 == 0 {
-        if i == 0 {
-          println!(
-            "[Warning] The glyph for '{}' (U+{:04X}) is not found! (index: {})",
-            *c, *c as u32, j
-          );
-        }
-      } else {
-        let glyph = glyph_id.with_scale(scale);
-        if let Some(outlined_glyph) = scaled_font.outline_glyph(glyph) {
-          outlined_glyph.draw(|x, y, v| {
-            if v > 0.5 {
-              let (x_pos, y_pos);
-
-              if CHINESE_PUNCTUATION_MARKS.contains(c) {
-                let (h_side_bearing, v_side_bearing) =
-                  get_chinese_punctuation_offset(*c, is_zh_hant);
-
-                x_pos = x + x_offset + h_side_bearing;
-                y_pos = y + y_offset + v_side_bearing;
-              } else {
-                let h_side_bearing = scaled_font.h_side_bearing(scaled_font.glyph_id(*c));
-                let v_side_bearing = scaled_font.v_side_bearing(scaled_font.glyph_id(*c));
-
-                let bounds = outlined_glyph.px_bounds();
-                let char_width = bounds.width();
-                let char_height = bounds.height();
-
-                // At least it works...
-                x_pos = if char_width + h_side_bearing.ceil() > CHAR_SIZE {
-                  // 极少数字符的边距+本体宽会超出9px边界的，因此直接舍弃边界值
-                  x + x_offset
-                } else if char_width < CHAR_SIZE && char_width + h_side_bearing.ceil() == CHAR_SIZE
-                {
-                  // 自、当、日、口、白、目……
-                  // 对于比较瘦的字，尽可能靠左
-                  x + (h_side_bearing.ceil() as u32) + x_offset - 1
-                } else {
-                  // 常见规格的方块字
-                  x + (h_side_bearing.ceil() as u32) + x_offset
-                };
-
-                y_pos = if char_height + v_side_bearing.ceil() > CHAR_SIZE {
-                  // 类似于水平方向的向左，这里尽可能靠近垂直向下方向。
-                  y + y_offset + (CHAR_SIZE - char_height) as u32
-                } else {
-                  // 常见规格的方块字
-                  y + (v_side_bearing.ceil() as u32) + y_offset
-                };
-              }
-
-              if font_size == 10 {
-                // Bottom shadow
-                image.put_pixel(x_pos, y_pos + 1, Rgb(config.char_shadow_color));
-                // Bottom-right shadow
-                image.put_pixel(x_pos + 1, y_pos + 1, Rgb(config.char_shadow_color));
-                // Right shadow
-                image.put_pixel(x_pos + 1, y_pos, Rgb(config.char_shadow_color));
-                // Character itself
-                image.put_pixel(x_pos, y_pos, Rgb(config.char_color));
-              } else {
-                let (x_pos, y_pos) = (x_pos + 1, y_pos + 1);
-                if i == 0 {
-                  // Bottom shadow
-                  image.put_pixel(x_pos, y_pos + 1, Rgb(config.char_shadow_color));
-                  // Bottom-right shadow
-                  image.put_pixel(x_pos + 1, y_pos + 1, Rgb(config.char_shadow_color));
-                  // Right shadow
-                  image.put_pixel(x_pos + 1, y_pos, Rgb(config.char_shadow_color));
-                  // Top-right shadow
-                  image.put_pixel(x_pos + 1, y_pos - 1, Rgb(config.char_shadow_color));
-                  // Top shadow
-                  image.put_pixel(x_pos, y_pos - 1, Rgb(config.char_shadow_color));
-                  // Top-left shadow
-                  image.put_pixel(x_pos - 1, y_pos - 1, Rgb(config.char_shadow_color));
-                  // Left shadow
-                  image.put_pixel(x_pos - 1, y_pos, Rgb(config.char_shadow_color));
-                  // Bottom-left shadow
-                  image.put_pixel(x_pos - 1, y_pos + 1, Rgb(config.char_shadow_color));
-                } else {
-                  // Character itself
-                  image.put_pixel(x_pos, y_pos, Rgb(config.char_color));
-                }
-              }
-            }
-          });
+  if label_cells > 0 {
+    if cli.pack == PackMode::Tight {
+      bail!(Failure::new(
+        FailureKind::InvalidArguments,
+        "[Error] Row labels need the grid layout!"
+      ));
+    }
+    if config.chars_per_line <= label_cells {
+      bail!(Failure::new(
+        FailureKind::InvalidArguments,
+        format!(
+          "[Error] chars_per_line must be greater than {} to fit the row labels!",
+          label_cells
+        )
+      ));
+    }
+    builder = builder.chars_per_line(config.chars_per_line - label_cells);
+  }
+  let label_font = match cli.row_label.as_ref().or(cli.label_font.as_ref()) {
+    Some(label_font_path) => {
+      let label_font_file = read_input(label_font_path)?;
+      let sfnt = to_sfnt(label_font_file.clone())
+        .map_err(|e| Failure::new(FailureKind::Font, e.to_string()))?;
+      let diagnosis = FontDiagnosis::new(&label_font_file, &sfnt, 0);
+      match FontVec::try_from_vec(sfnt) {
+        Ok(font) => Some(font),
+        Err(_) => bail!(Failure::new(
+          FailureKind::Font,
+          format!(
+            "[Error] Row label font could not be parsed! ({})",
+            diagnosis
+          )
+        )),
+      }
+    }
+    None => None,
+  };
+  match cli.cell_border_color {
+    Some(Some(color)) => builder = builder.cell_border_color(color),
+    Some(None) => match config.grid_line_color {
+      Some(color) => builder = builder.cell_border_color(color),
+      None => bail!(Failure::new(
+        FailureKind::InvalidArguments,
+        "[Error] --cell-border-color needs a color when grid_line_color is not configured!"
+      )),
+    },
+    None => {}
+  }
+  builder = builder
+    .group_spacing(cli.char_group_spacing)
+    .line_separator(cli.line_separator)
+    .pack(cli.pack)
+    .background_mode(cli.char_background_mode);
+  if let Some(background_file) = &cli.background_image {
+    let background = image::load_from_memory(&read_input(background_file)?).map_err(|e| {
+      Failure::new(
+        FailureKind::Input,
+        format!(
+          "[Error] Cannot read background image {}: {}",
+          background_file.display(),
+          e
+        ),
+      )
+    })?;
+    builder = builder.background_image(flatten_on_white(&background.to_rgba8()));
+  }
+  if cli.char_color_per_block {
+    let block_colors = BlockColors::parse(&config.char_colors)
+      .map_err(|e| Failure::new(FailureKind::InvalidArguments, e.to_string()))?;
+    if block_colors.is_empty() {
+      bail!(Failure::new(
+        FailureKind::InvalidArguments,
+        "[Error] --char-color-per-block needs a char_colors table in the config!"
+      ));
+    }
+    builder = builder.block_colors(block_colors);
+  }
+  let generator = builder.build()?;
+  // Printed once the builder accepted the cell size the layout was computed for.
+  if let Some((chars_per_line, rows, page_count)) = texture_pages {
+    println!(
+      "Page 0: {}×{} chars, Pages: {}.",
+      chars_per_line, rows, page_count
+    );
+  }
+  if cli.verbose {
+    let scale = generator.options().scale();
+    for (font_index, font) in generator.fonts().iter().enumerate() {
+      let metrics = measure_font(font, scale);
+      println!(
+        "[Note] Font #{} at scale {}: ascender {:.2}px, descender {:.2}px, line gap {:.2}px, {} \
+         units per em, '{}' {:.2}x{:.2}px.",
+        font_index,
+        scale.y,
+        metrics.ascender,
+        metrics.descender,
+        metrics.line_gap,
+        metrics.units_per_em,
+        CALIBRATION_CHAR,
+        metrics.cjk_char_width,
+        metrics.cjk_char_height
+      );
+    }
+  }
+  let shared_glyphs = shared_glyphs(generator.fonts(), &chars);
+  for shared in &shared_glyphs {
+    context.warn(shared_glyph_warning(shared));
+  }
+  let chars_per_line = generator.options().chars_per_line;
+  let mut block_pages = None;
+  let atlas = if cli.split_by_block {
+    let mut blocks = Vec::new();
+    let mut atlases = Vec::new();
+    let mut first_tile = 0;
+    for (block, block_chars) in CharSet::from(chars.clone()).split_by_block() {
+      let first_warning = context.warnings.len();
+      let atlas = generator.generate_with_context(block_chars.chars(), &[], &mut context)?;
+      // combine_pages numbers the tiles across the blocks, the warnings follow.
+      for warning in &mut context.warnings[first_warning..] {
+        if let RenderWarning::GlyphNotFound { index, .. } = warning {
+          *index += first_tile;
         }
       }
+      first_tile += atlas.tile_count;
+      atlases.push(atlas);
+      blocks.push(block);
+    }
+    let (atlas, pages) = combine_pages(atlases);
+    block_pages = Some((blocks, pages));
+    atlas
+  } else {
+    generator.generate_with_context(&chars, &classes, &mut context)?
+  };
+  let Atlas {
+    mut image,
+    tile_count,
+    mut placements,
+    clamped_chars,
+    gamma_flipped_pixels,
+    threshold_overrides,
+    mut cells,
+  } = atlas;
+  let rendering_time = rendering_start.elapsed();
+  // Keep the indices of a --skip-chars window global.
+  for placement in &mut placements {
+    placement.tile_index += cli.skip_chars;
+    placement.vertical_index = placement.vertical_index.map(|index| index + cli.skip_chars);
+  }
+  for warning in &mut context.warnings {
+    if let RenderWarning::GlyphNotFound { index, .. } = warning {
+      *index += cli.skip_chars;
+    }
+  }
 
-      if (j + 1) % config.chars_per_line == 0 {
-        x_offset = 0;
-        y_offset += font_size;
-      } else {
-        x_offset += font_size;
+  if cli.verbose && font_paths.len() > 1 {
+    for placement in &placements {
+      if let Some(font_index) = placement.font_index {
+        println!(
+          "[Note] '{}' ({}) uses font #{}: {}",
+          placement.char,
+          codepoint_label(placement.char),
+          font_index,
+          font_paths[font_index].0.display()
+        );
       }
     }
   }
 
-  image.save(output_file)?;
+  let rotated = placements
+    .iter()
+    .filter(|placement| placement.rotated)
+    .map(|placement| placement.vertical_of.unwrap_or(placement.char))
+    .collect::<String>();
+  if !rotated.is_empty() {
+    println!(
+      "[Note] No font has the vertical form of {}, rotated the horizontal marks instead.",
+      rotated
+    );
+  }
 
-  Ok(())
-}
+  if let Some((_, _, layout)) = &tbl_layout {
+    // The gap fillers are blank tiles, not characters.
+    let is_filler = |placement: &GlyphPlacement| {
+      placement.class.is_none() && layout.codes.get(placement.tile_index) == Some(&None)
+    };
+    (placements, cells) = placements
+      .into_iter()
+      .zip(cells)
+      .filter(|(placement, _)| !is_filler(placement))
+      .unzip();
+  }
 
-fn get_unique_chinese_chars(game_script: &str) -> Vec<char> {
-  let no_whitespace_chinese_script: String = game_script
-    .chars()
-    .filter(|c| {
-      !c.is_whitespace()
-        && (CHINESE_PUNCTUATION_MARKS.contains(c) || is_chinese::is_chinese(c.to_string().as_str()))
-    })
-    .collect();
-  let unique_chars = no_whitespace_chinese_script.chars().collect::<HashSet<_>>();
+  let pixel_count_stats = if cli.verify_pixel_count {
+    verify_pixel_counts(&placements, &cells, &mut context)
+  } else {
+    None
+  };
+  if let Some(stats) = pixel_count_stats {
+    println!(
+      "Pixel count: {} cells checked, min {}, max {}, mean {:.1} pixels per cell.",
+      stats.cells, stats.min, stats.max, stats.mean
+    );
+  }
 
-  let mut sorted_chars = unique_chars.iter().copied().collect::<Vec<_>>();
-  sorted_chars.sort_unstable();
+  let missing_count = placements
+    .iter()
+    .filter(|placement| placement.missing)
+    .count();
+  if cli.fail_on_missing && missing_count > 0 {
+    for warning in context.sorted_warnings() {
+      println!("{}", warning);
+    }
+    bail!(Failure::new(
+      FailureKind::MissingGlyphs,
+      format!(
+        "[Error] {} glyphs are missing from the fonts!",
+        missing_count
+      )
+    ));
+  }
 
-  sorted_chars
-}
+  if cli.verbose && config.coverage_gamma != 1.0 {
+    println!(
+      "[Note] coverage_gamma {} flipped {} pixels compared to gamma 1.0.",
+      config.coverage_gamma, gamma_flipped_pixels
+    );
+  }
 
-/// FUSION PIXEL FONT 10PX ONLY
-/// This is stupid, but it works.
-fn get_chinese_punctuation_offset(c: char, is_zh_hant: bool) -> (u32, u32) {
-  match c {
-    '·' => (3, 4),
-    '—' => (0, 4),
-    '‘' => (5, 0),
-    '’' => (0, 0),
-    '“' => (2, 0),
-    '”' => (0, 0),
-    '…' => (0, 4),
-    '、' => {
-      if is_zh_hant {
-        (3, 3)
-      } else {
-        (0, 6)
-      }
+  if cli.verbose && !threshold_overrides.is_empty() {
+    println!(
+      "[Note] char_thresholds overrides applied to: {}",
+      threshold_overrides.iter().collect::<String>()
+    );
+  }
+
+  if cli.verbose && !clamped_chars.is_empty() {
+    println!(
+      "[Note] Negative side bearings clamped to the cell edge for: {}",
+      clamped_chars.iter().collect::<String>()
+    );
+  }
+
+  if cli.pack == PackMode::Tight && block_pages.is_none() {
+    let grid_area = (chars_per_line as u64 * font_size as u64)
+      * (tile_count.div_ceil(chars_per_line) as u64 * font_size as u64);
+    let packed_area = image.width() as u64 * image.height() as u64;
+    let ink_area: u64 = placements
+      .iter()
+      .filter_map(|placement| placement.packed)
+      .map(|rect| rect.width as u64 * rect.height as u64)
+      .sum();
+    println!(
+      "Tight packing: {}x{} px, {:.1}% of the grid layout area, {:.1}% of the atlas used.",
+      image.width(),
+      image.height(),
+      packed_area as f64 * 100.0 / grid_area as f64,
+      ink_area as f64 * 100.0 / packed_area as f64
+    );
+  }
+
+  if cli.line_numbers {
+    image = add_line_numbers(
+      &image,
+      &mut placements,
+      label_font.as_ref(),
+      font_size,
+      generator.options().bg_color,
+      Rgb(config.char_color),
+    );
+  } else if let Some(label_font) = &label_font {
+    image = add_row_labels(
+      &image,
+      &mut placements,
+      label_font,
+      font_size,
+      generator.options().bg_color,
+      Rgb(config.char_color),
+    );
+  }
+
+  if cli.strip_unused_cells {
+    let (old_width, old_height) = image.dimensions();
+    image = strip_unused_cells(&image, Rgb(config.img_bg_color), font_size);
+    if image.dimensions() != (old_width, old_height) {
+      println!(
+        "Image trimmed from {}x{} to {}x{}.",
+        old_width,
+        old_height,
+        image.width(),
+        image.height()
+      );
     }
-    '。' => {
-      if is_zh_hant {
-        (2, 3)
-      } else {
-        (0, 5)
-      }
+  }
+
+  let (mut pages, page_files) = match (block_pages, max_rows) {
+    (Some((blocks, pages)), _) => {
+      let files = blocks
+        .iter()
+        .map(|block| block_path(&output_file, *block))
+        .collect::<Vec<_>>();
+      (pages, files)
     }
-    '〈' => (4, 0),
-    '〉' => (0, 0),
-    '《' => (1, 0),
-    '》' => (0, 0),
-    '「' => (4, 0),
-    '」' => (0, 2),
-    '『' => (2, 0),
-    '』' => (0, 2),
-    '【' => (3, 0),
-    '】' => (0, 0),
-    '〔' => (4, 0),
-    '〕' => (0, 0),
-    '︰' => (3, 1),
-    '！' => {
-      if is_zh_hant {
-        (3, 0)
-      } else {
-        (1, 0)
-      }
+    (None, max_rows) => {
+      let pages = match max_rows {
+        Some(max_rows) => split_pages(&image, &mut placements, font_size, max_rows),
+        None => vec![image],
+      };
+      let files = (0..pages.len())
+        .map(|page| page_path(&output_file, page, pages.len()))
+        .collect::<Vec<_>>();
+      (pages, files)
     }
-    '（' => (4, 0),
-    '）' => (0, 0),
-    '，' => {
-      if is_zh_hant {
-        (3, 3)
-      } else {
-        (0, 5)
-      }
+  };
+  if let Some(palette) = &target_palette {
+    for (page, page_image) in pages.iter_mut().enumerate() {
+      let page_placements = placements.iter().filter(|placement| placement.page == page);
+      *page_image = quantize(page_image, palette, cli.dither, page_placements, font_size);
     }
-    '．' => {
-      if is_zh_hant {
-        (3, 4)
-      } else {
-        (0, 6)
-      }
+  }
+  if cli.fail_on_overflow && !cli.split_by_block && pages.len() > 1 {
+    bail!(Failure::new(
+      FailureKind::Overflow,
+      format!(
+        "[Error] The characters need {} pages, more than one atlas!",
+        pages.len()
+      )
+    ));
+  }
+
+  let flip = Flip {
+    horizontal: cli.flip_x,
+    vertical: cli.flip_y,
+  };
+  if !flip.is_identity() {
+    for (page, page_image) in pages.iter_mut().enumerate() {
+      flip.apply(page_image);
+      flip.placements(
+        placements
+          .iter_mut()
+          .filter(|placement| placement.page == page),
+        page_image.width(),
+        page_image.height(),
+        font_size,
+      );
     }
-    '：' => {
-      if is_zh_hant {
-        (3, 1)
-      } else {
-        (0, 1)
-      }
+  }
+
+  let png_options = PngOptions {
+    compression: cli
+      .png_compression
+      .map(compression_from_level)
+      .unwrap_or_default(),
+    filter: cli.png_filter,
+    indexed: cli.png_indexed,
+    palette: config.palette_order.as_ref().map(|order| {
+      let options = generator.options();
+      FixedPalette::new(
+        order,
+        options.colors.char_color,
+        blend(
+          options.colors.shadow_color,
+          options.bg_color,
+          options.shadow_opacity,
+        ),
+        options.bg_color,
+        cli.color_key.map(Rgb),
+      )
+    }),
+  };
+  let encoding_start = Instant::now();
+  let mut file_size = 0;
+  for (page_image, page_file) in pages.iter().zip(&page_files) {
+    file_size += save_png(page_image, page_file, png_options)?;
+  }
+  let encoding_time = encoding_start.elapsed();
+  if cli.debug_checker {
+    for (page_image, page_file) in pages.iter().zip(&page_files) {
+      let checker = debug_checker(
+        page_image,
+        font_size,
+        generator.options().bg_color,
+        Rgb(config.alt_bg_color()),
+      );
+      save_png(&checker, &debug_path(page_file), png_options)?;
     }
-    '；' => {
-      if is_zh_hant {
-        (3, 1)
-      } else {
-        (0, 1)
+  }
+  let destination = if page_files.len() == 1 {
+    page_files[0].display().to_string()
+  } else {
+    format!(
+      "{} pages ({} ... {})",
+      page_files.len(),
+      page_files[0].display(),
+      page_files[page_files.len() - 1].display()
+    )
+  };
+  println!(
+    "{} tiles ({} characters, {} blank padding) written to {} ({} bytes)",
+    tile_count,
+    placements.len(),
+    tile_count - placements.len(),
+    destination,
+    file_size
+  );
+  let image = &pages[0];
+
+  if let Some(page_index_file) = cli.page_index_file {
+    let index = page_index(&placements, &page_files);
+    fs::write(page_index_file, serde_json::to_string_pretty(&index)?)?;
+  }
+
+  let mut unencodable_chars = None;
+  if cli.map.is_some()
+    || cli.lua.is_some()
+    || cli.base64_json.is_some()
+    || cli.output_rust.is_some()
+  {
+    let mapping = Mapping::from_placements(
+      output_file.display().to_string(),
+      font_size,
+      chars_per_line,
+      cli.pack,
+      tile_count,
+      &placements,
+    );
+    let mapping = if cli.split_by_block {
+      mapping.with_page_files(&page_files)
+    } else {
+      mapping
+    }
+    .with_tile_rotation(cli.rotate_tiles);
+    let mapping = match &kerning {
+      Some(kerning) => mapping.with_kerning(kerning),
+      None => mapping,
+    };
+    let mapping = match cli.map_encoding {
+      Some(encoding) => {
+        let (mapping, unencodable) = mapping.with_encoding(encoding);
+        unencodable_chars = Some(unencodable);
+        mapping
       }
+      None => mapping,
+    };
+    if let Some(map_file) = cli.map {
+      fs::write(map_file, serde_json::to_string_pretty(&mapping)?)?;
     }
-    '？' => {
-      if is_zh_hant {
-        (1, 0)
+    if let Some(lua_file) = cli.lua {
+      fs::write(lua_file, lua_module(&mapping, &page_files, cli.lua_key))?;
+    }
+    if let Some(base64_json_file) = cli.base64_json {
+      let embedded = EmbeddedAtlas::new(
+        &fs::read(&output_file)?,
+        image.width(),
+        image.height(),
+        &mapping,
+      );
+      let json = if cli.pretty_json {
+        serde_json::to_string_pretty(&embedded)?
       } else {
-        (0, 0)
-      }
+        serde_json::to_string(&embedded)?
+      };
+      fs::write(base64_json_file, json)?;
+    }
+    if let Some(rust_file) = cli.output_rust {
+      let module = rust_module(
+        &fs::read(&output_file)?,
+        &serde_json::to_string(&mapping)?,
+        &output_file.display().to_string(),
+      );
+      fs::write(rust_file, module)?;
+    }
+  }
+
+  if let (Some(tbl_out_file), Some((tbl, tbl_text, layout))) = (&cli.tbl_out, &tbl_layout) {
+    let mut updated = tbl_text.clone();
+    if !updated.is_empty() && !updated.ends_with('\n') {
+      updated.push('\n');
+    }
+    updated.push_str(&tbl.addition_lines(&layout.additions));
+    fs::write(tbl_out_file, updated)?;
+  }
+
+  if let Some(hex_file) = cli.hex {
+    fs::write(hex_file, unifont_hex(&placements, &cells))?;
+  }
+
+  if let Some(tsx_file) = &cli.tsx {
+    let tsx_dir = tsx_file.parent().unwrap_or(Path::new(""));
+    for (page, page_image) in pages.iter().enumerate() {
+      let page_placements = placements
+        .iter()
+        .filter(|placement| placement.page == page)
+        .collect::<Vec<_>>();
+      let name = page_files[page]
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+      let tsx = tiled_tileset(
+        &name,
+        &relative_path(tsx_dir, &page_files[page]),
+        page_image.dimensions(),
+        font_size,
+        &page_placements,
+      );
+      fs::write(page_path(tsx_file, page, pages.len()), tsx)?;
+    }
+  }
+
+  if let Some(sheet_file) = &cli.texturepacker {
+    let sheet_dir = sheet_file.parent().unwrap_or(Path::new(""));
+    for (page, page_image) in pages.iter().enumerate() {
+      let page_placements = placements
+        .iter()
+        .filter(|placement| placement.page == page)
+        .collect::<Vec<_>>();
+      let sheet = TexturePackerSheet::new(
+        &relative_path(sheet_dir, &page_files[page]),
+        page_image.dimensions(),
+        font_size,
+        &page_placements,
+        cli.texturepacker_names,
+      );
+      fs::write(
+        page_path(sheet_file, page, pages.len()),
+        serde_json::to_string_pretty(&sheet)?,
+      )?;
+    }
+  }
+
+  if let Some(xml_file) = &cli.sprite_sheet_xml {
+    let xml_dir = xml_file.parent().unwrap_or(Path::new(""));
+    for (page, page_image) in pages.iter().enumerate() {
+      let page_placements = placements
+        .iter()
+        .filter(|placement| placement.page == page)
+        .collect::<Vec<_>>();
+      let xml = sprite_sheet_xml(
+        &relative_path(xml_dir, &page_files[page]),
+        page_image.dimensions(),
+        font_size,
+        &page_placements,
+      );
+      fs::write(page_path(xml_file, page, pages.len()), xml)?;
     }
-    '［' => (4, 0),
-    '］' => (0, 0),
-    _ => unreachable!(),
   }
+
+  if let Some(html_file) = cli.html {
+    let html = contact_sheet(
+      &fs::read(&output_file)?,
+      image.width(),
+      image.height(),
+      font_size,
+      &placements,
+    );
+    fs::write(html_file, html)?;
+  }
+
+  if let Some(rle_file) = cli.encode_rle {
+    let options = generator.options();
+    let (depth, pixels) = rle_pixels(image, options.bg_color, options.colors.char_color);
+    let encoded = encode_rle(image.width(), image.height(), depth, &pixels)?;
+    fs::write(rle_file, encoded)?;
+  }
+
+  let warnings = context.sorted_warnings();
+  for warning in &warnings {
+    println!("{}", warning);
+  }
+  let stats = RunStats {
+    scanned_chars: game_script.chars().count(),
+    unique_chars: chars.len(),
+    counts: PlacementCounts::from_placements(&placements),
+    atlas_width: image.width(),
+    atlas_height: pages.iter().map(|page| page.height()).sum(),
+    page_count: pages.len(),
+    file_size,
+    gamma_flipped_pixels,
+    unencodable_chars,
+    shared_glyphs,
+    warnings,
+    timings: StageTimings::new(extraction_time, rendering_time, encoding_time),
+  };
+  println!("{}", stats.summary());
+  if let Some(stats_file) = cli.stats_json {
+    fs::write(stats_file, serde_json::to_string_pretty(&stats)?)?;
+  }
+
+  #[cfg(feature = "preview")]
+  if cli.show {
+    chinese_bitmap_font_demo::preview::show(image, &page_files[0].display().to_string())?;
+  }
+
+  Ok(())
 }
@@ -0,0 +1,259 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{atlas::GlyphPlacement, kerning::KerningTable, pack::PackMode, render::Rotation};
+
+/// One atlas cell in the mapping export.
+#[derive(Serialize, Deserialize)]
+pub struct CharEntry {
+  pub char: String,
+  pub codepoint: String,
+  pub index: usize,
+  /// Page of a `--max-rows` split atlas, 0 otherwise.
+  pub page: usize,
+  pub x: u32,
+  pub y: u32,
+  pub width: u32,
+  pub height: u32,
+  /// Position of the rect's top-left corner within the original cell; 0 in grid mode.
+  pub offset_x: u32,
+  pub offset_y: u32,
+  /// Bounding box of the character and shadow pixels relative to the cell origin, for engines
+  /// trimming the glyphs; an empty tile has a zero-size box at 0, 0.
+  #[serde(default)]
+  pub ink_x: u32,
+  #[serde(default)]
+  pub ink_y: u32,
+  #[serde(default)]
+  pub ink_w: u32,
+  #[serde(default)]
+  pub ink_h: u32,
+  /// Index into the `--font` list of the font that supplied the glyph, `null` when missing and
+  /// not substituted.
+  pub font_index: Option<usize>,
+  /// No font has the character: the tile holds the `missing_fallback_char` glyph instead.
+  #[serde(default, skip_serializing_if = "is_false")]
+  pub substituted: bool,
+  /// `[color_classes]` name of a duplicated tile, omitted for the regular tiles.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub class: Option<String>,
+  /// Image holding the character when the atlas is split into several files (`--split-by-block`).
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub file: Option<String>,
+  /// Hex bytes of the character in the `--map-encoding` codec, `null` when it cannot be encoded.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub bytes: Option<Option<String>>,
+  /// `--kerning-file` adjustments (px) of the characters following this one, by codepoint label.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub kerning: Option<BTreeMap<String, i32>>,
+  /// `--vertical-punctuation`: tile index of the vertical form of this punctuation mark.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub vertical_index: Option<usize>,
+  /// `--vertical-punctuation`: horizontal punctuation mark this tile is the vertical form of.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub vertical_of: Option<String>,
+}
+
+/// Legacy codec of the `bytes` field of the mapping entries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum MapEncoding {
+  ShiftJis,
+  Gbk,
+  Big5,
+}
+
+impl MapEncoding {
+  /// Uppercase hex of the encoded bytes of `c`, `None` when the codec has no code for it.
+  pub fn encode_hex(self, c: char) -> Option<String> {
+    let encoding = match self {
+      MapEncoding::ShiftJis => encoding_rs::SHIFT_JIS,
+      MapEncoding::Gbk => encoding_rs::GBK,
+      MapEncoding::Big5 => encoding_rs::BIG5,
+    };
+    let mut buf = [0; 4];
+    let (bytes, _, had_errors) = encoding.encode(c.encode_utf8(&mut buf));
+    (!had_errors).then(|| bytes.iter().map(|byte| format!("{:02X}", byte)).collect())
+  }
+}
+
+/// Mapping export (`--map`) describing where every character lives in the atlas.
+#[derive(Serialize, Deserialize)]
+pub struct Mapping {
+  pub image: String,
+  pub cell_size: u32,
+  pub chars_per_line: usize,
+  /// `grid`: every entry is a full cell. `tight`: entries are packed ink rects.
+  pub pack: PackMode,
+  /// Number of tiles holding a real character.
+  pub char_count: usize,
+  /// Number of tiles in the atlas, including blank padding tiles.
+  pub tile_count: usize,
+  /// `--rotate-tiles`: clockwise rotation (degrees) of every cell's pixels, omitted when 0.
+  #[serde(default, skip_serializing_if = "is_zero")]
+  pub tile_rotation: u32,
+  pub chars: Vec<CharEntry>,
+}
+
+fn is_zero(value: &u32) -> bool {
+  *value == 0
+}
+
+fn is_false(value: &bool) -> bool {
+  !*value
+}
+
+/// `U+XXXX` label for a character.
+pub fn codepoint_label(c: char) -> String {
+  format!("U+{:04X}", c as u32)
+}
+
+impl Mapping {
+  /// Build the mapping export from the renderer's placement records.
+  pub fn from_placements(
+    image: String,
+    cell_size: u32,
+    chars_per_line: usize,
+    pack: PackMode,
+    tile_count: usize,
+    placements: &[GlyphPlacement],
+  ) -> Self {
+    let chars = placements
+      .iter()
+      .map(|placement| {
+        let rect = placement.atlas_rect(cell_size);
+        let (offset_x, offset_y) = match (placement.packed, placement.ink_bbox) {
+          (Some(_), Some(ink)) => (ink.x, ink.y),
+          _ => (0, 0),
+        };
+        let ink = placement.ink_bbox.unwrap_or_default();
+        CharEntry {
+          char: placement.char.to_string(),
+          codepoint: codepoint_label(placement.char),
+          index: placement.tile_index,
+          page: placement.page,
+          x: rect.x,
+          y: rect.y,
+          width: rect.width,
+          height: rect.height,
+          offset_x,
+          offset_y,
+          ink_x: ink.x,
+          ink_y: ink.y,
+          ink_w: ink.width,
+          ink_h: ink.height,
+          font_index: placement.font_index,
+          substituted: placement.substituted,
+          class: placement.class.clone(),
+          file: None,
+          kerning: None,
+          bytes: None,
+          vertical_index: placement.vertical_index,
+          vertical_of: placement.vertical_of.map(|mark| mark.to_string()),
+        }
+      })
+      .collect();
+    Mapping {
+      image,
+      cell_size,
+      chars_per_line,
+      pack,
+      char_count: placements.len(),
+      tile_count,
+      tile_rotation: 0,
+      chars,
+    }
+  }
+
+  /// Record the `--rotate-tiles` rotation of the cells.
+  pub fn with_tile_rotation(mut self, rotation: Rotation) -> Self {
+    self.tile_rotation = rotation.degrees();
+    self
+  }
+
+  /// Attach the kerning pairs starting with each entry's character.
+  pub fn with_kerning(mut self, kerning: &KerningTable) -> Self {
+    for entry in &mut self.chars {
+      let pairs = entry.char.chars().next().and_then(|c| kerning.get(&c));
+      entry.kerning = pairs.map(|pairs| {
+        pairs
+          .iter()
+          .map(|(c, adjustment)| (codepoint_label(*c), *adjustment))
+          .collect()
+      });
+    }
+    self
+  }
+
+  /// Fill the `bytes` of every entry; returns how many characters could not be encoded.
+  pub fn with_encoding(mut self, encoding: MapEncoding) -> (Self, usize) {
+    let mut unencodable = 0;
+    for entry in &mut self.chars {
+      let bytes = entry
+        .char
+        .chars()
+        .next()
+        .and_then(|c| encoding.encode_hex(c));
+      unencodable += bytes.is_none() as usize;
+      entry.bytes = Some(bytes);
+    }
+    (self, unencodable)
+  }
+
+  /// Point every entry at the image of its page.
+  pub fn with_page_files(mut self, page_files: &[PathBuf]) -> Self {
+    for entry in &mut self.chars {
+      entry.file = Some(page_files[entry.page].display().to_string());
+    }
+    self
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::atlas::PixelRect;
+
+  #[test]
+  fn chars_are_encoded_as_hex_bytes() {
+    assert_eq!(
+      MapEncoding::ShiftJis.encode_hex('あ').as_deref(),
+      Some("82A0")
+    );
+    assert_eq!(MapEncoding::Gbk.encode_hex('中').as_deref(), Some("D6D0"));
+    assert_eq!(MapEncoding::Big5.encode_hex('中').as_deref(), Some("A4A4"));
+    assert_eq!(MapEncoding::ShiftJis.encode_hex('丂'), None);
+  }
+
+  #[test]
+  fn entries_carry_the_ink_box_of_the_cell() {
+    let inked = GlyphPlacement {
+      char: '中',
+      ink_bbox: Some(PixelRect {
+        x: 1,
+        y: 0,
+        width: 9,
+        height: 10,
+      }),
+      ..Default::default()
+    };
+    let blank = GlyphPlacement {
+      char: '一',
+      tile_index: 1,
+      cell_x: 10,
+      ..Default::default()
+    };
+    let mapping = Mapping::from_placements(
+      "font.png".to_string(),
+      10,
+      2,
+      PackMode::Grid,
+      2,
+      &[inked, blank],
+    );
+    let ink = |entry: &CharEntry| (entry.ink_x, entry.ink_y, entry.ink_w, entry.ink_h);
+    assert_eq!(ink(&mapping.chars[0]), (1, 0, 9, 10));
+    assert_eq!(ink(&mapping.chars[1]), (0, 0, 0, 0));
+  }
+}
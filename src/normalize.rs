@@ -0,0 +1,118 @@
+use clap::ValueEnum;
+
+use crate::script::is_supported_char;
+
+/// Halfwidth punctuation and its fullwidth equivalent.
+pub const WIDTH_PAIRS: [(char, char); 7] = [
+  ('!', '！'),
+  ('?', '？'),
+  (':', '：'),
+  (';', '；'),
+  (',', '，'),
+  ('(', '（'),
+  (')', '）'),
+];
+
+/// Which punctuation width the script is normalized to before extraction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum WidthNormalization {
+  /// Halfwidth punctuation next to CJK text becomes fullwidth.
+  #[default]
+  Fullwidth,
+  /// Fullwidth punctuation becomes halfwidth.
+  Halfwidth,
+  /// Leave the script as it is.
+  Off,
+}
+
+/// One replacement applied by [`normalize_width`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WidthMapping {
+  pub from: char,
+  pub to: char,
+  pub count: usize,
+}
+
+/// Replace punctuation of the other width, returning the new text and every mapping applied in
+/// [`WIDTH_PAIRS`] order. Halfwidth marks are only widened next to CJK text, so Latin sentences
+/// keep their punctuation.
+pub fn normalize_width(text: &str, mode: WidthNormalization) -> (String, Vec<WidthMapping>) {
+  if mode == WidthNormalization::Off {
+    return (text.to_string(), Vec::new());
+  }
+  let chars = text.chars().collect::<Vec<_>>();
+  let is_cjk_at = |i: Option<usize>| {
+    i.and_then(|i| chars.get(i))
+      .is_some_and(|c| is_supported_char(*c))
+  };
+  let mut counts = [0; WIDTH_PAIRS.len()];
+  let mut normalized = String::with_capacity(text.len());
+  for (i, c) in chars.iter().enumerate() {
+    let pair = if mode == WidthNormalization::Fullwidth {
+      WIDTH_PAIRS
+        .iter()
+        .position(|(half, _)| half == c)
+        .filter(|_| is_cjk_at(i.checked_sub(1)) || is_cjk_at(Some(i + 1)))
+    } else {
+      WIDTH_PAIRS.iter().position(|(_, full)| full == c)
+    };
+    match pair {
+      Some(pair) => {
+        counts[pair] += 1;
+        let (half, full) = WIDTH_PAIRS[pair];
+        normalized.push(if mode == WidthNormalization::Fullwidth {
+          full
+        } else {
+          half
+        });
+      }
+      None => normalized.push(*c),
+    }
+  }
+  let mappings = WIDTH_PAIRS
+    .iter()
+    .zip(counts)
+    .filter(|(_, count)| *count > 0)
+    .map(|(&(half, full), count)| match mode {
+      WidthNormalization::Fullwidth => WidthMapping {
+        from: half,
+        to: full,
+        count,
+      },
+      _ => WidthMapping {
+        from: full,
+        to: half,
+        count,
+      },
+    })
+    .collect();
+  (normalized, mappings)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn halfwidth_next_to_cjk_is_widened() {
+    let (text, mappings) = normalize_width("你好!Hi! 再见?", WidthNormalization::Fullwidth);
+    assert_eq!(text, "你好！Hi! 再见？");
+    assert_eq!(
+      mappings,
+      vec![
+        WidthMapping {
+          from: '!',
+          to: '！',
+          count: 1
+        },
+        WidthMapping {
+          from: '?',
+          to: '？',
+          count: 1
+        },
+      ]
+    );
+    let (text, _) = normalize_width("你好！", WidthNormalization::Halfwidth);
+    assert_eq!(text, "你好!");
+  }
+}
@@ -0,0 +1,76 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// How rendered glyphs are laid out in the atlas.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PackMode {
+  /// Uniform grid of `font_size` cells, `chars_per_line` per row.
+  #[default]
+  Grid,
+  /// Each glyph's ink bounding box (shadow included) packed onto shelves.
+  Tight,
+}
+
+/// Result of [`shelf_pack`].
+pub struct ShelfPacking {
+  /// Top-left corner of each rectangle, in input order.
+  pub positions: Vec<(u32, u32)>,
+  /// Width actually used, at most the `max_width` passed in.
+  pub width: u32,
+  pub height: u32,
+}
+
+/// Pack `(width, height)` rectangles onto shelves no wider than `max_width`.
+///
+/// Rectangles are placed tallest first, left to right; a new shelf starts below the previous
+/// one when the current shelf is full. Empty rectangles are placed at (0, 0).
+pub fn shelf_pack(sizes: &[(u32, u32)], max_width: u32) -> ShelfPacking {
+  let mut order = (0..sizes.len()).collect::<Vec<_>>();
+  order.sort_by_key(|i| std::cmp::Reverse(sizes[*i].1));
+
+  let mut positions = vec![(0, 0); sizes.len()];
+  let (mut x, mut shelf_y, mut shelf_height) = (0, 0, 0);
+  let mut width = 0;
+  for i in order {
+    let (w, h) = sizes[i];
+    if w == 0 || h == 0 {
+      continue;
+    }
+    if x + w > max_width && x > 0 {
+      shelf_y += shelf_height;
+      x = 0;
+      shelf_height = 0;
+    }
+    positions[i] = (x, shelf_y);
+    x += w;
+    width = width.max(x);
+    shelf_height = shelf_height.max(h);
+  }
+
+  ShelfPacking {
+    positions,
+    width,
+    height: shelf_y + shelf_height,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn rects_never_overlap() {
+    let sizes = [(8, 9), (3, 3), (9, 9), (0, 0), (5, 10), (4, 2)];
+    let packing = shelf_pack(&sizes, 20);
+    assert!(packing.width <= 20);
+    for (i, (&(ax, ay), &(aw, ah))) in packing.positions.iter().zip(&sizes).enumerate() {
+      assert!(ax + aw <= packing.width && ay + ah <= packing.height);
+      for (&(bx, by), &(bw, bh)) in packing.positions.iter().zip(&sizes).skip(i + 1) {
+        let overlap = ax < bx + bw && bx < ax + aw && ay < by + bh && by < ay + ah;
+        assert!(!overlap);
+      }
+    }
+    assert_eq!(packing.height, 19);
+  }
+}
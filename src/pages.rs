@@ -0,0 +1,224 @@
+use std::{
+  collections::BTreeMap,
+  path::{Path, PathBuf},
+};
+
+use anyhow::{Result, bail};
+use image::{RgbImage, imageops};
+use serde::Serialize;
+
+use crate::{
+  atlas::{Atlas, GlyphPlacement},
+  mapping::codepoint_label,
+};
+
+/// Cut a grid atlas into pages of `rows_per_page` cell rows, updating each placement's page and
+/// making `cell_y` page-relative. The last page may be shorter.
+pub fn split_pages(
+  image: &RgbImage,
+  placements: &mut [GlyphPlacement],
+  cell_size: u32,
+  rows_per_page: usize,
+) -> Vec<RgbImage> {
+  let page_height = rows_per_page as u32 * cell_size;
+  let page_count = image.height().div_ceil(page_height).max(1);
+  for placement in placements {
+    placement.page = (placement.cell_y / page_height) as usize;
+    placement.cell_y %= page_height;
+  }
+  (0..page_count)
+    .map(|page| {
+      let y = page * page_height;
+      let height = page_height.min(image.height() - y);
+      imageops::crop_imm(image, 0, y, image.width(), height).to_image()
+    })
+    .collect()
+}
+
+/// Cells per row and rows per page of a `--texture-page-size` page. Both sides must be powers
+/// of two holding at least one cell.
+pub fn texture_page_layout(width: u32, height: u32, cell_size: u32) -> Result<(usize, usize)> {
+  if !width.is_power_of_two() || !height.is_power_of_two() {
+    bail!(
+      "[Error] The texture page size {}x{} must be powers of two!",
+      width,
+      height
+    );
+  }
+  if width < cell_size || height < cell_size {
+    bail!(
+      "[Error] The texture page size {}x{} is smaller than a {}px cell!",
+      width,
+      height,
+      cell_size
+    );
+  }
+  Ok(((width / cell_size) as usize, (height / cell_size) as usize))
+}
+
+/// Merge atlases rendered separately into one record, each atlas becoming a page. The tile
+/// indices continue across the pages. The record's image is the first page; all pages are
+/// returned alongside.
+pub fn combine_pages(atlases: Vec<Atlas>) -> (Atlas, Vec<RgbImage>) {
+  let mut combined = Atlas {
+    image: RgbImage::new(0, 0),
+    tile_count: 0,
+    placements: Vec::new(),
+    clamped_chars: Vec::new(),
+    gamma_flipped_pixels: 0,
+    threshold_overrides: Vec::new(),
+    cells: Vec::new(),
+  };
+  let mut pages = Vec::with_capacity(atlases.len());
+  for (page, atlas) in atlases.into_iter().enumerate() {
+    let first_tile = combined.tile_count;
+    combined.tile_count += atlas.tile_count;
+    combined.placements.extend(
+      atlas
+        .placements
+        .into_iter()
+        .map(|placement| GlyphPlacement {
+          page,
+          tile_index: first_tile + placement.tile_index,
+          vertical_index: placement.vertical_index.map(|index| first_tile + index),
+          ..placement
+        }),
+    );
+    combined.clamped_chars.extend(atlas.clamped_chars);
+    combined.gamma_flipped_pixels += atlas.gamma_flipped_pixels;
+    combined
+      .threshold_overrides
+      .extend(atlas.threshold_overrides);
+    combined.cells.extend(atlas.cells);
+    pages.push(atlas.image);
+  }
+  if let Some(first) = pages.first() {
+    combined.image = first.clone();
+  }
+  (combined, pages)
+}
+
+/// `<stem><suffix>.<ext>` next to `file`.
+fn suffixed_path(file: &Path, suffix: &str) -> PathBuf {
+  let stem = file
+    .file_stem()
+    .map(|stem| stem.to_string_lossy().into_owned())
+    .unwrap_or_default();
+  let file_name = match file.extension() {
+    Some(ext) => format!("{}{}.{}", stem, suffix, ext.to_string_lossy()),
+    None => format!("{}{}", stem, suffix),
+  };
+  file.with_file_name(file_name)
+}
+
+/// File of page `page`: `<stem>_000.<ext>` next to `output`. A single page keeps `output`.
+pub fn page_path(output: &Path, page: usize, page_count: usize) -> PathBuf {
+  if page_count <= 1 {
+    return output.to_path_buf();
+  }
+  suffixed_path(output, &format!("_{:03}", page))
+}
+
+/// File of the `--split-by-block` atlas of Unicode block `block` (codepoint >> 8):
+/// `<stem>_block_4E.<ext>` next to `output`.
+pub fn block_path(output: &Path, block: u32) -> PathBuf {
+  suffixed_path(output, &format!("_block_{:02X}", block))
+}
+
+/// `<stem>_debug.<ext>` next to an atlas file.
+pub fn debug_path(file: &Path) -> PathBuf {
+  suffixed_path(file, "_debug")
+}
+
+/// Where one character lives in a paged atlas.
+#[derive(Serialize)]
+pub struct PageIndexEntry {
+  pub page: usize,
+  pub x: u32,
+  pub y: u32,
+  pub file: String,
+}
+
+/// `--page-index-file` export: codepoint label → page entry. Color class tiles are keyed
+/// `U+XXXX:<class>`.
+pub fn page_index(
+  placements: &[GlyphPlacement],
+  page_files: &[PathBuf],
+) -> BTreeMap<String, PageIndexEntry> {
+  placements
+    .iter()
+    .map(|placement| {
+      let label = match &placement.class {
+        Some(class) => format!("{}:{}", codepoint_label(placement.char), class),
+        None => codepoint_label(placement.char),
+      };
+      (
+        label,
+        PageIndexEntry {
+          page: placement.page,
+          x: placement.cell_x,
+          y: placement.cell_y,
+          file: page_files[placement.page].display().to_string(),
+        },
+      )
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn texture_pages_hold_whole_cells() {
+    assert_eq!(texture_page_layout(2048, 1024, 10).unwrap(), (204, 102));
+    assert_eq!(texture_page_layout(16, 16, 11).unwrap(), (1, 1));
+    assert!(texture_page_layout(2000, 2048, 10).is_err());
+    assert!(texture_page_layout(8, 8, 10).is_err());
+  }
+
+  #[test]
+  fn combined_pages_keep_global_tile_indices() {
+    let atlas = |chars: &[char]| Atlas {
+      image: RgbImage::new(10, 10),
+      tile_count: chars.len(),
+      placements: chars
+        .iter()
+        .enumerate()
+        .map(|(i, c)| GlyphPlacement {
+          char: *c,
+          tile_index: i,
+          ..Default::default()
+        })
+        .collect(),
+      clamped_chars: Vec::new(),
+      gamma_flipped_pixels: 0,
+      threshold_overrides: Vec::new(),
+      cells: Vec::new(),
+    };
+    let (combined, pages) = combine_pages(vec![atlas(&['一', '中']), atlas(&['口'])]);
+    assert_eq!(pages.len(), 2);
+    assert_eq!(combined.tile_count, 3);
+    let tiles = combined
+      .placements
+      .iter()
+      .map(|placement| (placement.char, placement.page, placement.tile_index))
+      .collect::<Vec<_>>();
+    assert_eq!(tiles, [('一', 0, 0), ('中', 0, 1), ('口', 1, 2)]);
+  }
+
+  #[test]
+  fn page_files_are_numbered() {
+    let output = Path::new("out/font.png");
+    assert_eq!(page_path(output, 0, 1), PathBuf::from("out/font.png"));
+    assert_eq!(page_path(output, 2, 3), PathBuf::from("out/font_002.png"));
+    assert_eq!(
+      debug_path(&page_path(output, 2, 3)),
+      PathBuf::from("out/font_002_debug.png")
+    );
+    assert_eq!(
+      block_path(output, 0x4e),
+      PathBuf::from("out/font_block_4E.png")
+    );
+  }
+}
@@ -0,0 +1,116 @@
+use crate::{
+  atlas::GlyphPlacement,
+  render::{CellBuffer, CellPixel},
+  warning::{RenderContext, RenderWarning},
+};
+
+/// Non-background pixels per rendered cell, for `--verify-pixel-count`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PixelCountStats {
+  pub cells: usize,
+  pub min: usize,
+  pub max: usize,
+  pub mean: f64,
+}
+
+/// Char and shadow pixel counts of `cell`.
+fn count_pixels(cell: &CellBuffer) -> (usize, usize) {
+  let size = cell.size();
+  let mut counts = (0, 0);
+  for y in 0..size {
+    for x in 0..size {
+      match cell.get(x, y) {
+        CellPixel::Char => counts.0 += 1,
+        CellPixel::Shadow => counts.1 += 1,
+        CellPixel::Background => {}
+      }
+    }
+  }
+  counts
+}
+
+/// Count the pixels of every cell whose glyph was found, warning about cells that rendered
+/// nothing and cells that hold only shadow pixels. Whitespace characters are expected to be
+/// blank and are left out. `None` when no cell was checked.
+pub fn verify_pixel_counts(
+  placements: &[GlyphPlacement],
+  cells: &[Option<CellBuffer>],
+  context: &mut RenderContext,
+) -> Option<PixelCountStats> {
+  let mut counts = Vec::new();
+  for (placement, cell) in placements.iter().zip(cells) {
+    if placement.missing || placement.char.is_whitespace() {
+      continue;
+    }
+    let (char_pixels, shadow_pixels) = cell.as_ref().map_or((0, 0), count_pixels);
+    let (char, codepoint) = (placement.char, placement.char as u32);
+    if char_pixels + shadow_pixels == 0 {
+      context.warn(RenderWarning::EmptyGlyph { char, codepoint });
+    } else if char_pixels == 0 {
+      context.warn(RenderWarning::ShadowOnlyGlyph { char, codepoint });
+    }
+    counts.push(char_pixels + shadow_pixels);
+  }
+  let min = *counts.iter().min()?;
+  let max = *counts.iter().max()?;
+  Some(PixelCountStats {
+    cells: counts.len(),
+    min,
+    max,
+    mean: counts.iter().sum::<usize>() as f64 / counts.len() as f64,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn placement(char: char, missing: bool) -> GlyphPlacement {
+    GlyphPlacement {
+      char,
+      missing,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn empty_and_shadow_only_cells_are_flagged() {
+    let mut full = CellBuffer::new(10);
+    full.set(0, 0, CellPixel::Char);
+    full.set(1, 1, CellPixel::Shadow);
+    let mut shadow_only = CellBuffer::new(10);
+    shadow_only.set(1, 1, CellPixel::Shadow);
+    let placements = [
+      placement('中', false),
+      placement('丶', false),
+      placement('一', false),
+      placement('口', true),
+      placement('\u{3000}', false),
+    ];
+    let cells = [Some(full), Some(shadow_only), None, None, None];
+    let mut context = RenderContext::default();
+    let stats = verify_pixel_counts(&placements, &cells, &mut context).unwrap();
+    assert_eq!(
+      stats,
+      PixelCountStats {
+        cells: 3,
+        min: 0,
+        max: 2,
+        mean: 1.0
+      }
+    );
+    assert_eq!(
+      context.warnings,
+      [
+        RenderWarning::ShadowOnlyGlyph {
+          char: '丶',
+          codepoint: 0x4e36
+        },
+        RenderWarning::EmptyGlyph {
+          char: '一',
+          codepoint: 0x4e00
+        },
+      ]
+    );
+  }
+}
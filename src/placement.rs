@@ -0,0 +1,94 @@
+/// Cell-local origin (top-left corner) of a glyph's pixel bounds, in signed pixels.
+///
+/// `char_width`/`char_height` are the glyph's rasterized pixel bounds, the side bearings come
+/// straight from the scaled font, and `char_size` is the glyph area of the cell.
+pub fn glyph_origin(
+  char_width: f32,
+  char_height: f32,
+  h_side_bearing: f32,
+  v_side_bearing: f32,
+  char_size: f32,
+) -> (i32, i32) {
+  let h_side_bearing = h_side_bearing.ceil();
+  let v_side_bearing = v_side_bearing.ceil();
+
+  // At least it works...
+  let x = if char_width + h_side_bearing > char_size {
+    // 极少数字符的边距+本体宽会超出9px边界的，因此直接舍弃边界值
+    0
+  } else if char_width < char_size && char_width + h_side_bearing == char_size {
+    // 自、当、日、口、白、目……
+    // 对于比较瘦的字，尽可能靠左
+    h_side_bearing as i32 - 1
+  } else {
+    // 常见规格的方块字
+    h_side_bearing as i32
+  };
+
+  let y = if char_height + v_side_bearing > char_size {
+    // 类似于水平方向的向左，这里尽可能靠近垂直向下方向。
+    (char_size - char_height) as i32
+  } else {
+    // 常见规格的方块字
+    v_side_bearing as i32
+  };
+
+  (x, y)
+}
+
+/// Move a glyph whose negative side bearing would push it outside the cell back to the cell
+/// edge. Returns the adjusted origin and whether any clamping happened.
+pub fn clamp_origin(origin: (i32, i32)) -> ((i32, i32), bool) {
+  let clamped = (origin.0.max(0), origin.1.max(0));
+  (clamped, clamped != origin)
+}
+
+/// Clamp a cell-local pixel position into the `char_size × char_size` glyph area.
+pub fn clamp_to_cell(x: i32, y: i32, char_size: u32) -> (u32, u32) {
+  let max = char_size as i32 - 1;
+  (x.clamp(0, max) as u32, y.clamp(0, max) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn square_glyph_keeps_bearing() {
+    assert_eq!(glyph_origin(7.0, 7.0, 1.0, 1.0, 9.0), (1, 1));
+  }
+
+  #[test]
+  fn skinny_glyph_with_zero_bearing_does_not_underflow() {
+    let (x, y) = glyph_origin(1.0, 9.0, 0.0, 0.0, 9.0);
+    assert_eq!((x, y), (0, 0));
+    assert_eq!(clamp_to_cell(x, y, 9), (0, 0));
+  }
+
+  #[test]
+  fn skinny_glyph_moves_left() {
+    assert_eq!(glyph_origin(5.0, 5.0, 4.0, 2.0, 9.0), (3, 2));
+  }
+
+  #[test]
+  fn negative_bearing_is_clamped_into_cell() {
+    let (x, y) = glyph_origin(8.0, 8.0, -1.0, -2.0, 9.0);
+    assert_eq!((x, y), (-1, -2));
+    assert_eq!(clamp_to_cell(x, y, 9), (0, 0));
+    assert_eq!(clamp_to_cell(x + 8, y + 8, 9), (7, 6));
+  }
+
+  #[test]
+  fn negative_bearing_shifts_whole_glyph_to_cell_edge() {
+    let origin = glyph_origin(5.0, 5.0, -1.5, 2.0, 9.0);
+    assert_eq!(origin, (-1, 2));
+    assert_eq!(clamp_origin(origin), ((0, 2), true));
+    assert_eq!(clamp_origin((1, 1)), ((1, 1), false));
+  }
+
+  #[test]
+  fn oversized_glyph_sticks_to_edges() {
+    assert_eq!(glyph_origin(9.0, 9.0, 1.0, 1.0, 9.0), (0, 0));
+    assert_eq!(clamp_to_cell(12, 12, 9), (8, 8));
+  }
+}
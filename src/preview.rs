@@ -0,0 +1,43 @@
+use anyhow::{Result, bail};
+use image::RgbImage;
+use minifb::{Key, Scale, Window, WindowOptions};
+
+/// Largest window (px) the zoomed atlas may take before a smaller zoom is used.
+const MAX_WINDOW_SIZE: (u32, u32) = (1600, 1000);
+
+/// Largest of 4×/2× zoom that keeps the window within [`MAX_WINDOW_SIZE`]; bigger atlases are
+/// fitted to the screen.
+fn preview_scale(width: u32, height: u32) -> Scale {
+  let fits = |zoom: u32| width * zoom <= MAX_WINDOW_SIZE.0 && height * zoom <= MAX_WINDOW_SIZE.1;
+  if fits(4) {
+    Scale::X4
+  } else if fits(2) {
+    Scale::X2
+  } else {
+    Scale::FitScreen
+  }
+}
+
+/// Show the atlas in a window with nearest-neighbor zoom until it is closed or Esc is pressed.
+pub fn show(image: &RgbImage, title: &str) -> Result<()> {
+  let (width, height) = image.dimensions();
+  let buffer = image
+    .pixels()
+    .map(|pixel| u32::from_be_bytes([0, pixel[0], pixel[1], pixel[2]]))
+    .collect::<Vec<_>>();
+  let options = WindowOptions {
+    scale: preview_scale(width, height),
+    ..WindowOptions::default()
+  };
+  let mut window = match Window::new(title, width as usize, height as usize, options) {
+    Ok(window) => window,
+    Err(e) => bail!("[Error] Could not open the preview window: {}", e),
+  };
+  window.set_target_fps(30);
+  while window.is_open() && !window.is_key_down(Key::Escape) {
+    if let Err(e) = window.update_with_buffer(&buffer, width as usize, height as usize) {
+      bail!("[Error] Could not update the preview window: {}", e);
+    }
+  }
+  Ok(())
+}
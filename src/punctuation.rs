@@ -0,0 +1,175 @@
+use std::collections::BTreeMap;
+
+/// Punctuation marks kept alongside Chinese characters.
+///
+/// https://baike.baidu.com/item/%E6%A0%87%E7%82%B9%E7%AC%A6%E5%8F%B7/588793
+/// https://zh.wikipedia.org/wiki/%E6%A0%87%E7%82%B9%E7%AC%A6%E5%8F%B7
+pub const CHINESE_PUNCTUATION_MARKS: [char; 32] = [
+  '·', '—', '‘', '’', '“', '”', '…', '、', '。', '〈', '〉', '《', '》', '「', '」', '『', '』',
+  '【', '】', '〔', '〕', '︰', '！', '（', '）', '，', '．', '：', '；', '？', '［', '］',
+];
+
+/// The hardcoded offsets of every punctuation mark as a `punctuation_offsets_*` config table,
+/// for users to paste into their config and adjust.
+pub fn default_punctuation_offsets(is_zh_hant: bool) -> BTreeMap<String, [u32; 2]> {
+  CHINESE_PUNCTUATION_MARKS
+    .iter()
+    .map(|c| {
+      let (x, y) = get_chinese_punctuation_offset(*c, is_zh_hant);
+      (c.to_string(), [x, y])
+    })
+    .collect()
+}
+
+/// FUSION PIXEL FONT 10PX ONLY
+/// This is stupid, but it works.
+pub fn get_chinese_punctuation_offset(c: char, is_zh_hant: bool) -> (u32, u32) {
+  match c {
+    '·' => (3, 4),
+    '—' => (0, 4),
+    '‘' => (5, 0),
+    '’' => (0, 0),
+    '“' => (2, 0),
+    '”' => (0, 0),
+    '…' => (0, 4),
+    '、' => {
+      if is_zh_hant {
+        (3, 3)
+      } else {
+        (0, 6)
+      }
+    }
+    '。' => {
+      if is_zh_hant {
+        (2, 3)
+      } else {
+        (0, 5)
+      }
+    }
+    '〈' => (4, 0),
+    '〉' => (0, 0),
+    '《' => (1, 0),
+    '》' => (0, 0),
+    '「' => (4, 0),
+    '」' => (0, 2),
+    '『' => (2, 0),
+    '』' => (0, 2),
+    '【' => (3, 0),
+    '】' => (0, 0),
+    '〔' => (4, 0),
+    '〕' => (0, 0),
+    '︰' => (3, 1),
+    '！' => {
+      if is_zh_hant {
+        (3, 0)
+      } else {
+        (1, 0)
+      }
+    }
+    '（' => (4, 0),
+    '）' => (0, 0),
+    '，' => {
+      if is_zh_hant {
+        (3, 3)
+      } else {
+        (0, 5)
+      }
+    }
+    '．' => {
+      if is_zh_hant {
+        (3, 4)
+      } else {
+        (0, 6)
+      }
+    }
+    '：' => {
+      if is_zh_hant {
+        (3, 1)
+      } else {
+        (0, 1)
+      }
+    }
+    '；' => {
+      if is_zh_hant {
+        (3, 1)
+      } else {
+        (0, 1)
+      }
+    }
+    '？' => {
+      if is_zh_hant {
+        (1, 0)
+      } else {
+        (0, 0)
+      }
+    }
+    '［' => (4, 0),
+    '］' => (0, 0),
+    _ => unreachable!(),
+  }
+}
+
+/// Vertical presentation form (U+FE10-FE19, U+FE30-FE4F) of a horizontal punctuation mark.
+pub fn vertical_form(c: char) -> Option<char> {
+  let form = match c {
+    '，' => '︐',
+    '、' => '︑',
+    '。' => '︒',
+    '：' => '︓',
+    '；' => '︔',
+    '！' => '︕',
+    '？' => '︖',
+    '〖' => '︗',
+    '〗' => '︘',
+    '…' => '︙',
+    '‥' => '︰',
+    '—' => '︱',
+    '–' => '︲',
+    '＿' => '︳',
+    '（' => '︵',
+    '）' => '︶',
+    '｛' => '︷',
+    '｝' => '︸',
+    '〔' => '︹',
+    '〕' => '︺',
+    '【' => '︻',
+    '】' => '︼',
+    '《' => '︽',
+    '》' => '︾',
+    '〈' => '︿',
+    '〉' => '﹀',
+    '「' => '﹁',
+    '」' => '﹂',
+    '『' => '﹃',
+    '』' => '﹄',
+    '［' => '﹇',
+    '］' => '﹈',
+    _ => return None,
+  };
+  Some(form)
+}
+
+/// (horizontal mark, vertical form) of every punctuation mark in `chars` that has a vertical
+/// form, in order. Forms already in `chars` are skipped.
+pub fn vertical_punctuation(chars: &[char]) -> Vec<(char, char)> {
+  chars
+    .iter()
+    .filter_map(|c| Some((*c, vertical_form(*c)?)))
+    .filter(|(_, form)| !chars.contains(form))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn vertical_forms_skip_marks_already_present() {
+    assert_eq!(vertical_form('「'), Some('﹁'));
+    assert_eq!(vertical_form('中'), None);
+    assert_eq!(
+      vertical_punctuation(&['中', '，', '。', '︒', '·']),
+      [('，', '︐')]
+    );
+  }
+}
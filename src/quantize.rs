@@ -0,0 +1,136 @@
+use clap::ValueEnum;
+use image::{Rgb, RgbImage};
+
+use crate::atlas::GlyphPlacement;
+
+/// Ordered dithering applied when reducing the atlas to `target_palette`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Dither {
+  /// Nearest palette color, no dithering.
+  #[default]
+  None,
+  /// 2×2 Bayer matrix.
+  Bayer2,
+  /// 4×4 Bayer matrix.
+  Bayer4,
+}
+
+const BAYER_2: [[u8; 2]; 2] = [[0, 2], [3, 1]];
+const BAYER_4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+impl Dither {
+  /// Threshold in [-0.5, 0.5) at matrix position (`x`, `y`).
+  fn threshold(self, x: u32, y: u32) -> f32 {
+    let (value, levels) = match self {
+      Dither::None => return 0.0,
+      Dither::Bayer2 => (BAYER_2[(y % 2) as usize][(x % 2) as usize], 4),
+      Dither::Bayer4 => (BAYER_4[(y % 4) as usize][(x % 4) as usize], 16),
+    };
+    (value as f32 + 0.5) / levels as f32 - 0.5
+  }
+}
+
+fn nearest(palette: &[Rgb<u8>], color: [f32; 3]) -> Rgb<u8> {
+  *palette
+    .iter()
+    .min_by(|a, b| {
+      let distance =
+        |entry: &Rgb<u8>| -> f32 { (0..3).map(|i| (entry[i] as f32 - color[i]).powi(2)).sum() };
+      distance(a).total_cmp(&distance(b))
+    })
+    .expect("palette is not empty")
+}
+
+/// Map every pixel to the nearest `palette` entry. With dithering, the matrix phase restarts at
+/// the cell origin of the placement whose atlas area holds the pixel, so identical glyphs dither
+/// identically wherever they are, packed tightly or spaced in groups. Pixels outside every
+/// placement, e.g. blank padding tiles, restart it in every `cell_size` cell.
+pub fn quantize<'a>(
+  image: &RgbImage,
+  palette: &[Rgb<u8>],
+  dither: Dither,
+  placements: impl IntoIterator<Item = &'a GlyphPlacement>,
+  cell_size: u32,
+) -> RgbImage {
+  let (width, height) = image.dimensions();
+  let mut origins = vec![None; width as usize * height as usize];
+  for placement in placements {
+    let rect = placement.atlas_rect(cell_size);
+    let origin = placement.cell_origin();
+    for y in rect.y..(rect.y + rect.height).min(height) {
+      for x in rect.x..(rect.x + rect.width).min(width) {
+        origins[(y * width + x) as usize] = Some(origin);
+      }
+    }
+  }
+  // One palette step: the spread the threshold is scaled to.
+  let spread = 255.0 / palette.len().max(2) as f32;
+  let mut quantized = image.clone();
+  for (x, y, pixel) in quantized.enumerate_pixels_mut() {
+    let (origin_x, origin_y) = origins[(y * width + x) as usize]
+      .unwrap_or(((x - x % cell_size) as i64, (y - y % cell_size) as i64));
+    // Both matrix sizes divide 4.
+    let phase_x = (x as i64 - origin_x).rem_euclid(4) as u32;
+    let phase_y = (y as i64 - origin_y).rem_euclid(4) as u32;
+    let offset = dither.threshold(phase_x, phase_y) * spread;
+    *pixel = nearest(palette, pixel.0.map(|channel| channel as f32 + offset));
+  }
+  quantized
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::atlas::PixelRect;
+
+  #[test]
+  fn dither_is_consistent_across_cells() {
+    let palette = [Rgb([0, 0, 0]), Rgb([255, 255, 255])];
+    let image = RgbImage::from_pixel(8, 4, Rgb([128, 128, 128]));
+    assert!(
+      quantize(&image, &palette, Dither::None, [], 4)
+        .pixels()
+        .all(|pixel| *pixel == Rgb([255, 255, 255]))
+    );
+
+    let dithered = quantize(&image, &palette, Dither::Bayer2, [], 4);
+    let whites = dithered
+      .pixels()
+      .filter(|pixel| **pixel == Rgb([255, 255, 255]))
+      .count();
+    assert_eq!(whites, 16);
+    for y in 0..4 {
+      for x in 0..4 {
+        assert_eq!(dithered.get_pixel(x, y), dithered.get_pixel(x + 4, y));
+      }
+    }
+  }
+
+  #[test]
+  fn dither_phase_follows_packed_cells() {
+    let palette = [Rgb([0, 0, 0]), Rgb([255, 255, 255])];
+    let image = RgbImage::from_pixel(9, 4, Rgb([128, 128, 128]));
+    // Two glyphs whose ink starts at (1, 1) of their cells, packed 5px apart.
+    let placements = [0, 5].map(|x| GlyphPlacement {
+      ink_bbox: Some(PixelRect {
+        x: 1,
+        y: 1,
+        width: 3,
+        height: 3,
+      }),
+      packed: Some(PixelRect {
+        x,
+        y: 0,
+        width: 3,
+        height: 3,
+      }),
+      ..Default::default()
+    });
+    let dithered = quantize(&image, &palette, Dither::Bayer4, &placements, 10);
+    for y in 0..3 {
+      for x in 0..3 {
+        assert_eq!(dithered.get_pixel(x, y), dithered.get_pixel(x + 5, y));
+      }
+    }
+  }
+}
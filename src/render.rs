@@ -0,0 +1,873 @@
+use std::collections::BTreeSet;
+
+use ab_glyph::OutlinedGlyph;
+use clap::ValueEnum;
+use image::{Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  atlas::{PixelRect, RenderOptions},
+  placement::clamp_to_cell,
+};
+
+/// What a single pixel of a cell holds after rendering.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CellPixel {
+  Background,
+  Shadow,
+  Char,
+}
+
+/// Colors used when a cell is blitted into the atlas.
+#[derive(Clone, Copy, Debug)]
+pub struct CellColors {
+  /// Character color, the top color of a gradient.
+  pub char_color: Rgb<u8>,
+  pub shadow_color: Rgb<u8>,
+  /// Bottom color of a vertical gradient over each glyph box, flat `char_color` when `None`.
+  pub gradient_bottom: Option<Rgb<u8>>,
+}
+
+/// `color` at `opacity` (0-255) blended over `background`.
+pub fn blend(color: Rgb<u8>, background: Rgb<u8>, opacity: u8) -> Rgb<u8> {
+  Rgb(std::array::from_fn(|i| {
+    ((color[i] as u32 * opacity as u32 + background[i] as u32 * (255 - opacity as u32) + 127) / 255)
+      as u8
+  }))
+}
+
+/// Color at row `y` of a top-to-bottom gradient spanning `height` rows.
+fn gradient_color(top: Rgb<u8>, bottom: Rgb<u8>, y: u32, height: u32) -> Rgb<u8> {
+  if height <= 1 {
+    return top;
+  }
+  let t = y.min(height - 1) as f32 / (height - 1) as f32;
+  Rgb(std::array::from_fn(|i| {
+    (top[i] as f32 + (bottom[i] as f32 - top[i] as f32) * t).round() as u8
+  }))
+}
+
+/// A `size × size` cell rendered in isolation, so neighboring glyphs can never touch each other.
+#[derive(Clone, Debug)]
+pub struct CellBuffer {
+  size: u32,
+  pixels: Vec<CellPixel>,
+}
+
+impl CellBuffer {
+  pub fn new(size: u32) -> Self {
+    CellBuffer {
+      size,
+      pixels: vec![CellPixel::Background; (size * size) as usize],
+    }
+  }
+
+  pub fn size(&self) -> u32 {
+    self.size
+  }
+
+  pub fn get(&self, x: u32, y: u32) -> CellPixel {
+    self.pixels[(y * self.size + x) as usize]
+  }
+
+  /// Set a pixel; positions outside the cell are dropped.
+  pub fn set(&mut self, x: i32, y: i32, pixel: CellPixel) {
+    if x >= 0 && y >= 0 && (x as u32) < self.size && (y as u32) < self.size {
+      self.pixels[(y as u32 * self.size + x as u32) as usize] = pixel;
+    }
+  }
+
+  /// The whole cell, shadow included, turned clockwise by `rotation` around its center.
+  pub fn rotated(&self, rotation: Rotation) -> CellBuffer {
+    let mut rotated = CellBuffer::new(self.size);
+    for y in 0..self.size {
+      for x in 0..self.size {
+        let (rx, ry) = rotation.apply(x as i32, y as i32, self.size);
+        rotated.set(rx, ry, self.get(x, y));
+      }
+    }
+    rotated
+  }
+
+  /// Non-background pixels with their colors, in cell-local coordinates. A gradient is
+  /// interpolated over the rows of [`CellBuffer::char_bbox`].
+  pub fn colored_pixels(&self, colors: CellColors) -> impl Iterator<Item = (u32, u32, Rgb<u8>)> {
+    let size = self.size;
+    let gradient = colors
+      .gradient_bottom
+      .zip(self.char_bbox())
+      .map(|(bottom, bbox)| (bottom, bbox.y, bbox.height));
+    self
+      .pixels
+      .iter()
+      .enumerate()
+      .filter_map(move |(i, pixel)| {
+        let (x, y) = (i as u32 % size, i as u32 / size);
+        let color = match pixel {
+          CellPixel::Background => return None,
+          CellPixel::Shadow => colors.shadow_color,
+          CellPixel::Char => match gradient {
+            Some((bottom, top_y, height)) => {
+              gradient_color(colors.char_color, bottom, y - top_y, height)
+            }
+            None => colors.char_color,
+          },
+        };
+        Some((x, y, color))
+      })
+  }
+
+  /// Bounding box of the character pixels, `None` when the cell has none.
+  pub fn char_bbox(&self) -> Option<PixelRect> {
+    self.bbox(|pixel| pixel == CellPixel::Char)
+  }
+
+  /// Bounding box of every non-background pixel (character and shadow).
+  pub fn ink_bbox(&self) -> Option<PixelRect> {
+    self.bbox(|pixel| pixel != CellPixel::Background)
+  }
+
+  fn bbox(&self, include: impl Fn(CellPixel) -> bool) -> Option<PixelRect> {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+    for (i, pixel) in self.pixels.iter().enumerate() {
+      if include(*pixel) {
+        let (x, y) = (i as u32 % self.size, i as u32 / self.size);
+        min_x = min_x.min(x);
+        min_y = min_y.min(y);
+        max_x = max_x.max(x);
+        max_y = max_y.max(y);
+      }
+    }
+    (min_x != u32::MAX).then(|| PixelRect {
+      x: min_x,
+      y: min_y,
+      width: max_x - min_x + 1,
+      height: max_y - min_y + 1,
+    })
+  }
+
+  /// Color of the pixel at (`x`, `y`), colored `color`, over the atlas pixel `below`. Only the
+  /// shadow is translucent, at `shadow_opacity`.
+  pub fn over(
+    &self,
+    x: u32,
+    y: u32,
+    color: Rgb<u8>,
+    below: Rgb<u8>,
+    shadow_opacity: u8,
+  ) -> Rgb<u8> {
+    match self.get(x, y) {
+      CellPixel::Shadow => blend(color, below, shadow_opacity),
+      _ => color,
+    }
+  }
+
+  /// Draw the non-background pixels over the atlas at (`x_offset`, `y_offset`).
+  pub fn blit(
+    &self,
+    image: &mut RgbImage,
+    x_offset: u32,
+    y_offset: u32,
+    colors: CellColors,
+    shadow_opacity: u8,
+  ) {
+    for (x, y, color) in self.colored_pixels(colors) {
+      let below = *image.get_pixel(x + x_offset, y + y_offset);
+      image.put_pixel(
+        x + x_offset,
+        y + y_offset,
+        self.over(x, y, color, below, shadow_opacity),
+      );
+    }
+  }
+
+  /// Draw only the pixels inside `rect`, with the rect's top-left corner landing at
+  /// (`x_offset`, `y_offset`).
+  pub fn blit_rect(
+    &self,
+    image: &mut RgbImage,
+    rect: PixelRect,
+    x_offset: u32,
+    y_offset: u32,
+    colors: CellColors,
+    shadow_opacity: u8,
+  ) {
+    for (x, y, color) in self.colored_pixels(colors) {
+      if x >= rect.x && y >= rect.y && x < rect.x + rect.width && y < rect.y + rect.height {
+        let (image_x, image_y) = (x - rect.x + x_offset, y - rect.y + y_offset);
+        let below = *image.get_pixel(image_x, image_y);
+        image.put_pixel(
+          image_x,
+          image_y,
+          self.over(x, y, color, below, shadow_opacity),
+        );
+      }
+    }
+  }
+}
+
+/// How the shadow around each glyph is drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShadowStyle {
+  /// 1px bottom-right drop shadow, the 10px style (9px + drop shadow).
+  Drop,
+  /// 1px outline on every side, the 11px style (9px + stroke outline).
+  Outline,
+}
+
+impl ShadowStyle {
+  /// The style that matches a cell size: 10px cells use a drop shadow, 11px cells an outline.
+  pub fn for_font_size(font_size: u32) -> Self {
+    if font_size == 10 {
+      ShadowStyle::Drop
+    } else {
+      ShadowStyle::Outline
+    }
+  }
+
+  /// Shadow pixel offsets relative to each glyph pixel.
+  pub fn offsets(self) -> &'static [(i32, i32)] {
+    match self {
+      ShadowStyle::Drop => &DROP_SHADOW_OFFSETS,
+      ShadowStyle::Outline => &OUTLINE_OFFSETS,
+    }
+  }
+
+  /// Size (px) of the square glyph area inside a `font_size` cell.
+  pub fn glyph_area_size(self, font_size: u32) -> u32 {
+    match self {
+      ShadowStyle::Drop => font_size - 1,
+      ShadowStyle::Outline => font_size - 2,
+    }
+  }
+}
+
+/// Where the drop shadow of [`ShadowStyle::Drop`] falls; the outline is unaffected.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShadowDirection {
+  #[default]
+  BottomRight,
+  Bottom,
+  Right,
+  BottomLeft,
+  TopRight,
+  TopLeft,
+  Top,
+  Left,
+}
+
+impl ShadowDirection {
+  /// Shadow pixel offsets relative to each glyph pixel. Diagonal directions also cover both
+  /// adjacent sides, like the original bottom-right shadow.
+  pub fn offsets(self) -> &'static [(i32, i32)] {
+    match self {
+      ShadowDirection::BottomRight => &DROP_SHADOW_OFFSETS,
+      ShadowDirection::Bottom => &[(0, 1)],
+      ShadowDirection::Right => &[(1, 0)],
+      ShadowDirection::BottomLeft => &[(0, 1), (-1, 1), (-1, 0)],
+      ShadowDirection::TopRight => &[(0, -1), (1, -1), (1, 0)],
+      ShadowDirection::TopLeft => &[(0, -1), (-1, -1), (-1, 0)],
+      ShadowDirection::Top => &[(0, -1)],
+      ShadowDirection::Left => &[(-1, 0)],
+    }
+  }
+}
+
+/// Distance (x, y) of the glyph area from the cell's top-left corner: enough room for shadow
+/// pixels left of and above the glyph, within the `cell_size - char_size` spare pixels.
+pub fn glyph_area_inset(
+  shadow_offsets: &[(i32, i32)],
+  cell_size: u32,
+  char_size: u32,
+) -> (i32, i32) {
+  let spare = (cell_size - char_size) as i32;
+  let inset = |axis: fn(&(i32, i32)) -> i32| {
+    (-shadow_offsets.iter().map(axis).min().unwrap_or(0)).clamp(0, spare)
+  };
+  (inset(|(dx, _)| *dx), inset(|(_, dy)| *dy))
+}
+
+/// How the thresholded glyph itself is drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum RenderStyle {
+  /// Glyph pixels in `char_color` plus the cell size's shadow.
+  #[default]
+  Filled,
+  /// Only the 1px 8-neighborhood outline around the glyph, in `char_color`, with a hollow
+  /// interior and no shadow.
+  Hollow,
+}
+
+/// Clockwise rotation of every glyph, for UIs that show vertical text with rotated characters.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Rotation {
+  #[default]
+  #[value(name = "0")]
+  None,
+  #[value(name = "90")]
+  Quarter,
+  #[value(name = "180")]
+  Half,
+  #[value(name = "270")]
+  ThreeQuarters,
+}
+
+impl Rotation {
+  /// Clockwise angle in degrees.
+  pub fn degrees(self) -> u32 {
+    match self {
+      Rotation::None => 0,
+      Rotation::Quarter => 90,
+      Rotation::Half => 180,
+      Rotation::ThreeQuarters => 270,
+    }
+  }
+
+  /// This rotation plus a further quarter turn clockwise.
+  pub fn turned(self) -> Rotation {
+    match self {
+      Rotation::None => Rotation::Quarter,
+      Rotation::Quarter => Rotation::Half,
+      Rotation::Half => Rotation::ThreeQuarters,
+      Rotation::ThreeQuarters => Rotation::None,
+    }
+  }
+
+  /// Rotate (`x`, `y`) within a `size`×`size` square around its center.
+  pub fn apply(self, x: i32, y: i32, size: u32) -> (i32, i32) {
+    let last = size as i32 - 1;
+    match self {
+      Rotation::None => (x, y),
+      Rotation::Quarter => (last - y, x),
+      Rotation::Half => (last - x, last - y),
+      Rotation::ThreeQuarters => (y, last - x),
+    }
+  }
+}
+
+/// Coverage above which a pixel counts as part of the glyph.
+pub const COVERAGE_THRESHOLD: f32 = 0.5;
+
+/// How raw glyph coverage becomes mask pixels: `v.powf(1.0 / gamma) > threshold`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Coverage {
+  pub threshold: f32,
+  pub gamma: f32,
+}
+
+impl Default for Coverage {
+  fn default() -> Self {
+    Coverage {
+      threshold: COVERAGE_THRESHOLD,
+      gamma: 1.0,
+    }
+  }
+}
+
+impl Coverage {
+  pub fn is_inked(self, v: f32) -> bool {
+    v.powf(1.0 / self.gamma) > self.threshold
+  }
+}
+
+/// Drop shadow of 10px cells: bottom, bottom-right, right.
+pub const DROP_SHADOW_OFFSETS: [(i32, i32); 3] = [(0, 1), (1, 1), (1, 0)];
+/// Outline of 11px cells: bottom, bottom-right, right, top-right, top, top-left, left,
+/// bottom-left.
+pub const OUTLINE_OFFSETS: [(i32, i32); 8] = [
+  (0, 1),
+  (1, 1),
+  (1, 0),
+  (1, -1),
+  (0, -1),
+  (-1, -1),
+  (-1, 0),
+  (-1, 1),
+];
+
+/// Thresholded glyph coverage in cell-local coordinates.
+pub struct GlyphMask {
+  size: u32,
+  bits: Vec<bool>,
+}
+
+impl GlyphMask {
+  pub fn new(size: u32) -> Self {
+    GlyphMask {
+      size,
+      bits: vec![false; (size * size) as usize],
+    }
+  }
+
+  pub fn size(&self) -> u32 {
+    self.size
+  }
+
+  /// Whether (`x`, `y`) is inked; positions outside the mask are not.
+  pub fn get(&self, x: i32, y: i32) -> bool {
+    x >= 0
+      && y >= 0
+      && (x as u32) < self.size
+      && (y as u32) < self.size
+      && self.bits[(y as u32 * self.size + x as u32) as usize]
+  }
+
+  /// Set a pixel; positions outside the mask are dropped.
+  pub fn set(&mut self, x: i32, y: i32, inked: bool) {
+    if x >= 0 && y >= 0 && (x as u32) < self.size && (y as u32) < self.size {
+      self.bits[(y as u32 * self.size + x as u32) as usize] = inked;
+    }
+  }
+
+  /// Number of pixels inked in only one of the two masks.
+  pub fn count_differences(&self, other: &GlyphMask) -> usize {
+    self
+      .bits
+      .iter()
+      .zip(&other.bits)
+      .filter(|(a, b)| a != b)
+      .count()
+  }
+
+  /// Fake bold: ink every pixel's right neighbor too, as if the glyph were drawn again 1px to the
+  /// right. Strikes at or past `right_edge` are dropped; returns whether any was.
+  pub fn double_strike(&mut self, right_edge: i32) -> bool {
+    let pixels = self.pixels().collect::<Vec<_>>();
+    let mut clipped = false;
+    for (x, y) in pixels {
+      if x + 1 < right_edge {
+        self.set(x + 1, y, true);
+      } else {
+        clipped = true;
+      }
+    }
+    clipped
+  }
+
+  /// Fake italic: shift the rows of the inked box right, `slant` px at the top third, half of it
+  /// (rounded down) in the middle third and none at the bottom. Pixels pushed to or past
+  /// `right_edge` are dropped; returns whether any was.
+  pub fn shear(&mut self, slant: u32, right_edge: i32) -> bool {
+    let pixels = self.pixels().collect::<Vec<_>>();
+    let (Some(top), Some(bottom)) = (
+      pixels.iter().map(|(_, y)| *y).min(),
+      pixels.iter().map(|(_, y)| *y).max(),
+    ) else {
+      return false;
+    };
+    let height = bottom - top + 1;
+    let mut clipped = false;
+    self.bits.fill(false);
+    for (x, y) in pixels {
+      let band = (y - top) * 3 / height;
+      let x = x + slant as i32 * (2 - band) / 2;
+      if x < right_edge {
+        self.set(x, y, true);
+      } else {
+        clipped = true;
+      }
+    }
+    clipped
+  }
+
+  /// Inked pixel positions in row-major order.
+  pub fn pixels(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+    let size = self.size;
+    self
+      .bits
+      .iter()
+      .enumerate()
+      .filter(|(_, inked)| **inked)
+      .map(move |(i, _)| ((i as u32 % size) as i32, (i as u32 / size) as i32))
+  }
+}
+
+/// Threshold a glyph into a `cell_size` mask.
+///
+/// `origin` is the cell-local position of the glyph's pixel bounds, `char_size` the glyph area
+/// the pixels are clamped into and `inset` the (x, y) distance of that area from the cell's
+/// top-left. The pixels are rotated by `rotation` within the glyph area, so the shadow built
+/// from the mask follows the rotated glyph and stays inside the cell.
+pub fn rasterize(
+  outlined_glyph: &OutlinedGlyph,
+  origin: (i32, i32),
+  cell_size: u32,
+  char_size: u32,
+  inset: (i32, i32),
+  coverage: Coverage,
+  rotation: Rotation,
+) -> GlyphMask {
+  let mut mask = GlyphMask::new(cell_size);
+  let (origin_x, origin_y) = origin;
+  outlined_glyph.draw(|x, y, v| {
+    if coverage.is_inked(v) {
+      let (x, y) = clamp_to_cell(x as i32 + origin_x, y as i32 + origin_y, char_size);
+      let (x, y) = rotation.apply(x as i32, y as i32, char_size);
+      mask.set(x + inset.0, y + inset.1, true);
+    }
+  });
+  mask
+}
+
+/// Offsets of a `width` px shadow: the 1px `offsets` plus every offset within Manhattan
+/// distance `width` of the glyph pixel, in the directions of `offsets`. A drop shadow extends
+/// further bottom-right, an outline gets thicker.
+pub fn spread_offsets(offsets: &[(i32, i32)], width: u32) -> Vec<(i32, i32)> {
+  let width = width as i32;
+  // Range of the signs of an axis, e.g. 0..=1 for the bottom-right drop shadow.
+  let signs = |axis: fn(&(i32, i32)) -> i32| {
+    let signs = offsets.iter().map(|offset| axis(offset).signum());
+    signs.clone().min().unwrap_or(0)..=signs.max().unwrap_or(0)
+  };
+  let (x_signs, y_signs) = (signs(|(dx, _)| *dx), signs(|(_, dy)| *dy));
+  let mut spread = offsets.iter().copied().collect::<BTreeSet<_>>();
+  for dy in -width..=width {
+    for dx in -width..=width {
+      if dx.abs() + dy.abs() <= width
+        && x_signs.contains(&dx.signum())
+        && y_signs.contains(&dy.signum())
+      {
+        spread.insert((dx, dy));
+      }
+    }
+  }
+  spread.remove(&(0, 0));
+  spread.into_iter().collect()
+}
+
+/// Composite a glyph mask into a cell: every shadow pixel first, then every character pixel,
+/// so a shadow can never cover a stroke of the same glyph.
+pub fn compose_cell(mask: &GlyphMask, shadow_offsets: &[(i32, i32)]) -> CellBuffer {
+  let mut cell = CellBuffer::new(mask.size());
+  for (x, y) in mask.pixels() {
+    for (dx, dy) in shadow_offsets {
+      cell.set(x + dx, y + dy, CellPixel::Shadow);
+    }
+  }
+  for (x, y) in mask.pixels() {
+    cell.set(x, y, CellPixel::Char);
+  }
+  cell
+}
+
+/// Draw only the pixels surrounding a glyph mask (its 8-neighborhood outline) as character
+/// pixels; the glyph's own pixels stay background.
+pub fn hollow_cell(mask: &GlyphMask) -> CellBuffer {
+  let mut cell = CellBuffer::new(mask.size());
+  for (x, y) in mask.pixels() {
+    for (dx, dy) in OUTLINE_OFFSETS {
+      if !mask.get(x + dx, y + dy) {
+        cell.set(x + dx, y + dy, CellPixel::Char);
+      }
+    }
+  }
+  cell
+}
+
+/// A glyph rendered by [`render_cell`].
+pub struct RenderedCell {
+  pub cell: CellBuffer,
+  /// Mask pixels the coverage gamma flipped compared to gamma 1.0.
+  pub gamma_flips: usize,
+  /// The bold strike was cut at the right edge of the glyph area.
+  pub bold_clipped: bool,
+  /// The slant pushed pixels past the right edge of the glyph area.
+  pub slant_clipped: bool,
+  /// Shadow pixels fell outside the cell, as a shadow wider than 1px has no room reserved.
+  pub shadow_clipped: bool,
+}
+
+/// Per-tile drawing style on top of the shared [`RenderOptions`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GlyphStyle {
+  /// Double-strike the mask 1px apart horizontally.
+  pub bold: bool,
+  /// Shear (px) of the mask's top third, see [`GlyphMask::shear`].
+  pub slant: u32,
+  pub rotation: Rotation,
+}
+
+/// Largest `slant` (px of shear across the glyph height).
+pub const MAX_SLANT: u32 = 2;
+
+/// Render a glyph into its own cell buffer with the cell size, shadow style and drawing style of
+/// `options`. `shadow_offsets` are the shadow pixels (usually `options.shadow.offsets()`). The
+/// mask is rotated, sheared and double-struck according to `style` before the shadow pass.
+pub fn render_cell(
+  outlined_glyph: &OutlinedGlyph,
+  origin: (i32, i32),
+  options: &RenderOptions,
+  shadow_offsets: &[(i32, i32)],
+  coverage: Coverage,
+  style: GlyphStyle,
+) -> RenderedCell {
+  let font_size = options.font_size;
+  let char_size = options.shadow.glyph_area_size(font_size);
+  let inset = glyph_area_inset(shadow_offsets, font_size, char_size);
+  let rasterize_with = |coverage| {
+    rasterize(
+      outlined_glyph,
+      origin,
+      font_size,
+      char_size,
+      inset,
+      coverage,
+      style.rotation,
+    )
+  };
+  let mut mask = rasterize_with(coverage);
+  let gamma_flips = if coverage.gamma == 1.0 {
+    0
+  } else {
+    mask.count_differences(&rasterize_with(Coverage {
+      gamma: 1.0,
+      ..coverage
+    }))
+  };
+  let right_edge = inset.0 + char_size as i32;
+  let slant_clipped = style.slant > 0 && mask.shear(style.slant, right_edge);
+  let bold_clipped = style.bold && mask.double_strike(right_edge);
+  let spread;
+  let shadow_offsets = if options.shadow_width > 1 {
+    spread = spread_offsets(shadow_offsets, options.shadow_width);
+    &spread
+  } else {
+    shadow_offsets
+  };
+  let (cell, shadow_clipped) = match options.style {
+    RenderStyle::Filled => {
+      let size = font_size as i32;
+      let shadow_clipped = mask.pixels().any(|(x, y)| {
+        shadow_offsets
+          .iter()
+          .any(|(dx, dy)| !(0..size).contains(&(x + dx)) || !(0..size).contains(&(y + dy)))
+      });
+      (compose_cell(&mask, shadow_offsets), shadow_clipped)
+    }
+    RenderStyle::Hollow => (hollow_cell(&mask), false),
+  };
+  RenderedCell {
+    cell,
+    gamma_flips,
+    bold_clipped,
+    slant_clipped,
+    shadow_clipped,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn rotation_turns_clockwise_around_the_center() {
+    assert_eq!(Rotation::None.apply(1, 2, 9), (1, 2));
+    assert_eq!(Rotation::Quarter.apply(1, 2, 9), (6, 1));
+    assert_eq!(Rotation::Half.apply(1, 2, 9), (7, 6));
+    assert_eq!(Rotation::ThreeQuarters.apply(1, 2, 9), (2, 7));
+  }
+
+  #[test]
+  fn double_strike_widens_strokes_within_the_glyph_area() {
+    let mut mask = GlyphMask::new(10);
+    mask.set(3, 2, true);
+    mask.set(4, 2, true);
+    assert!(!mask.double_strike(9));
+    assert_eq!(mask.pixels().collect::<Vec<_>>(), [(3, 2), (4, 2), (5, 2)]);
+    mask.set(8, 5, true);
+    assert!(mask.double_strike(9));
+    assert!(!mask.get(9, 5));
+  }
+
+  #[test]
+  fn shear_shifts_the_top_rows_most() {
+    let mut mask = GlyphMask::new(10);
+    for y in 0..9 {
+      mask.set(3, y, true);
+    }
+    assert!(!mask.shear(2, 9));
+    let column = |mask: &GlyphMask, y| (0..10).find(|x| mask.get(*x, y));
+    assert_eq!((column(&mask, 0), column(&mask, 2)), (Some(5), Some(5)));
+    assert_eq!((column(&mask, 3), column(&mask, 5)), (Some(4), Some(4)));
+    assert_eq!((column(&mask, 6), column(&mask, 8)), (Some(3), Some(3)));
+    assert!(mask.shear(2, 6));
+    assert_eq!(column(&mask, 0), None);
+  }
+
+  #[test]
+  fn wider_shadows_cover_the_manhattan_distance() {
+    assert_eq!(
+      spread_offsets(&DROP_SHADOW_OFFSETS, 1),
+      [(0, 1), (1, 0), (1, 1)]
+    );
+    assert_eq!(spread_offsets(&OUTLINE_OFFSETS, 1).len(), 8);
+    assert_eq!(
+      spread_offsets(&DROP_SHADOW_OFFSETS, 2),
+      [(0, 1), (0, 2), (1, 0), (1, 1), (2, 0)]
+    );
+    // The (2, 2) corner is 4px away.
+    assert_eq!(spread_offsets(&DROP_SHADOW_OFFSETS, 3).len(), 9);
+    assert!(!spread_offsets(&DROP_SHADOW_OFFSETS, 3).contains(&(2, 2)));
+    // A 2px outline is the diamond of 13 pixels around the glyph pixel, less the pixel itself.
+    assert_eq!(spread_offsets(&OUTLINE_OFFSETS, 2).len(), 12);
+    assert_eq!(spread_offsets(&OUTLINE_OFFSETS, 3).len(), 24);
+    assert_eq!(spread_offsets(&[(0, 1)], 3), [(0, 1), (0, 2), (0, 3)]);
+  }
+
+  #[test]
+  fn rotated_cells_keep_the_shadow_with_the_glyph() {
+    let mut mask = GlyphMask::new(10);
+    mask.set(0, 0, true);
+    let cell = compose_cell(&mask, &DROP_SHADOW_OFFSETS).rotated(Rotation::Quarter);
+    assert_eq!(cell.get(9, 0), CellPixel::Char);
+    assert_eq!(cell.get(8, 0), CellPixel::Shadow);
+    assert_eq!(cell.get(8, 1), CellPixel::Shadow);
+    assert_eq!(cell.get(9, 1), CellPixel::Shadow);
+  }
+
+  #[test]
+  fn shadow_never_covers_char_pixels() {
+    let mut mask = GlyphMask::new(10);
+    mask.set(3, 3, true);
+    mask.set(4, 3, true);
+    mask.set(3, 4, true);
+    let cell = compose_cell(&mask, &DROP_SHADOW_OFFSETS);
+    assert_eq!(cell.get(3, 3), CellPixel::Char);
+    assert_eq!(cell.get(4, 3), CellPixel::Char);
+    assert_eq!(cell.get(3, 4), CellPixel::Char);
+    assert_eq!(cell.get(4, 4), CellPixel::Shadow);
+    assert_eq!(cell.get(5, 3), CellPixel::Shadow);
+  }
+
+  #[test]
+  fn outline_surrounds_glyph() {
+    let mut mask = GlyphMask::new(11);
+    mask.set(1, 1, true);
+    let cell = compose_cell(&mask, &OUTLINE_OFFSETS);
+    assert_eq!(cell.get(1, 1), CellPixel::Char);
+    for (dx, dy) in OUTLINE_OFFSETS {
+      assert_eq!(
+        cell.get((1 + dx) as u32, (1 + dy) as u32),
+        CellPixel::Shadow
+      );
+    }
+  }
+
+  #[test]
+  fn outline_is_clipped_at_the_cell_edges() {
+    // The -1 offsets of the 11px outline must not wrap or panic at the first row and column.
+    let mut mask = GlyphMask::new(11);
+    mask.set(0, 0, true);
+    mask.set(10, 10, true);
+    let cell = compose_cell(&mask, &OUTLINE_OFFSETS);
+    assert_eq!(cell.get(0, 0), CellPixel::Char);
+    assert_eq!(cell.get(10, 10), CellPixel::Char);
+    let shadow = (0..11)
+      .flat_map(|y| (0..11).map(move |x| (x, y)))
+      .filter(|(x, y)| cell.get(*x, *y) == CellPixel::Shadow)
+      .collect::<Vec<_>>();
+    assert_eq!(shadow, [(1, 0), (0, 1), (1, 1), (9, 9), (10, 9), (9, 10)]);
+  }
+
+  #[test]
+  fn gradient_spans_the_glyph_box() {
+    let mut mask = GlyphMask::new(10);
+    for y in 2..5 {
+      mask.set(3, y, true);
+    }
+    let cell = compose_cell(&mask, &DROP_SHADOW_OFFSETS);
+    let colors = CellColors {
+      char_color: Rgb([200, 200, 200]),
+      shadow_color: Rgb([1, 1, 1]),
+      gradient_bottom: Some(Rgb([100, 100, 100])),
+    };
+    let pixels = cell
+      .colored_pixels(colors)
+      .filter(|(x, _, _)| *x == 3)
+      .map(|(_, y, color)| (y, color[0]))
+      .collect::<Vec<_>>();
+    assert_eq!(pixels, vec![(2, 200), (3, 150), (4, 100), (5, 1)]);
+  }
+
+  #[test]
+  fn shadow_opacity_blends_over_background() {
+    let (shadow, bg) = (Rgb([0, 0, 0]), Rgb([200, 100, 50]));
+    assert_eq!(blend(shadow, bg, 255), shadow);
+    assert_eq!(blend(shadow, bg, 0), bg);
+    assert_eq!(blend(shadow, bg, 128), Rgb([100, 50, 25]));
+  }
+
+  #[test]
+  fn translucent_shadow_blends_over_the_pixel_below() {
+    let mut cell = CellBuffer::new(2);
+    cell.set(0, 0, CellPixel::Char);
+    cell.set(1, 0, CellPixel::Shadow);
+    cell.set(0, 1, CellPixel::Shadow);
+    let colors = CellColors {
+      char_color: Rgb([255, 255, 255]),
+      shadow_color: Rgb([0, 0, 0]),
+      gradient_bottom: None,
+    };
+    // A checkerboard-like destination: the two shadow pixels lie over different colors.
+    let mut image = RgbImage::from_pixel(2, 2, Rgb([200, 200, 200]));
+    image.put_pixel(0, 1, Rgb([100, 100, 100]));
+    cell.blit(&mut image, 0, 0, colors, 128);
+    assert_eq!(*image.get_pixel(0, 0), Rgb([255, 255, 255]));
+    assert_eq!(*image.get_pixel(1, 0), Rgb([100, 100, 100]));
+    assert_eq!(*image.get_pixel(0, 1), Rgb([50, 50, 50]));
+    assert_eq!(*image.get_pixel(1, 1), Rgb([200, 200, 200]));
+  }
+
+  #[test]
+  fn glyph_area_makes_room_for_shadows_above_and_left() {
+    assert_eq!(
+      glyph_area_inset(ShadowDirection::BottomRight.offsets(), 10, 9),
+      (0, 0)
+    );
+    assert_eq!(
+      glyph_area_inset(ShadowDirection::TopLeft.offsets(), 10, 9),
+      (1, 1)
+    );
+    assert_eq!(
+      glyph_area_inset(ShadowDirection::Left.offsets(), 10, 9),
+      (1, 0)
+    );
+    assert_eq!(glyph_area_inset(&OUTLINE_OFFSETS, 11, 9), (1, 1));
+    assert_eq!(glyph_area_inset(&[(-3, 0)], 10, 9), (1, 0));
+  }
+
+  #[test]
+  fn gamma_lifts_faint_coverage() {
+    let coverage = Coverage::default();
+    assert!(!coverage.is_inked(0.45));
+    assert!(
+      Coverage {
+        gamma: 1.5,
+        ..coverage
+      }
+      .is_inked(0.45)
+    );
+    assert!(
+      !Coverage {
+        gamma: 1.5,
+        ..coverage
+      }
+      .is_inked(0.3)
+    );
+  }
+
+  #[test]
+  fn hollow_keeps_only_the_outline() {
+    let mut mask = GlyphMask::new(10);
+    mask.set(4, 4, true);
+    mask.set(5, 4, true);
+    let cell = hollow_cell(&mask);
+    assert_eq!(cell.get(4, 4), CellPixel::Background);
+    assert_eq!(cell.get(5, 4), CellPixel::Background);
+    assert_eq!(cell.get(3, 3), CellPixel::Char);
+    assert_eq!(cell.get(6, 5), CellPixel::Char);
+    assert_eq!(cell.get(7, 4), CellPixel::Background);
+    assert_eq!(cell.ink_bbox().unwrap().width, 4);
+  }
+}
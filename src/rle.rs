@@ -0,0 +1,169 @@
+use anyhow::{Result, bail};
+use image::{Rgb, RgbImage};
+
+/// Magic bytes at the start of every RLE stream.
+pub const RLE_MAGIC: [u8; 4] = *b"CBRL";
+/// Header layout: magic(4) + width(u32 LE) + height(u32 LE) + depth(u8) + reserved(3).
+pub const RLE_HEADER_SIZE: usize = 16;
+
+/// Color depth of the pixel data stored in an RLE stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RleDepth {
+  /// 1 byte per pixel, 0 = background, anything else = foreground.
+  Monochrome = 1,
+  /// 3 bytes per pixel (R, G, B).
+  Rgb = 24,
+}
+
+impl RleDepth {
+  pub fn bytes_per_pixel(self) -> usize {
+    match self {
+      RleDepth::Monochrome => 1,
+      RleDepth::Rgb => 3,
+    }
+  }
+
+  fn from_u8(value: u8) -> Result<Self> {
+    match value {
+      1 => Ok(RleDepth::Monochrome),
+      24 => Ok(RleDepth::Rgb),
+      _ => bail!("[Error] Unsupported RLE color depth: {}", value),
+    }
+  }
+}
+
+/// Encode raw pixel data as `(pixel, run_length: u8)` pairs behind a 16-byte header.
+///
+/// `pixels` holds `depth.bytes_per_pixel()` bytes per pixel in row-major order.
+pub fn encode_rle(width: u32, height: u32, depth: RleDepth, pixels: &[u8]) -> Result<Vec<u8>> {
+  let bpp = depth.bytes_per_pixel();
+  if pixels.len() != width as usize * height as usize * bpp {
+    bail!(
+      "[Error] RLE input size mismatch: expected {} bytes for {}x{}, got {}",
+      width as usize * height as usize * bpp,
+      width,
+      height,
+      pixels.len()
+    );
+  }
+
+  let mut encoded = Vec::with_capacity(RLE_HEADER_SIZE + pixels.len() / 4);
+  encoded.extend_from_slice(&RLE_MAGIC);
+  encoded.extend_from_slice(&width.to_le_bytes());
+  encoded.extend_from_slice(&height.to_le_bytes());
+  encoded.push(depth as u8);
+  encoded.extend_from_slice(&[0; 3]);
+
+  let mut chunks = pixels.chunks_exact(bpp).peekable();
+  while let Some(pixel) = chunks.next() {
+    let mut run_length: u8 = 1;
+    while run_length < u8::MAX && chunks.peek() == Some(&pixel) {
+      chunks.next();
+      run_length += 1;
+    }
+    encoded.extend_from_slice(pixel);
+    encoded.push(run_length);
+  }
+
+  Ok(encoded)
+}
+
+/// Depth and pixel data of `image` for [`encode_rle`]: [`RleDepth::Monochrome`] (0 for
+/// `background`, 1 for `foreground`) when the image has no other color, [`RleDepth::Rgb`]
+/// otherwise.
+pub fn rle_pixels(
+  image: &RgbImage,
+  background: Rgb<u8>,
+  foreground: Rgb<u8>,
+) -> (RleDepth, Vec<u8>) {
+  let monochrome = image
+    .pixels()
+    .map(|pixel| match *pixel {
+      pixel if pixel == background => Some(0),
+      pixel if pixel == foreground => Some(1),
+      _ => None,
+    })
+    .collect::<Option<Vec<_>>>();
+  match monochrome {
+    Some(pixels) => (RleDepth::Monochrome, pixels),
+    None => (RleDepth::Rgb, image.as_raw().clone()),
+  }
+}
+
+/// Decode an RLE stream produced by [`encode_rle`] back into raw pixel data.
+pub fn decode_rle(bytes: &[u8]) -> Result<Vec<u8>> {
+  if bytes.len() < RLE_HEADER_SIZE || bytes[0..4] != RLE_MAGIC {
+    bail!("[Error] Not a valid RLE stream!");
+  }
+  let width = u32::from_le_bytes(bytes[4..8].try_into()?);
+  let height = u32::from_le_bytes(bytes[8..12].try_into()?);
+  let depth = RleDepth::from_u8(bytes[12])?;
+  let bpp = depth.bytes_per_pixel();
+  let expected_len = width as usize * height as usize * bpp;
+
+  let body = &bytes[RLE_HEADER_SIZE..];
+  if !body.len().is_multiple_of(bpp + 1) {
+    bail!("[Error] Truncated RLE stream!");
+  }
+
+  let mut pixels = Vec::with_capacity(expected_len);
+  for run in body.chunks_exact(bpp + 1) {
+    let (pixel, run_length) = run.split_at(bpp);
+    for _ in 0..run_length[0] {
+      pixels.extend_from_slice(pixel);
+    }
+  }
+  if pixels.len() != expected_len {
+    bail!(
+      "[Error] RLE stream decoded to {} bytes, expected {}",
+      pixels.len(),
+      expected_len
+    );
+  }
+
+  Ok(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn round_trip_rgb() {
+    let mut pixels = vec![45; 300 * 3];
+    pixels[10 * 3..10 * 3 + 3].copy_from_slice(&[250, 250, 245]);
+    let encoded = encode_rle(30, 10, RleDepth::Rgb, &pixels).unwrap();
+    assert_eq!(&encoded[0..4], &RLE_MAGIC);
+    assert!(encoded.len() < pixels.len());
+    assert_eq!(decode_rle(&encoded).unwrap(), pixels);
+  }
+
+  #[test]
+  fn round_trip_monochrome_long_runs() {
+    let mut pixels = vec![0; 1000];
+    pixels[999] = 1;
+    let encoded = encode_rle(100, 10, RleDepth::Monochrome, &pixels).unwrap();
+    assert_eq!(decode_rle(&encoded).unwrap(), pixels);
+  }
+
+  #[test]
+  fn two_color_images_are_monochrome() {
+    let (bg, fg) = (Rgb([45, 45, 45]), Rgb([250, 250, 245]));
+    let mut image = RgbImage::from_pixel(3, 1, bg);
+    image.put_pixel(1, 0, fg);
+    assert_eq!(
+      rle_pixels(&image, bg, fg),
+      (RleDepth::Monochrome, vec![0, 1, 0])
+    );
+    image.put_pixel(2, 0, Rgb([0, 0, 0]));
+    assert_eq!(
+      rle_pixels(&image, bg, fg),
+      (RleDepth::Rgb, image.as_raw().clone())
+    );
+  }
+
+  #[test]
+  fn rejects_bad_magic() {
+    assert!(decode_rle(&[0; RLE_HEADER_SIZE]).is_err());
+  }
+}
@@ -0,0 +1,300 @@
+use std::{
+  collections::{HashMap, HashSet},
+  ops::RangeInclusive,
+};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde_json::Value;
+
+use crate::punctuation::CHINESE_PUNCTUATION_MARKS;
+
+/// Shortest run of consecutive CJK characters kept when scanning binary scripts.
+const MIN_BINARY_RUN_LENGTH: usize = 2;
+
+/// CJK Compatibility Ideographs, found in scripts converted from Big5 and other legacy
+/// encodings.
+pub const CJK_COMPAT_IDEOGRAPHS: RangeInclusive<char> = '\u{F900}'..='\u{FAFF}';
+
+pub fn is_compat_ideograph(c: char) -> bool {
+  CJK_COMPAT_IDEOGRAPHS.contains(&c)
+}
+
+/// Order of the characters in the atlas.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+  /// Ascending Unicode codepoint.
+  #[default]
+  Codepoint,
+  /// Most frequent in the script first, ties by codepoint.
+  Frequency,
+  /// Ascending GB2312 区位 code, characters outside GB2312 last by codepoint.
+  Gb2312,
+  /// Ascending Big5 code, characters outside Big5 last by codepoint.
+  Big5,
+}
+
+impl SortOrder {
+  /// Legacy double-byte encoding of the order, `None` for the Unicode-based orders.
+  pub fn legacy_encoding(self) -> Option<&'static str> {
+    match self {
+      SortOrder::Codepoint | SortOrder::Frequency => None,
+      SortOrder::Gb2312 => Some("GB2312"),
+      SortOrder::Big5 => Some("Big5"),
+    }
+  }
+}
+
+/// U+3000, used for indentation in scripts. Whitespace, so only kept on request, as a blank tile.
+pub const IDEOGRAPHIC_SPACE: char = '\u{3000}';
+
+/// How the game script file is stored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ScriptFormat {
+  /// Plain UTF-8 text.
+  #[default]
+  Plain,
+  /// Compiled Ren'Py script (or any binary): UTF-8 CJK runs are scanned out of the bytes.
+  Rpyc,
+  /// JSON document: every string value is collected.
+  Json,
+}
+
+/// Turn a script file into text according to its format.
+pub fn read_script(bytes: &[u8], format: ScriptFormat) -> Result<String> {
+  match format {
+    ScriptFormat::Plain => {
+      Ok(String::from_utf8(bytes.to_vec()).context("[Error] Game script is not valid UTF-8!")?)
+    }
+    ScriptFormat::Rpyc => Ok(extract_binary_strings(bytes)),
+    ScriptFormat::Json => {
+      let value: Value =
+        serde_json::from_slice(bytes).context("[Error] Game script is not valid JSON!")?;
+      let mut strings = Vec::new();
+      collect_json_strings(&value, &mut strings);
+      Ok(strings.join("\n"))
+    }
+  }
+}
+
+/// Whether `c` is kept by [`get_unique_chinese_chars`], i.e. whether an atlas built from a
+/// script containing it has a tile for it.
+pub fn is_supported_char(c: char) -> bool {
+  CHINESE_PUNCTUATION_MARKS.contains(&c)
+    || is_compat_ideograph(c)
+    || is_chinese::is_chinese(c.to_string().as_str())
+}
+
+/// Scan arbitrary bytes for runs of UTF-8 encoded CJK characters, without parsing the format.
+/// Runs shorter than two characters are dropped to avoid false positives.
+pub fn extract_binary_strings(bytes: &[u8]) -> String {
+  let mut runs = Vec::new();
+  let mut run = String::new();
+  let mut i = 0;
+  while i < bytes.len() {
+    // UTF-8 sequences are at most 4 bytes long.
+    let decoded = (2..=4)
+      .filter(|len| i + len <= bytes.len())
+      .find_map(|len| {
+        std::str::from_utf8(&bytes[i..i + len])
+          .ok()
+          .and_then(|s| s.chars().next())
+          .filter(|c| c.len_utf8() == len && is_supported_char(*c))
+      });
+    if let Some(c) = decoded {
+      run.push(c);
+      i += c.len_utf8();
+    } else {
+      if run.chars().count() >= MIN_BINARY_RUN_LENGTH {
+        runs.push(std::mem::take(&mut run));
+      }
+      run.clear();
+      i += 1;
+    }
+  }
+  if run.chars().count() >= MIN_BINARY_RUN_LENGTH {
+    runs.push(run);
+  }
+  runs.join("\n")
+}
+
+fn collect_json_strings(value: &Value, strings: &mut Vec<String>) {
+  match value {
+    Value::String(s) => strings.push(s.clone()),
+    Value::Array(values) => values.iter().for_each(|v| collect_json_strings(v, strings)),
+    Value::Object(map) => map.values().for_each(|v| collect_json_strings(v, strings)),
+    _ => {}
+  }
+}
+
+/// Clean up text produced by game script extractors: drop null bytes and byte order marks, and
+/// trim leading/trailing whitespace of every line.
+pub fn preprocess_script(game_script: &str) -> String {
+  game_script
+    .replace(['\0', '\u{FEFF}'], "")
+    .lines()
+    .map(str::trim)
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+/// Sorted, de-duplicated Chinese characters and punctuation marks found in a game script.
+pub fn get_unique_chinese_chars(game_script: &str) -> Vec<char> {
+  let no_whitespace_chinese_script: String = game_script
+    .chars()
+    .filter(|c| !c.is_whitespace() && is_supported_char(*c))
+    .collect();
+  let unique_chars = no_whitespace_chinese_script.chars().collect::<HashSet<_>>();
+
+  let mut sorted_chars = unique_chars.iter().copied().collect::<Vec<_>>();
+  sorted_chars.sort_unstable();
+
+  sorted_chars
+}
+
+/// How many times each Chinese character or punctuation mark appears in a game script.
+pub fn char_frequencies(game_script: &str) -> HashMap<char, usize> {
+  let mut frequencies = HashMap::new();
+  for c in game_script.chars().filter(|c| is_supported_char(*c)) {
+    *frequencies.entry(c).or_insert(0) += 1;
+  }
+  frequencies
+}
+
+/// Sort by descending frequency, ties by ascending codepoint.
+pub fn sort_by_frequency(chars: &mut [char], frequencies: &HashMap<char, usize>) {
+  chars.sort_unstable_by_key(|c| {
+    (
+      std::cmp::Reverse(frequencies.get(c).copied().unwrap_or(0)),
+      *c,
+    )
+  });
+}
+
+/// Double-byte code of `c` in the legacy encoding of `order`, `None` when it cannot be encoded.
+/// GB2312 is the GBK range of rows A1-F7 (区 1-87) without the unassigned rows AA-AF.
+pub fn legacy_code(c: char, order: SortOrder) -> Option<u16> {
+  let encoding = match order {
+    SortOrder::Gb2312 => encoding_rs::GBK,
+    SortOrder::Big5 => encoding_rs::BIG5,
+    SortOrder::Codepoint | SortOrder::Frequency => return None,
+  };
+  let mut buf = [0; 4];
+  let (bytes, _, had_errors) = encoding.encode(c.encode_utf8(&mut buf));
+  let [lead, trail] = *bytes else {
+    return None;
+  };
+  if had_errors
+    || order == SortOrder::Gb2312
+      && !((0xa1..=0xf7).contains(&lead)
+        && !(0xaa..=0xaf).contains(&lead)
+        && (0xa1..=0xfe).contains(&trail))
+  {
+    return None;
+  }
+  Some(u16::from_be_bytes([lead, trail]))
+}
+
+/// Sort by the legacy code of `order`; characters outside the encoding follow by codepoint.
+/// Returns how many characters fell outside.
+pub fn sort_by_legacy_code(chars: &mut [char], order: SortOrder) -> usize {
+  chars.sort_unstable_by_key(|c| (legacy_code(*c, order).unwrap_or(u16::MAX), *c));
+  chars
+    .iter()
+    .filter(|c| legacy_code(**c, order).is_none())
+    .count()
+}
+
+/// `character,codepoint,frequency` CSV of `chars`, most frequent first.
+pub fn frequency_report(chars: &[char], frequencies: &HashMap<char, usize>) -> String {
+  let mut sorted = chars.to_vec();
+  sort_by_frequency(&mut sorted, frequencies);
+  let mut csv = String::from("character,codepoint,frequency\n");
+  for c in sorted {
+    csv.push_str(&format!(
+      "{},U+{:04X},{}\n",
+      c,
+      c as u32,
+      frequencies.get(&c).copied().unwrap_or(0)
+    ));
+  }
+  csv
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn binary_scan_keeps_runs_of_two_or_more() {
+    let mut bytes = b"\x00\x01label ".to_vec();
+    bytes.extend_from_slice("你好".as_bytes());
+    bytes.extend_from_slice(b"\x80\xff");
+    bytes.extend_from_slice("中".as_bytes());
+    bytes.push(0);
+    assert_eq!(extract_binary_strings(&bytes), "你好");
+  }
+
+  #[test]
+  fn compat_ideographs_are_extracted() {
+    assert_eq!(
+      get_unique_chinese_chars("\u{F900}中\u{FAFF}"),
+      vec!['中', '\u{F900}', '\u{FAFF}']
+    );
+  }
+
+  #[test]
+  fn legacy_orders_put_unencodable_chars_last() {
+    let mut chars = ['丂', '一', '阿', '啊'];
+    assert_eq!(sort_by_legacy_code(&mut chars, SortOrder::Gb2312), 1);
+    assert_eq!(chars, ['啊', '阿', '一', '丂']);
+    assert_eq!(legacy_code('啊', SortOrder::Gb2312), Some(0xb0a1));
+    let mut chars = ['\u{20000}', '中', '一'];
+    assert_eq!(sort_by_legacy_code(&mut chars, SortOrder::Big5), 1);
+    assert_eq!(chars, ['一', '中', '\u{20000}']);
+  }
+
+  #[test]
+  fn frequency_sort_breaks_ties_by_codepoint() {
+    let script = "口中口一中口人";
+    let frequencies = char_frequencies(script);
+    let mut chars = get_unique_chinese_chars(script);
+    sort_by_frequency(&mut chars, &frequencies);
+    assert_eq!(chars, vec!['口', '中', '一', '人']);
+    assert!(
+      frequency_report(&chars, &frequencies)
+        .starts_with("character,codepoint,frequency\n口,U+53E3,3\n")
+    );
+  }
+
+  #[test]
+  fn null_bytes_and_bom_are_removed() {
+    let script = "\u{FEFF}\t你\0好 \r\n\0\0  再见\u{FEFF}\0";
+    assert_eq!(preprocess_script(script), "你好\n再见");
+    assert_eq!(
+      get_unique_chinese_chars(&preprocess_script(script)),
+      vec!['你', '再', '好', '见']
+    );
+  }
+
+  #[test]
+  fn supported_chars_match_extraction() {
+    for c in ['中', '，', '。', '\u{F900}', '㐀'] {
+      assert!(is_supported_char(c));
+    }
+    for c in ['a', '!', ' ', '\u{3000}', 'あ'] {
+      assert!(!is_supported_char(c));
+      assert!(get_unique_chinese_chars(&c.to_string()).is_empty());
+    }
+  }
+
+  #[test]
+  fn json_strings_are_collected_recursively() {
+    let script = r#"[{"name": "甲", "text": ["你好", 1]}, "再见"]"#;
+    let text = read_script(script.as_bytes(), ScriptFormat::Json).unwrap();
+    assert_eq!(
+      get_unique_chinese_chars(&text),
+      vec!['你', '再', '好', '甲', '见']
+    );
+  }
+}
@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::{
+  atlas::GlyphPlacement, duplicates::SharedGlyph, punctuation::CHINESE_PUNCTUATION_MARKS,
+  warning::RenderWarning,
+};
+
+/// Per-character problems counted from the placement records.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct PlacementCounts {
+  pub punctuation: usize,
+  pub missing: usize,
+  pub substituted: usize,
+  pub clipped: usize,
+}
+
+impl PlacementCounts {
+  pub fn from_placements(placements: &[GlyphPlacement]) -> Self {
+    let mut counts = PlacementCounts::default();
+    for placement in placements {
+      counts.punctuation += CHINESE_PUNCTUATION_MARKS.contains(&placement.char) as usize;
+      counts.missing += placement.missing as usize;
+      counts.substituted += placement.substituted as usize;
+      counts.clipped += placement.clipped as usize;
+    }
+    counts
+  }
+}
+
+/// Wall-clock time of each pipeline stage, in milliseconds.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct StageTimings {
+  pub extraction_ms: f64,
+  pub rendering_ms: f64,
+  pub encoding_ms: f64,
+}
+
+impl StageTimings {
+  pub fn new(extraction: Duration, rendering: Duration, encoding: Duration) -> Self {
+    StageTimings {
+      extraction_ms: extraction.as_secs_f64() * 1000.0,
+      rendering_ms: rendering.as_secs_f64() * 1000.0,
+      encoding_ms: encoding.as_secs_f64() * 1000.0,
+    }
+  }
+}
+
+/// Summary of a run, printed at the end and exported with `--stats-json`.
+#[derive(Clone, Debug, Serialize)]
+pub struct RunStats {
+  /// Characters in the game script, including duplicates and non-Chinese text.
+  pub scanned_chars: usize,
+  /// Unique characters kept for the atlas.
+  pub unique_chars: usize,
+  #[serde(flatten)]
+  pub counts: PlacementCounts,
+  pub atlas_width: u32,
+  pub atlas_height: u32,
+  pub page_count: usize,
+  /// Total size of the written PNG files (bytes).
+  pub file_size: u64,
+  /// Mask pixels flipped by `coverage_gamma` compared to gamma 1.0.
+  pub gamma_flipped_pixels: usize,
+  /// Mapping entries without `--map-encoding` bytes, omitted without `--map-encoding`.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub unencodable_chars: Option<usize>,
+  /// Groups of characters drawn with the same glyph, omitted when there are none.
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub shared_glyphs: Vec<SharedGlyph>,
+  /// The printed rendering warnings, grouped and sorted, omitted when there are none.
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub warnings: Vec<RenderWarning>,
+  pub timings: StageTimings,
+}
+
+impl RunStats {
+  /// Compact human readable summary.
+  pub fn summary(&self) -> String {
+    let mut summary = format!(
+      "Summary: {} characters scanned, {} unique kept ({} punctuation), {} missing, {} \
+       substituted, {} clipped.\nAtlas: {}x{} px, {} page(s), {} bytes. Time: extraction {:.1} \
+       ms, rendering {:.1} ms, encoding {:.1} ms.",
+      self.scanned_chars,
+      self.unique_chars,
+      self.counts.punctuation,
+      self.counts.missing,
+      self.counts.substituted,
+      self.counts.clipped,
+      self.atlas_width,
+      self.atlas_height,
+      self.page_count,
+      self.file_size,
+      self.timings.extraction_ms,
+      self.timings.rendering_ms,
+      self.timings.encoding_ms
+    );
+    if let Some(unencodable) = self.unencodable_chars {
+      summary.push_str(&format!(
+        "\nMap encoding: {} characters could not be encoded.",
+        unencodable
+      ));
+    }
+    if !self.shared_glyphs.is_empty() {
+      summary.push_str(&format!(
+        "\nShared glyphs: {} group(s) of characters with identical tiles.",
+        self.shared_glyphs.len()
+      ));
+    }
+    summary
+  }
+}
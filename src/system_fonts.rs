@@ -0,0 +1,118 @@
+use std::{collections::BTreeSet, path::PathBuf};
+
+use anyhow::{Result, bail};
+use fontdb::{Database, Family, Query, Source};
+
+/// Close matches listed when a family is not found.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// A font face found in the OS font directories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemFont {
+  pub path: PathBuf,
+  /// Face index within a font collection (`.ttc`), 0 for single fonts.
+  pub index: u32,
+}
+
+/// Find the regular face of `family` in the OS font directories.
+pub fn find_family(family: &str) -> Result<SystemFont> {
+  let mut database = Database::new();
+  database.load_system_fonts();
+
+  let query = Query {
+    families: &[Family::Name(family)],
+    ..Query::default()
+  };
+  if let Some((source, index)) = database
+    .query(&query)
+    .and_then(|id| database.face_source(id))
+  {
+    return match source {
+      Source::File(path) | Source::SharedFile(path, _) => Ok(SystemFont { path, index }),
+      Source::Binary(_) => bail!("[Error] Font family '{}' is not backed by a file!", family),
+    };
+  }
+
+  let families = database
+    .faces()
+    .flat_map(|face| face.families.iter().map(|(name, _)| name.as_str()))
+    .collect::<BTreeSet<_>>();
+  let suggestions = close_matches(family, families);
+  if suggestions.is_empty() {
+    bail!(
+      "[Error] Font family '{}' not found in system fonts!",
+      family
+    );
+  }
+  bail!(
+    "[Error] Font family '{}' not found in system fonts! Close matches: {}",
+    family,
+    suggestions.join(", ")
+  );
+}
+
+/// Up to [`MAX_SUGGESTIONS`] of `families` resembling `family`, best first. Case is ignored, and
+/// names containing (or contained in) `family` count as the closest.
+fn close_matches<'a>(family: &str, families: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+  let wanted = family.to_lowercase();
+  let max_distance = (wanted.chars().count() / 3).max(2);
+  let mut matches = families
+    .into_iter()
+    .filter_map(|name| {
+      let candidate = name.to_lowercase();
+      let distance = if candidate.contains(&wanted) || wanted.contains(&candidate) {
+        0
+      } else {
+        edit_distance(&wanted, &candidate)
+      };
+      (distance <= max_distance).then_some((distance, name))
+    })
+    .collect::<Vec<_>>();
+  matches.sort();
+  matches
+    .into_iter()
+    .take(MAX_SUGGESTIONS)
+    .map(|(_, name)| name)
+    .collect()
+}
+
+/// Levenshtein distance in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b = b.chars().collect::<Vec<_>>();
+  let mut row = (0..=b.len()).collect::<Vec<_>>();
+  for (i, ca) in a.chars().enumerate() {
+    let mut diagonal = row[0];
+    row[0] = i + 1;
+    for (j, cb) in b.iter().enumerate() {
+      let substitution = diagonal + usize::from(ca != *cb);
+      diagonal = row[j + 1];
+      row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+    }
+  }
+  row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn close_matches_rank_containment_then_distance() {
+    let families = [
+      "Sarasa Fixed SC",
+      "Sarasa Fixed TC",
+      "Sarasa Mono SC",
+      "DejaVu Sans",
+    ];
+    assert_eq!(
+      close_matches("sarasa fixed", families),
+      ["Sarasa Fixed SC", "Sarasa Fixed TC"]
+    );
+    assert_eq!(
+      close_matches("Sarasa Fixd SC", families),
+      ["Sarasa Fixed SC", "Sarasa Fixed TC", "Sarasa Mono SC"]
+    );
+    assert!(close_matches("Noto Serif", families).is_empty());
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+  }
+}
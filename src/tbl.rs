@@ -0,0 +1,187 @@
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  fmt::Write,
+};
+
+use anyhow::{Result, bail};
+
+/// One `CODE=value` line of a `.tbl` file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TblEntry {
+  pub code: u32,
+  /// Text the code stands for; a single character for font tiles, longer for control codes.
+  pub value: String,
+  /// 1-based line number in the file.
+  pub line: usize,
+}
+
+/// A `.tbl` table mapping byte codes to characters, as used by ROM hacking tools.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tbl {
+  pub entries: Vec<TblEntry>,
+  /// Hex digits of the longest code, used when writing new codes.
+  pub digits: usize,
+}
+
+/// Parse a `--tbl-base` hex code (`8140`, `0x8140`).
+pub fn parse_hex_code(value: &str) -> Result<u32, String> {
+  let digits = value.trim_start_matches("0x").trim_start_matches("0X");
+  u32::from_str_radix(digits, 16).map_err(|_| format!("'{}' is not a hex code", value))
+}
+
+impl Tbl {
+  /// Parse `HEX=value` lines; blank lines and lines starting with `#` or `;` are skipped.
+  pub fn parse(text: &str) -> Result<Tbl> {
+    let mut entries = Vec::new();
+    let mut digits = 2;
+    for (i, line) in text.trim_start_matches('\u{feff}').lines().enumerate() {
+      if line.trim().is_empty() || line.starts_with('#') || line.starts_with(';') {
+        continue;
+      }
+      let parsed = line.split_once('=').and_then(|(code, value)| {
+        let code = code.trim();
+        (!code.is_empty() && code.len() <= 8)
+          .then(|| u32::from_str_radix(code, 16).ok())
+          .flatten()
+          .map(|number| (code.len(), number, value))
+      });
+      let Some((code_digits, code, value)) = parsed else {
+        bail!(
+          "[Error] Line {} of the .tbl is not a HEX=value entry: {}",
+          i + 1,
+          line
+        );
+      };
+      digits = digits.max(code_digits);
+      entries.push(TblEntry {
+        code,
+        value: value.to_string(),
+        line: i + 1,
+      });
+    }
+    Ok(Tbl { entries, digits })
+  }
+
+  /// Lay out the table characters at their tile index `(code - base) / stride` and put
+  /// `new_chars` that the table lacks into the free indices, lowest first.
+  pub fn layout(&self, new_chars: &[char], base: u32, stride: u32) -> TblLayout {
+    let mut tiles = BTreeMap::new();
+    let mut occupied = BTreeSet::new();
+    let mut problems = Vec::new();
+    for entry in &self.entries {
+      let index = entry
+        .code
+        .checked_sub(base)
+        .filter(|offset| offset % stride == 0)
+        .map(|offset| (offset / stride) as usize);
+      let Some(index) = index else {
+        problems.push(format!(
+          "[Warning] .tbl line {}: code {:0width$X} is not base {:X} + a multiple of stride {}, \
+           skipped.",
+          entry.line,
+          entry.code,
+          base,
+          stride,
+          width = self.digits
+        ));
+        continue;
+      };
+      let mut value_chars = entry.value.chars();
+      let (Some(c), None) = (value_chars.next(), value_chars.next()) else {
+        // Control codes and multi-character entries keep their code but get no tile.
+        occupied.insert(index);
+        continue;
+      };
+      if let Some(&(kept, kept_code)) = tiles.get(&index) {
+        problems.push(format!(
+          "[Warning] .tbl conflict: '{}' ({:0width$X}) and '{}' ({:0width$X}) both map to tile {}, \
+           keeping '{}'.",
+          kept,
+          kept_code,
+          c,
+          entry.code,
+          index,
+          kept,
+          width = self.digits
+        ));
+        continue;
+      }
+      occupied.insert(index);
+      tiles.insert(index, (c, entry.code));
+    }
+
+    let tbl_chars = tiles.values().map(|(c, _)| *c).collect::<BTreeSet<_>>();
+    let mut additions = Vec::new();
+    let mut free = (0..).filter(|index| !occupied.contains(index));
+    for c in new_chars.iter().filter(|c| !tbl_chars.contains(c)) {
+      let index = free.next().unwrap();
+      tiles.insert(index, (*c, base + index as u32 * stride));
+      additions.push((base + index as u32 * stride, *c));
+    }
+
+    let tile_count = tiles.keys().next_back().map_or(0, |last| last + 1);
+    let mut chars = vec![FILLER_CHAR; tile_count];
+    let mut codes = vec![None; tile_count];
+    for (index, (c, code)) in &tiles {
+      chars[*index] = *c;
+      codes[*index] = Some(*code);
+    }
+    TblLayout {
+      chars,
+      codes,
+      additions,
+      problems,
+    }
+  }
+
+  /// `HEX=char` lines of the characters added to the table.
+  pub fn addition_lines(&self, additions: &[(u32, char)]) -> String {
+    let mut lines = String::new();
+    for (code, c) in additions {
+      writeln!(lines, "{:0width$X}={}", code, c, width = self.digits).unwrap();
+    }
+    lines
+  }
+}
+
+/// Character rendered into the tiles the table leaves free; always a blank tile.
+const FILLER_CHAR: char = '\u{3000}';
+
+/// Tile order of a `--tbl-in` atlas.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TblLayout {
+  /// Character of every tile index, gaps holding a blank filler.
+  pub chars: Vec<char>,
+  /// Code of every tile index, `None` for the gap fillers.
+  pub codes: Vec<Option<u32>>,
+  /// (code, char) of the characters not in the table, in tile order.
+  pub additions: Vec<(u32, char)>,
+  /// `[Warning]` lines about conflicting or misaligned codes.
+  pub problems: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn table_chars_keep_their_index_and_new_chars_fill_gaps() {
+    let tbl = Tbl::parse("# comment\n8140=一\n8142=中\n8144=<END>\n8146=口\n8146=大\n").unwrap();
+    assert_eq!(tbl.digits, 4);
+    let layout = tbl.layout(&['中', '人', '日'], 0x8140, 2);
+    assert_eq!(layout.chars, ['一', '中', FILLER_CHAR, '口', '人', '日']);
+    assert_eq!(
+      layout.codes,
+      [0x8140, 0x8142, 0, 0x8146, 0x8148, 0x814a].map(|code| (code != 0).then_some(code))
+    );
+    assert_eq!(layout.additions, [(0x8148, '人'), (0x814a, '日')]);
+    assert_eq!(layout.problems.len(), 1);
+    assert_eq!(tbl.addition_lines(&layout.additions), "8148=人\n814A=日\n");
+  }
+
+  #[test]
+  fn malformed_lines_are_rejected() {
+    assert!(Tbl::parse("8140=一\nnot an entry\n").is_err());
+    assert_eq!(parse_hex_code("0x8140"), Ok(0x8140));
+  }
+}
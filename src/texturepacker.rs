@@ -0,0 +1,235 @@
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{atlas::GlyphPlacement, html::escape_html, mapping::codepoint_label};
+
+/// Frame names of the `--texturepacker` export.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum FrameName {
+  /// `"中"`
+  Char,
+  /// `"U+4E2D"`
+  #[default]
+  Codepoint,
+}
+
+#[derive(Serialize)]
+pub struct Rect {
+  pub x: u32,
+  pub y: u32,
+  pub w: u32,
+  pub h: u32,
+}
+
+#[derive(Serialize)]
+pub struct Size {
+  pub w: u32,
+  pub h: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Frame {
+  pub frame: Rect,
+  pub rotated: bool,
+  pub trimmed: bool,
+  pub sprite_source_size: Rect,
+  pub source_size: Size,
+}
+
+#[derive(Serialize)]
+pub struct Meta {
+  pub app: &'static str,
+  pub version: &'static str,
+  pub image: String,
+  pub format: &'static str,
+  pub size: Size,
+  pub scale: &'static str,
+}
+
+/// TexturePacker JSON (hash) sheet of one atlas page.
+#[derive(Serialize)]
+pub struct TexturePackerSheet {
+  pub frames: BTreeMap<String, Frame>,
+  pub meta: Meta,
+}
+
+impl TexturePackerSheet {
+  /// Sheet of the `placements` of one page. Grid cells are untrimmed frames; tightly packed
+  /// glyphs are trimmed to their ink rect within the cell. Nothing is rotated. Color class
+  /// tiles are named `<name>:<class>`.
+  pub fn new(
+    image: &Path,
+    image_size: (u32, u32),
+    cell_size: u32,
+    placements: &[&GlyphPlacement],
+    name: FrameName,
+  ) -> Self {
+    let frames = placements
+      .iter()
+      .map(|placement| {
+        let char_name = match name {
+          FrameName::Char => placement.char.to_string(),
+          FrameName::Codepoint => codepoint_label(placement.char),
+        };
+        let frame_name = match &placement.class {
+          Some(class) => format!("{}:{}", char_name, class),
+          None => char_name,
+        };
+        let rect = placement.atlas_rect(cell_size);
+        let (offset_x, offset_y) = match (placement.packed, placement.ink_bbox) {
+          (Some(_), Some(ink)) => (ink.x, ink.y),
+          _ => (0, 0),
+        };
+        let frame = Frame {
+          frame: Rect {
+            x: rect.x,
+            y: rect.y,
+            w: rect.width,
+            h: rect.height,
+          },
+          rotated: false,
+          trimmed: placement.packed.is_some(),
+          sprite_source_size: Rect {
+            x: offset_x,
+            y: offset_y,
+            w: rect.width,
+            h: rect.height,
+          },
+          source_size: Size {
+            w: cell_size,
+            h: cell_size,
+          },
+        };
+        (frame_name, frame)
+      })
+      .collect();
+    TexturePackerSheet {
+      frames,
+      meta: Meta {
+        app: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        image: image.to_string_lossy().replace('\\', "/"),
+        format: "RGB888",
+        size: Size {
+          w: image_size.0,
+          h: image_size.1,
+        },
+        scale: "1",
+      },
+    }
+  }
+}
+
+/// TexturePacker XML (`<TextureAtlas>`, as read by Unity and Starling importers) of one atlas
+/// page. Sprites are named `U+XXXX`, or `U+XXXX:<class>` for color class tiles; tightly packed
+/// glyphs carry their position within the cell as negative `frameX`/`frameY`.
+pub fn sprite_sheet_xml(
+  image: &Path,
+  image_size: (u32, u32),
+  cell_size: u32,
+  placements: &[&GlyphPlacement],
+) -> String {
+  let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+  writeln!(
+    xml,
+    "<TextureAtlas imagePath=\"{}\" width=\"{}\" height=\"{}\">",
+    escape_html(&image.to_string_lossy().replace('\\', "/")),
+    image_size.0,
+    image_size.1
+  )
+  .unwrap();
+  for placement in placements {
+    let name = match &placement.class {
+      Some(class) => format!("{}:{}", codepoint_label(placement.char), class),
+      None => codepoint_label(placement.char),
+    };
+    let rect = placement.atlas_rect(cell_size);
+    write!(
+      xml,
+      " <SubTexture name=\"{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
+      escape_html(&name),
+      rect.x,
+      rect.y,
+      rect.width,
+      rect.height
+    )
+    .unwrap();
+    if let (Some(_), Some(ink)) = (placement.packed, placement.ink_bbox) {
+      write!(
+        xml,
+        " frameX=\"-{}\" frameY=\"-{}\" frameWidth=\"{}\" frameHeight=\"{}\"",
+        ink.x, ink.y, cell_size, cell_size
+      )
+      .unwrap();
+    }
+    xml.push_str("/>\n");
+  }
+  xml.push_str("</TextureAtlas>\n");
+  xml
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn frames_are_named_by_codepoint_or_char() {
+    let plain = GlyphPlacement {
+      char: '中',
+      cell_x: 10,
+      ..Default::default()
+    };
+    let classed = GlyphPlacement {
+      char: '中',
+      class: Some("names".to_string()),
+      ..Default::default()
+    };
+    let placements = [&plain, &classed];
+    let sheet = TexturePackerSheet::new(
+      Path::new("font.png"),
+      (20, 10),
+      10,
+      &placements,
+      FrameName::Codepoint,
+    );
+    assert_eq!(
+      sheet.frames.keys().collect::<Vec<_>>(),
+      ["U+4E2D", "U+4E2D:names"]
+    );
+    assert_eq!(sheet.frames["U+4E2D"].frame.x, 10);
+    assert!(!sheet.frames["U+4E2D"].trimmed);
+    let sheet = TexturePackerSheet::new(
+      Path::new("font.png"),
+      (20, 10),
+      10,
+      &placements,
+      FrameName::Char,
+    );
+    assert!(sheet.frames.contains_key("中:names"));
+  }
+
+  #[test]
+  fn sprite_sheet_xml_lists_every_tile() {
+    let plain = GlyphPlacement {
+      char: '中',
+      cell_x: 10,
+      ..Default::default()
+    };
+    let classed = GlyphPlacement {
+      char: '中',
+      class: Some("a&b".to_string()),
+      ..Default::default()
+    };
+    let xml = sprite_sheet_xml(Path::new("font.png"), (20, 10), 10, &[&plain, &classed]);
+    assert_eq!(
+      xml,
+      "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<TextureAtlas imagePath=\"font.png\" \
+       width=\"20\" height=\"10\">\n <SubTexture name=\"U+4E2D\" x=\"10\" y=\"0\" width=\"10\" \
+       height=\"10\"/>\n <SubTexture name=\"U+4E2D:a&amp;b\" x=\"0\" y=\"0\" width=\"10\" \
+       height=\"10\"/>\n</TextureAtlas>\n"
+    );
+  }
+}
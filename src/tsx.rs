@@ -0,0 +1,111 @@
+use std::{
+  fmt::Write,
+  path::{Component, Path, PathBuf},
+};
+
+use crate::{atlas::GlyphPlacement, html::escape_html, mapping::codepoint_label};
+
+/// Path of `target` relative to the directory `base`, as written into an XML `source`
+/// attribute. Falls back to `target` when the two share no common root.
+pub fn relative_path(base: &Path, target: &Path) -> PathBuf {
+  let (Ok(base), Ok(target)) = (std::path::absolute(base), std::path::absolute(target)) else {
+    return target.to_path_buf();
+  };
+  let base = base.components().collect::<Vec<_>>();
+  let target = target.components().collect::<Vec<_>>();
+  let common = base.iter().zip(&target).take_while(|(a, b)| a == b).count();
+  if common == 0 || matches!(target.get(common - 1), Some(Component::Prefix(_))) {
+    return target.iter().collect();
+  }
+  let mut relative = PathBuf::new();
+  for _ in common..base.len() {
+    relative.push("..");
+  }
+  relative.extend(&target[common..]);
+  relative
+}
+
+/// Tiled tileset (`.tsx`) of one grid atlas page. `image_source` is written as given, so pass it
+/// relative to the `.tsx` file. Every character tile carries `char` and `codepoint` properties.
+pub fn tiled_tileset(
+  name: &str,
+  image_source: &Path,
+  image_size: (u32, u32),
+  cell_size: u32,
+  placements: &[&GlyphPlacement],
+) -> String {
+  let columns = image_size.0 / cell_size;
+  let tile_count = columns * (image_size.1 / cell_size);
+  let mut tsx = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+  writeln!(
+    tsx,
+    "<tileset version=\"1.10\" name=\"{}\" tilewidth=\"{}\" tileheight=\"{}\" tilecount=\"{}\" \
+     columns=\"{}\">",
+    escape_html(name),
+    cell_size,
+    cell_size,
+    tile_count,
+    columns
+  )
+  .unwrap();
+  writeln!(
+    tsx,
+    " <image source=\"{}\" width=\"{}\" height=\"{}\"/>",
+    escape_html(&image_source.to_string_lossy().replace('\\', "/")),
+    image_size.0,
+    image_size.1
+  )
+  .unwrap();
+  let mut tiles = placements
+    .iter()
+    .map(|placement| {
+      let id = placement.cell_y / cell_size * columns + placement.cell_x / cell_size;
+      (id, *placement)
+    })
+    .collect::<Vec<_>>();
+  tiles.sort_by_key(|(id, _)| *id);
+  for (id, placement) in tiles {
+    writeln!(tsx, " <tile id=\"{}\">", id).unwrap();
+    tsx.push_str("  <properties>\n");
+    writeln!(
+      tsx,
+      "   <property name=\"char\" value=\"{}\"/>",
+      escape_html(&placement.char.to_string())
+    )
+    .unwrap();
+    writeln!(
+      tsx,
+      "   <property name=\"codepoint\" value=\"{}\"/>",
+      codepoint_label(placement.char)
+    )
+    .unwrap();
+    if let Some(class) = &placement.class {
+      writeln!(
+        tsx,
+        "   <property name=\"class\" value=\"{}\"/>",
+        escape_html(class)
+      )
+      .unwrap();
+    }
+    tsx.push_str("  </properties>\n </tile>\n");
+  }
+  tsx.push_str("</tileset>\n");
+  tsx
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn image_source_is_relative_to_the_tileset() {
+    assert_eq!(
+      relative_path(Path::new("out/tiled"), Path::new("out/font.png")),
+      PathBuf::from("../font.png")
+    );
+    assert_eq!(
+      relative_path(Path::new("out"), Path::new("out/font.png")),
+      PathBuf::from("font.png")
+    );
+  }
+}
@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+
+use image::{Rgb, RgbImage};
+
+use crate::{
+  mapping::{CharEntry, Mapping},
+  pack::PackMode,
+};
+
+/// Colors the `verify` subcommand tells character pixels apart with.
+pub struct VerifyColors {
+  pub char_color: Rgb<u8>,
+  /// `[color_classes]` colors of the class tiles.
+  pub class_colors: BTreeMap<String, Rgb<u8>>,
+  /// With a `char_gradient` the character color varies, so every pixel that is not in
+  /// `non_char_colors` counts as a character pixel.
+  pub gradient: bool,
+  /// Background, shadow and grid line colors.
+  pub non_char_colors: Vec<Rgb<u8>>,
+}
+
+impl VerifyColors {
+  fn is_char_pixel(&self, pixel: Rgb<u8>, class: Option<&str>) -> bool {
+    if self.gradient {
+      return !self.non_char_colors.contains(&pixel);
+    }
+    let color = class
+      .and_then(|class| self.class_colors.get(class))
+      .unwrap_or(&self.char_color);
+    pixel == *color
+  }
+}
+
+fn describe(entry: &CharEntry) -> String {
+  format!(
+    "Tile {} '{}' ({})",
+    entry.index, entry.char, entry.codepoint
+  )
+}
+
+/// Check the mapping entries of `page` against its atlas image: every rect lies within the image
+/// (and on the `cell_size` grid in grid mode), found glyphs have character pixels, missing ones
+/// (`font_index: null`) have none, and no two rects overlap. Returns one `[Warning]` line per
+/// problem.
+pub fn verify_atlas(
+  image: &RgbImage,
+  mapping: &Mapping,
+  page: usize,
+  cell_size: u32,
+  colors: &VerifyColors,
+) -> Vec<String> {
+  let mut problems = Vec::new();
+  if mapping.cell_size != cell_size {
+    problems.push(format!(
+      "[Warning] The mapping is for {}px cells, not {}px!",
+      mapping.cell_size, cell_size
+    ));
+  }
+  let entries = mapping
+    .chars
+    .iter()
+    .filter(|entry| entry.page == page)
+    .collect::<Vec<_>>();
+  for entry in &entries {
+    let inside = entry.x as u64 + entry.width as u64 <= image.width() as u64
+      && entry.y as u64 + entry.height as u64 <= image.height() as u64;
+    if !inside {
+      problems.push(format!(
+        "[Warning] {}: rect {}x{} at ({}, {}) is outside the {}x{} image!",
+        describe(entry),
+        entry.width,
+        entry.height,
+        entry.x,
+        entry.y,
+        image.width(),
+        image.height()
+      ));
+      continue;
+    }
+    if mapping.pack == PackMode::Grid
+      && (entry.x % cell_size != 0
+        || entry.y % cell_size != 0
+        || entry.width != cell_size
+        || entry.height != cell_size)
+    {
+      problems.push(format!(
+        "[Warning] {}: rect {}x{} at ({}, {}) is not a {}px grid cell!",
+        describe(entry),
+        entry.width,
+        entry.height,
+        entry.x,
+        entry.y,
+        cell_size
+      ));
+    }
+    let has_char_pixels = (entry.y..entry.y + entry.height).any(|y| {
+      (entry.x..entry.x + entry.width)
+        .any(|x| colors.is_char_pixel(*image.get_pixel(x, y), entry.class.as_deref()))
+    });
+    match (entry.font_index.is_some(), has_char_pixels) {
+      (true, false) => problems.push(format!(
+        "[Warning] {}: the tile has no character pixels!",
+        describe(entry)
+      )),
+      (false, true) => problems.push(format!(
+        "[Warning] {}: marked missing but the tile has character pixels!",
+        describe(entry)
+      )),
+      _ => {}
+    }
+  }
+
+  let mut rects = entries
+    .iter()
+    .filter(|entry| entry.width > 0 && entry.height > 0)
+    .collect::<Vec<_>>();
+  rects.sort_by_key(|entry| entry.x);
+  for (i, a) in rects.iter().enumerate() {
+    for b in rects[i + 1..]
+      .iter()
+      .take_while(|b| b.x < a.x.saturating_add(a.width))
+    {
+      if b.y < a.y.saturating_add(a.height) && a.y < b.y.saturating_add(b.height) {
+        problems.push(format!(
+          "[Warning] {} overlaps tile {} '{}' ({})!",
+          describe(a),
+          b.index,
+          b.char,
+          b.codepoint
+        ));
+      }
+    }
+  }
+  problems
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::atlas::GlyphPlacement;
+
+  #[test]
+  fn empty_overlapping_and_outside_tiles_are_reported() {
+    let (bg, ink) = (Rgb([0, 0, 0]), Rgb([255, 255, 255]));
+    let mut image = RgbImage::from_pixel(20, 10, bg);
+    image.put_pixel(3, 3, ink);
+    let placement = |char, cell_x, font_index| GlyphPlacement {
+      char,
+      cell_x,
+      font_index,
+      ..Default::default()
+    };
+    let placements = [
+      placement('一', 0, Some(0)),
+      placement('中', 10, Some(0)),
+      placement('口', 10, None),
+      placement('大', 20, Some(0)),
+    ];
+    let mapping = Mapping::from_placements(
+      "font.png".to_string(),
+      10,
+      2,
+      PackMode::Grid,
+      4,
+      &placements,
+    );
+    let colors = VerifyColors {
+      char_color: ink,
+      class_colors: BTreeMap::new(),
+      gradient: false,
+      non_char_colors: vec![bg],
+    };
+    let problems = verify_atlas(&image, &mapping, 0, 10, &colors);
+    assert_eq!(problems.len(), 3, "{:?}", problems);
+    assert!(problems[0].contains("'中'") && problems[0].contains("no character pixels"));
+    assert!(problems[1].contains("outside"));
+    assert!(problems[2].contains("overlaps"));
+  }
+}
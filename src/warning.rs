@@ -0,0 +1,291 @@
+use std::fmt;
+
+use serde::Serialize;
+
+/// Collects the problems found while rendering, for the caller to report.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenderContext {
+  pub warnings: Vec<RenderWarning>,
+}
+
+impl RenderContext {
+  pub fn warn(&mut self, warning: RenderWarning) {
+    self.warnings.push(warning);
+  }
+
+  /// The warnings grouped by kind (missing glyphs, clipped glyphs, substitutions, the other
+  /// glyph problems, then the ones about the script), sorted by codepoint within each kind, with
+  /// one warning per kind and subject. A character warned about for several tiles keeps its
+  /// first tile.
+  pub fn sorted_warnings(&self) -> Vec<RenderWarning> {
+    let mut warnings = self.warnings.clone();
+    warnings.sort_by(|a, b| a.key().cmp(&b.key()));
+    warnings.dedup_by(|a, b| a.key() == b.key());
+    warnings
+  }
+}
+
+/// A non-fatal rendering problem. `Display` gives the CLI's `[Warning] ...` line, `Serialize`
+/// the `--stats-json` entry.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RenderWarning {
+  /// No font in the fallback chain has a glyph for the character; `index` is its tile.
+  GlyphNotFound {
+    char: char,
+    codepoint: u32,
+    index: usize,
+  },
+  /// The glyph outline is larger than the glyph area of the cell and was cropped.
+  GlyphOverflow {
+    char: char,
+    codepoint: u32,
+    cell_dimension: u32,
+    glyph_dimension: u32,
+  },
+  /// The glyph renders pixels in the color-key color, so they would turn transparent.
+  ColorKeyCollision { char: char, codepoint: u32 },
+  /// The font has a glyph for the character but nothing was drawn in its cell.
+  EmptyGlyph { char: char, codepoint: u32 },
+  /// Only shadow pixels were drawn in the cell, e.g. the shadow covered a 1px glyph.
+  ShadowOnlyGlyph { char: char, codepoint: u32 },
+  /// The bold strike is 1px too wide for the glyph area and was clipped.
+  BoldClipped { char: char, codepoint: u32 },
+  /// The slanted glyph leans past the glyph area and was clipped.
+  SlantClipped { char: char, codepoint: u32 },
+  /// Part of the `--char-shadow-width` shadow fell outside the cell and was clipped.
+  ShadowClipped { char: char, codepoint: u32 },
+  /// No font has the character, so its tile holds the glyph of the configured fallback.
+  Substituted {
+    char: char,
+    codepoint: u32,
+    fallback: char,
+  },
+  /// The characters are drawn with one glyph of font `font_index`, so their tiles are identical.
+  /// `codepoint` is the one of the first character.
+  SharedGlyph {
+    chars: String,
+    codepoint: u32,
+    font_index: usize,
+    glyph_id: u16,
+  },
+  /// The CJK Compatibility Ideographs of the script were left out of the atlas.
+  CompatSkipped { chars: String },
+  /// None of the characters of the `--class-chars` file are in the script.
+  ColorClassSkipped { class: String, file: String },
+}
+
+impl RenderWarning {
+  /// Position of the kind in the printed report.
+  fn rank(&self) -> u8 {
+    match self {
+      RenderWarning::GlyphNotFound { .. } => 0,
+      RenderWarning::GlyphOverflow { .. } => 1,
+      RenderWarning::BoldClipped { .. } => 2,
+      RenderWarning::SlantClipped { .. } => 3,
+      RenderWarning::ShadowClipped { .. } => 4,
+      RenderWarning::Substituted { .. } => 5,
+      RenderWarning::ColorKeyCollision { .. } => 6,
+      RenderWarning::EmptyGlyph { .. } => 7,
+      RenderWarning::ShadowOnlyGlyph { .. } => 8,
+      RenderWarning::SharedGlyph { .. } => 9,
+      RenderWarning::CompatSkipped { .. } => 10,
+      RenderWarning::ColorClassSkipped { .. } => 11,
+    }
+  }
+
+  /// Sort and deduplication key: kind, character, then the class of a skipped color class.
+  fn key(&self) -> (u8, Option<u32>, &str) {
+    let class = match self {
+      RenderWarning::ColorClassSkipped { class, .. } => class.as_str(),
+      _ => "",
+    };
+    (self.rank(), self.codepoint(), class)
+  }
+
+  /// The character the warning is about, `None` for the warnings about the whole script.
+  pub fn codepoint(&self) -> Option<u32> {
+    match self {
+      RenderWarning::GlyphNotFound { codepoint, .. }
+      | RenderWarning::GlyphOverflow { codepoint, .. }
+      | RenderWarning::ColorKeyCollision { codepoint, .. }
+      | RenderWarning::EmptyGlyph { codepoint, .. }
+      | RenderWarning::ShadowOnlyGlyph { codepoint, .. }
+      | RenderWarning::BoldClipped { codepoint, .. }
+      | RenderWarning::SlantClipped { codepoint, .. }
+      | RenderWarning::ShadowClipped { codepoint, .. }
+      | RenderWarning::Substituted { codepoint, .. }
+      | RenderWarning::SharedGlyph { codepoint, .. } => Some(*codepoint),
+      RenderWarning::CompatSkipped { .. } | RenderWarning::ColorClassSkipped { .. } => None,
+    }
+  }
+}
+
+impl fmt::Display for RenderWarning {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      RenderWarning::GlyphNotFound {
+        char,
+        codepoint,
+        index,
+      } => write!(
+        f,
+        "[Warning] The glyph for '{}' (U+{:04X}) is not found! (index: {})",
+        char, codepoint, index
+      ),
+      RenderWarning::GlyphOverflow {
+        char,
+        codepoint,
+        cell_dimension,
+        glyph_dimension,
+      } => write!(
+        f,
+        "[Warning] The glyph for '{}' (U+{:04X}) is {}px, larger than the {}px glyph area!",
+        char, codepoint, glyph_dimension, cell_dimension
+      ),
+      RenderWarning::ColorKeyCollision { char, codepoint } => write!(
+        f,
+        "[Warning] The glyph for '{}' (U+{:04X}) contains pixels in the color-key color!",
+        char, codepoint
+      ),
+      RenderWarning::EmptyGlyph { char, codepoint } => write!(
+        f,
+        "[Warning] The glyph for '{}' (U+{:04X}) exists but rendered no pixels!",
+        char, codepoint
+      ),
+      RenderWarning::ShadowOnlyGlyph { char, codepoint } => write!(
+        f,
+        "[Warning] The glyph for '{}' (U+{:04X}) rendered only shadow pixels!",
+        char, codepoint
+      ),
+      RenderWarning::BoldClipped { char, codepoint } => write!(
+        f,
+        "[Warning] The bold glyph for '{}' (U+{:04X}) no longer fits the glyph area and was \
+         clipped!",
+        char, codepoint
+      ),
+      RenderWarning::SlantClipped { char, codepoint } => write!(
+        f,
+        "[Warning] The slanted glyph for '{}' (U+{:04X}) no longer fits the glyph area and was \
+         clipped!",
+        char, codepoint
+      ),
+      RenderWarning::ShadowClipped { char, codepoint } => write!(
+        f,
+        "[Warning] The wide shadow of '{}' (U+{:04X}) no longer fits the cell and was clipped!",
+        char, codepoint
+      ),
+      RenderWarning::Substituted {
+        char,
+        codepoint,
+        fallback,
+      } => write!(
+        f,
+        "[Warning] '{}' (U+{:04X}) is drawn with the fallback '{}' (U+{:04X})!",
+        char, codepoint, fallback, *fallback as u32
+      ),
+      RenderWarning::SharedGlyph {
+        chars,
+        font_index,
+        glyph_id,
+        ..
+      } => {
+        let labels = chars
+          .chars()
+          .map(|c| format!("'{}' (U+{:04X})", c, c as u32))
+          .collect::<Vec<_>>();
+        write!(
+          f,
+          "[Warning] {} share glyph #{} of font #{}, so their tiles are identical.",
+          labels.join(" and "),
+          glyph_id,
+          font_index
+        )
+      }
+      RenderWarning::CompatSkipped { chars } => write!(
+        f,
+        "[Warning] Skipped {} CJK Compatibility Ideographs ({}), use --include-compat to render \
+         them.",
+        chars.chars().count(),
+        chars
+      ),
+      RenderWarning::ColorClassSkipped { class, file } => write!(
+        f,
+        "[Warning] No characters of {} are used in the game script, skipping color class '{}'.",
+        file, class
+      ),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn warnings_are_grouped_sorted_and_deduplicated() {
+    let mut context = RenderContext::default();
+    for (c, index) in [('中', 3), ('一', 1), ('中', 7)] {
+      context.warn(RenderWarning::GlyphOverflow {
+        char: c,
+        codepoint: c as u32,
+        cell_dimension: 9,
+        glyph_dimension: 10,
+      });
+      context.warn(RenderWarning::GlyphNotFound {
+        char: c,
+        codepoint: c as u32,
+        index,
+      });
+    }
+    let lines = context
+      .sorted_warnings()
+      .iter()
+      .map(ToString::to_string)
+      .collect::<Vec<_>>();
+    assert_eq!(
+      lines,
+      [
+        "[Warning] The glyph for '一' (U+4E00) is not found! (index: 1)",
+        "[Warning] The glyph for '中' (U+4E2D) is not found! (index: 3)",
+        "[Warning] The glyph for '一' (U+4E00) is 10px, larger than the 9px glyph area!",
+        "[Warning] The glyph for '中' (U+4E2D) is 10px, larger than the 9px glyph area!",
+      ]
+    );
+  }
+
+  #[test]
+  fn script_warnings_come_last_and_keep_one_per_class() {
+    let mut context = RenderContext::default();
+    for class in ["red", "blue", "red"] {
+      context.warn(RenderWarning::ColorClassSkipped {
+        class: class.to_string(),
+        file: format!("{}.txt", class),
+      });
+    }
+    context.warn(RenderWarning::CompatSkipped {
+      chars: "\u{F900}\u{F901}".to_string(),
+    });
+    context.warn(RenderWarning::EmptyGlyph {
+      char: '口',
+      codepoint: '口' as u32,
+    });
+    let lines = context
+      .sorted_warnings()
+      .iter()
+      .map(ToString::to_string)
+      .collect::<Vec<_>>();
+    assert_eq!(
+      lines,
+      [
+        "[Warning] The glyph for '口' (U+53E3) exists but rendered no pixels!",
+        "[Warning] Skipped 2 CJK Compatibility Ideographs (\u{F900}\u{F901}), use --include-compat \
+         to render them.",
+        "[Warning] No characters of blue.txt are used in the game script, skipping color class \
+         'blue'.",
+        "[Warning] No characters of red.txt are used in the game script, skipping color class \
+         'red'.",
+      ]
+    );
+  }
+}
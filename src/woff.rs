@@ -0,0 +1,248 @@
+use std::io::Read;
+
+use anyhow::{Result, anyhow};
+use flate2::read::ZlibDecoder;
+
+const WOFF_SIGNATURE: &[u8; 4] = b"wOFF";
+const WOFF2_SIGNATURE: &[u8; 4] = b"wOF2";
+const WOFF_HEADER_SIZE: usize = 44;
+const WOFF_TABLE_ENTRY_SIZE: usize = 20;
+const SFNT_HEADER_SIZE: usize = 12;
+const SFNT_TABLE_RECORD_SIZE: usize = 16;
+/// Largest `totalSfntSize` accepted from a WOFF header, well above any real CJK font.
+const MAX_SFNT_SIZE: usize = 256 << 20;
+
+/// Font file container, detected by its magic bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FontContainer {
+  /// Plain TrueType/OpenType (or a collection), parsed as is.
+  Sfnt,
+  Woff,
+  Woff2,
+}
+
+impl FontContainer {
+  pub fn detect(bytes: &[u8]) -> FontContainer {
+    match bytes.get(..4) {
+      Some(signature) if signature == WOFF_SIGNATURE => FontContainer::Woff,
+      Some(signature) if signature == WOFF2_SIGNATURE => FontContainer::Woff2,
+      _ => FontContainer::Sfnt,
+    }
+  }
+}
+
+/// Unwrap a WOFF or WOFF2 font into SFNT bytes; other fonts are returned unchanged.
+pub fn to_sfnt(bytes: Vec<u8>) -> Result<Vec<u8>> {
+  match FontContainer::detect(&bytes) {
+    FontContainer::Sfnt => Ok(bytes),
+    FontContainer::Woff => decode_woff(&bytes),
+    FontContainer::Woff2 => decode_woff2(&bytes),
+  }
+}
+
+#[cfg(feature = "woff2")]
+fn decode_woff2(bytes: &[u8]) -> Result<Vec<u8>> {
+  woff2_patched::convert_woff2_to_ttf(&mut &bytes[..])
+    .map_err(|e| anyhow!("[Error] Could not decode the WOFF2 font: {}", e))
+}
+
+#[cfg(not(feature = "woff2"))]
+fn decode_woff2(_bytes: &[u8]) -> Result<Vec<u8>> {
+  Err(anyhow!(
+    "[Error] WOFF2 fonts need the `woff2` feature, or convert the font to TTF/OTF first!"
+  ))
+}
+
+pub(crate) fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+  Some(u16::from_be_bytes(
+    bytes.get(offset..offset + 2)?.try_into().ok()?,
+  ))
+}
+
+pub(crate) fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+  Some(u32::from_be_bytes(
+    bytes.get(offset..offset + 4)?.try_into().ok()?,
+  ))
+}
+
+/// Rebuild the SFNT from a WOFF 1.0 font, inflating the zlib-compressed tables.
+fn decode_woff(bytes: &[u8]) -> Result<Vec<u8>> {
+  let corrupt = |what: &str| anyhow!("[Error] Corrupt WOFF font: {}!", what);
+  if bytes.len() < WOFF_HEADER_SIZE {
+    return Err(corrupt("truncated header"));
+  }
+  let flavor = read_u32(bytes, 4).unwrap();
+  let num_tables = read_u16(bytes, 12).unwrap();
+  let total_sfnt_size = read_u32(bytes, 16).unwrap() as usize;
+  if total_sfnt_size > MAX_SFNT_SIZE {
+    return Err(corrupt(&format!(
+      "the declared font size of {} bytes is too large",
+      total_sfnt_size
+    )));
+  }
+
+  // Offset table.
+  let entry_selector = num_tables.max(1).ilog2();
+  let search_range = (1u32 << entry_selector) * 16;
+  let range_shift = (num_tables as u32 * 16).saturating_sub(search_range);
+  let mut sfnt = Vec::new();
+  sfnt.extend_from_slice(&flavor.to_be_bytes());
+  sfnt.extend_from_slice(&num_tables.to_be_bytes());
+  sfnt.extend_from_slice(&(search_range as u16).to_be_bytes());
+  sfnt.extend_from_slice(&(entry_selector as u16).to_be_bytes());
+  sfnt.extend_from_slice(&(range_shift as u16).to_be_bytes());
+
+  let mut records = Vec::with_capacity(num_tables as usize * SFNT_TABLE_RECORD_SIZE);
+  let mut data = Vec::new();
+  let data_start = SFNT_HEADER_SIZE + num_tables as usize * SFNT_TABLE_RECORD_SIZE;
+  for table in 0..num_tables as usize {
+    let entry = WOFF_HEADER_SIZE + table * WOFF_TABLE_ENTRY_SIZE;
+    let (Some(offset), Some(comp_length), Some(orig_length), Some(checksum)) = (
+      read_u32(bytes, entry + 4),
+      read_u32(bytes, entry + 8),
+      read_u32(bytes, entry + 12),
+      read_u32(bytes, entry + 16),
+    ) else {
+      return Err(corrupt("truncated table directory"));
+    };
+    let tag = &bytes[entry..entry + 4];
+    let Some(stored) = bytes.get(offset as usize..offset as usize + comp_length as usize) else {
+      return Err(corrupt(&format!(
+        "table '{}' is out of bounds",
+        String::from_utf8_lossy(tag)
+      )));
+    };
+    // The table lengths come from the file, so they are checked against the declared font size
+    // before anything is allocated for them.
+    if orig_length as usize > total_sfnt_size.saturating_sub(data_start + data.len()) {
+      return Err(corrupt(&format!(
+        "table '{}' is larger than the declared font size",
+        String::from_utf8_lossy(tag)
+      )));
+    }
+    let table_data = if comp_length < orig_length {
+      let mut inflated = Vec::with_capacity(orig_length as usize);
+      // One byte past the length is enough to tell an oversized stream by the check below.
+      ZlibDecoder::new(stored)
+        .take(orig_length as u64 + 1)
+        .read_to_end(&mut inflated)
+        .map_err(|_| {
+          corrupt(&format!(
+            "table '{}' could not be decompressed",
+            String::from_utf8_lossy(tag)
+          ))
+        })?;
+      inflated
+    } else {
+      stored.to_vec()
+    };
+    if table_data.len() != orig_length as usize {
+      return Err(corrupt(&format!(
+        "table '{}' has the wrong length",
+        String::from_utf8_lossy(tag)
+      )));
+    }
+
+    records.extend_from_slice(tag);
+    records.extend_from_slice(&checksum.to_be_bytes());
+    records.extend_from_slice(&((data_start + data.len()) as u32).to_be_bytes());
+    records.extend_from_slice(&orig_length.to_be_bytes());
+    data.extend_from_slice(&table_data);
+    // Tables start on 4-byte boundaries.
+    data.resize(data.len().next_multiple_of(4), 0);
+  }
+
+  sfnt.extend_from_slice(&records);
+  sfnt.extend_from_slice(&data);
+  Ok(sfnt)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Write;
+
+  use ab_glyph::{Font, FontVec};
+  use flate2::{Compression, write::ZlibEncoder};
+
+  use super::*;
+
+  /// Wrap an SFNT font into WOFF 1.0, compressing every table.
+  fn encode_woff(sfnt: &[u8]) -> Vec<u8> {
+    let num_tables = read_u16(sfnt, 4).unwrap() as usize;
+    let mut directory = Vec::new();
+    let mut data = Vec::new();
+    let data_start = WOFF_HEADER_SIZE + num_tables * WOFF_TABLE_ENTRY_SIZE;
+    for table in 0..num_tables {
+      let record = SFNT_HEADER_SIZE + table * SFNT_TABLE_RECORD_SIZE;
+      let offset = read_u32(sfnt, record + 8).unwrap() as usize;
+      let length = read_u32(sfnt, record + 12).unwrap() as usize;
+      let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+      encoder.write_all(&sfnt[offset..offset + length]).unwrap();
+      let compressed = encoder.finish().unwrap();
+      let stored = if compressed.len() < length {
+        compressed
+      } else {
+        sfnt[offset..offset + length].to_vec()
+      };
+      directory.extend_from_slice(&sfnt[record..record + 4]);
+      directory.extend_from_slice(&((data_start + data.len()) as u32).to_be_bytes());
+      directory.extend_from_slice(&(stored.len() as u32).to_be_bytes());
+      directory.extend_from_slice(&(length as u32).to_be_bytes());
+      directory.extend_from_slice(&sfnt[record + 4..record + 8]);
+      data.extend_from_slice(&stored);
+      data.resize(data.len().next_multiple_of(4), 0);
+    }
+
+    let mut woff = Vec::new();
+    woff.extend_from_slice(WOFF_SIGNATURE);
+    woff.extend_from_slice(&sfnt[0..4]);
+    woff.extend_from_slice(&((data_start + data.len()) as u32).to_be_bytes());
+    woff.extend_from_slice(&(num_tables as u16).to_be_bytes());
+    woff.resize(16, 0);
+    woff.extend_from_slice(&(sfnt.len() as u32).to_be_bytes());
+    woff.resize(WOFF_HEADER_SIZE, 0);
+    woff.extend_from_slice(&directory);
+    woff.extend_from_slice(&data);
+    woff
+  }
+
+  #[test]
+  fn woff_round_trips_to_sfnt() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/test_font.ttf");
+    let ttf = std::fs::read(path).unwrap();
+    let woff = encode_woff(&ttf);
+    assert_eq!(FontContainer::detect(&woff), FontContainer::Woff);
+    assert!(woff.len() < ttf.len());
+
+    let font = FontVec::try_from_vec(to_sfnt(woff.clone()).unwrap()).unwrap();
+    let reference = FontVec::try_from_vec(ttf.clone()).unwrap();
+    assert_eq!(font.glyph_count(), reference.glyph_count());
+    assert_eq!(font.glyph_id('中'), reference.glyph_id('中'));
+    assert_eq!(to_sfnt(ttf.clone()).unwrap(), ttf);
+
+    let truncated = woff[..woff.len() / 2].to_vec();
+    let error = to_sfnt(truncated).unwrap_err().to_string();
+    assert!(error.starts_with("[Error] Corrupt WOFF font"), "{}", error);
+  }
+
+  #[test]
+  fn forged_woff_lengths_are_rejected() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/test_font.ttf");
+    let woff = encode_woff(&std::fs::read(path).unwrap());
+
+    let mut huge_table = woff.clone();
+    let orig_length = WOFF_HEADER_SIZE + 12;
+    huge_table[orig_length..orig_length + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+    let error = to_sfnt(huge_table).unwrap_err().to_string();
+    assert!(
+      error.ends_with("is larger than the declared font size!"),
+      "{}",
+      error
+    );
+
+    let mut huge_font = woff;
+    huge_font[16..20].copy_from_slice(&u32::MAX.to_be_bytes());
+    let error = to_sfnt(huge_font).unwrap_err().to_string();
+    assert!(error.ends_with("bytes is too large!"), "{}", error);
+  }
+}
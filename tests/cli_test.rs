@@ -0,0 +1,166 @@
+//! Exit codes of the command line tool, see the "Exit codes" section of the README.
+
+mod common;
+
+use std::{fs, path::Path, process::Command};
+
+/// Run the binary on a script containing `script` with the bundled test font.
+fn run_with_script(test_name: &str, script: &str, extra_args: &[&str]) -> Option<i32> {
+  let dir = common::scratch_dir(test_name);
+  let script_file = dir.join("script.txt");
+  fs::write(&script_file, script).unwrap();
+  run(
+    &script_file,
+    &common::fixture_path("test_font.ttf"),
+    &dir,
+    extra_args,
+  )
+}
+
+fn run(script_file: &Path, font_file: &Path, dir: &Path, extra_args: &[&str]) -> Option<i32> {
+  Command::new(env!("CARGO_BIN_EXE_chinese_bitmap_font_demo"))
+    .arg("-t")
+    .arg(script_file)
+    .arg("-f")
+    .arg(font_file)
+    .arg("-o")
+    .arg(dir.join("out.png"))
+    .args(extra_args)
+    .output()
+    .unwrap()
+    .status
+    .code()
+}
+
+#[test]
+fn success_exits_with_zero() {
+  assert_eq!(run_with_script("success", "一中口", &[]), Some(0));
+}
+
+#[test]
+fn invalid_arguments_exit_with_two() {
+  assert_eq!(run_with_script("args", "一中口", &["-s", "12"]), Some(2));
+  assert_eq!(
+    run_with_script("clap", "一中口", &["--no-such-flag"]),
+    Some(2)
+  );
+}
+
+#[test]
+fn flipping_a_stripped_atlas_is_rejected() {
+  for flip in ["--flip-x", "--flip-y"] {
+    assert_eq!(
+      run_with_script("flip_strip", "一中口龘", &[flip, "--strip-unused-cells"]),
+      Some(2)
+    );
+  }
+}
+
+#[test]
+fn missing_input_exits_with_three() {
+  let dir = common::scratch_dir("input");
+  let font = common::fixture_path("test_font.ttf");
+  assert_eq!(run(&dir.join("missing.txt"), &font, &dir, &[]), Some(3));
+}
+
+#[test]
+fn punctuation_only_script_without_marks_exits_with_three() {
+  let args = ["--include-punctuation-only"];
+  assert_eq!(run_with_script("punct", "一，", &args), Some(0));
+  assert_eq!(run_with_script("no_punct", "一中口", &args), Some(3));
+}
+
+#[test]
+fn unparsable_font_exits_with_four() {
+  let dir = common::scratch_dir("font");
+  let script_file = dir.join("script.txt");
+  fs::write(&script_file, "一中口").unwrap();
+  assert_eq!(run(&script_file, &script_file, &dir, &[]), Some(4));
+}
+
+#[test]
+fn missing_glyphs_exit_with_five_only_when_requested() {
+  assert_eq!(run_with_script("missing", "一龍", &[]), Some(0));
+  assert_eq!(
+    run_with_script("missing_fail", "一龍", &["--fail-on-missing"]),
+    Some(5)
+  );
+}
+
+#[test]
+fn overflow_exits_with_six() {
+  assert_eq!(
+    run_with_script("overflow", "一中口", &["--pad-to-count", "2"]),
+    Some(6)
+  );
+}
+
+#[test]
+fn changed_tiles_make_compare_exit_with_one() {
+  let dir = common::scratch_dir("compare");
+  let map = dir.join("map.json");
+  let map_arg = map.to_str().unwrap();
+  assert_eq!(
+    run_with_script("compare", "一中口", &["-m", map_arg]),
+    Some(0)
+  );
+  let old = dir.join("out.png");
+  let new_dir = common::scratch_dir("compare_new");
+  let shifted = ["--font-baseline-offset", "1"];
+  assert_eq!(run_with_script("compare_new", "一中口", &shifted), Some(0));
+  let compare = |new: &Path| {
+    Command::new(env!("CARGO_BIN_EXE_chinese_bitmap_font_demo"))
+      .arg("compare")
+      .arg("--old")
+      .arg(&old)
+      .arg("--new")
+      .arg(new)
+      .arg("--map")
+      .arg(&map)
+      .output()
+      .unwrap()
+      .status
+      .code()
+  };
+  assert_eq!(compare(&old), Some(0));
+  assert_eq!(compare(&new_dir.join("out.png")), Some(1));
+}
+
+#[test]
+fn shadowless_atlases_are_encoded_as_monochrome_rle() {
+  let rle_depth = |test_name: &str, extra_args: &[&str]| {
+    let rle = common::scratch_dir(test_name).join("out.rle");
+    let mut args = vec!["--encode-rle", rle.to_str().unwrap()];
+    args.extend(extra_args);
+    assert_eq!(run_with_script(test_name, "一中口", &args), Some(0));
+    fs::read(&rle).unwrap()[12]
+  };
+  assert_eq!(rle_depth("rle_rgb", &[]), 24);
+  assert_eq!(rle_depth("rle_mono", &["--render-style", "hollow"]), 1);
+}
+
+#[test]
+fn zero_cell_size_is_rejected_by_the_subcommands() {
+  let run_subcommand = |args: &[&str]| {
+    Command::new(env!("CARGO_BIN_EXE_chinese_bitmap_font_demo"))
+      .args(args)
+      .output()
+      .unwrap()
+      .status
+      .code()
+  };
+  let verify = [
+    "verify", "--image", "a.png", "--map", "a.json", "--size", "0",
+  ];
+  assert_eq!(run_subcommand(&verify), Some(2));
+  let import = [
+    "import",
+    "--image",
+    "a.png",
+    "--charset",
+    "a.txt",
+    "--size",
+    "0",
+  ];
+  assert_eq!(run_subcommand(&import), Some(2));
+}
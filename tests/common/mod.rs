@@ -0,0 +1,123 @@
+//! Shared helpers for the integration tests.
+
+#![allow(dead_code)]
+
+pub mod pixel_font;
+
+use std::{fs, path::PathBuf};
+
+use pixel_font::{PixelGlyph, build_pixel_font};
+
+/// Characters covered by the bundled test font.
+pub const TEST_CHARS: &str = "一丿中人口大日目矗，";
+
+pub fn fixture_path(name: &str) -> PathBuf {
+  PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+    .join("tests")
+    .join("fixtures")
+    .join(name)
+}
+
+/// Fresh scratch directory for one test's input and output files.
+pub fn scratch_dir(test_name: &str) -> PathBuf {
+  let dir = std::env::temp_dir().join(format!(
+    "chinese_bitmap_font_demo-{}-{}",
+    test_name,
+    std::process::id()
+  ));
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(&dir).unwrap();
+  dir
+}
+
+/// Glyphs of `tests/fixtures/test_font.ttf`, a synthetic public-domain pixel font.
+///
+/// 丿 has a negative left side bearing, 口 and 日 are skinny glyphs, 矗 is dense enough for
+/// shadows to hit neighboring strokes.
+pub fn test_font_glyphs() -> Vec<PixelGlyph> {
+  vec![
+    PixelGlyph::new('一', &["", "", "", "", "#########"]),
+    PixelGlyph::new('丿', &["   ###", "  ##", " ##", "##", "#"]).with_left_bearing(-1),
+    PixelGlyph::new(
+      '中',
+      &[
+        "    #",
+        "#########",
+        "#   #   #",
+        "#   #   #",
+        "#########",
+        "    #",
+        "    #",
+        "    #",
+        "    #",
+      ],
+    ),
+    PixelGlyph::new(
+      '人',
+      &[
+        "    #",
+        "    #",
+        "    #",
+        "   # #",
+        "   # #",
+        "  #   #",
+        " #     #",
+        "#       #",
+      ],
+    ),
+    PixelGlyph::new(
+      '口',
+      &[
+        "#######", "#     #", "#     #", "#     #", "#     #", "#######",
+      ],
+    )
+    .with_left_bearing(2),
+    PixelGlyph::new(
+      '大',
+      &[
+        "    #",
+        "    #",
+        "#########",
+        "    #",
+        "   # #",
+        "  #   #",
+        " #     #",
+        "#       #",
+      ],
+    ),
+    PixelGlyph::new(
+      '日',
+      &[
+        "#####", "#   #", "#   #", "#####", "#   #", "#   #", "#####",
+      ],
+    )
+    .with_left_bearing(2),
+    PixelGlyph::new(
+      '目',
+      &[
+        "#######", "#     #", "#######", "#     #", "#######", "#     #", "#######",
+      ],
+    )
+    .with_left_bearing(1),
+    PixelGlyph::new(
+      '矗',
+      &[
+        "#########",
+        " ## # ## ",
+        "#########",
+        "## ### ##",
+        "#########",
+        "# ## ## #",
+        "#########",
+        "## # # ##",
+        "#########",
+      ],
+    ),
+    PixelGlyph::new('，', &["##", "##", " #", "#"]),
+  ]
+}
+
+/// Bytes of the bundled test font, rebuilt from [`test_font_glyphs`].
+pub fn build_test_font() -> Vec<u8> {
+  build_pixel_font(&test_font_glyphs())
+}
@@ -0,0 +1,211 @@
+//! Builds a tiny TrueType pixel font in memory, one square contour per inked pixel.
+
+/// Font units per pixel at the scale used by the renderer (13.5px per 1350 units).
+pub const UNITS_PER_PIXEL: i16 = 100;
+
+/// One glyph: `rows` are drawn top to bottom, `#` marks an inked pixel.
+pub struct PixelGlyph {
+  pub c: char,
+  pub rows: Vec<&'static str>,
+  /// Left side bearing in pixels (may be negative).
+  pub left_bearing: i16,
+  /// Advance width in pixels.
+  pub advance: u16,
+}
+
+impl PixelGlyph {
+  pub fn new(c: char, rows: &[&'static str]) -> Self {
+    PixelGlyph {
+      c,
+      rows: rows.to_vec(),
+      left_bearing: 0,
+      advance: 10,
+    }
+  }
+
+  pub fn with_left_bearing(mut self, left_bearing: i16) -> Self {
+    self.left_bearing = left_bearing;
+    self
+  }
+}
+
+const ASCENT: i16 = 1100;
+const DESCENT: i16 = -250;
+
+fn push_u16(buf: &mut Vec<u8>, v: u16) {
+  buf.extend_from_slice(&v.to_be_bytes());
+}
+
+fn push_i16(buf: &mut Vec<u8>, v: i16) {
+  buf.extend_from_slice(&v.to_be_bytes());
+}
+
+fn push_u32(buf: &mut Vec<u8>, v: u32) {
+  buf.extend_from_slice(&v.to_be_bytes());
+}
+
+/// Encode one glyph as a `glyf` record; returns (data, x_min) or an empty record for blank glyphs.
+fn encode_glyph(glyph: &PixelGlyph) -> (Vec<u8>, i16) {
+  let unit = UNITS_PER_PIXEL;
+  // Square contours in clockwise order, one per inked pixel.
+  let mut contours: Vec<[(i16, i16); 4]> = Vec::new();
+  for (row, line) in glyph.rows.iter().enumerate() {
+    for (col, ch) in line.chars().enumerate() {
+      if ch == '#' {
+        let x0 = (glyph.left_bearing + col as i16) * unit;
+        let y1 = ASCENT - 200 - row as i16 * unit;
+        let (x1, y0) = (x0 + unit, y1 - unit);
+        contours.push([(x0, y0), (x0, y1), (x1, y1), (x1, y0)]);
+      }
+    }
+  }
+  if contours.is_empty() {
+    return (Vec::new(), 0);
+  }
+
+  let points: Vec<(i16, i16)> = contours.iter().flatten().copied().collect();
+  let x_min = points.iter().map(|p| p.0).min().unwrap();
+  let x_max = points.iter().map(|p| p.0).max().unwrap();
+  let y_min = points.iter().map(|p| p.1).min().unwrap();
+  let y_max = points.iter().map(|p| p.1).max().unwrap();
+
+  let mut data = Vec::new();
+  push_i16(&mut data, contours.len() as i16);
+  push_i16(&mut data, x_min);
+  push_i16(&mut data, y_min);
+  push_i16(&mut data, x_max);
+  push_i16(&mut data, y_max);
+  for i in 0..contours.len() {
+    push_u16(&mut data, (i * 4 + 3) as u16);
+  }
+  push_u16(&mut data, 0);
+  data.extend(std::iter::repeat_n(0x01, points.len()));
+  let (mut last_x, mut last_y) = (0, 0);
+  for &(x, _) in &points {
+    push_i16(&mut data, x - last_x);
+    last_x = x;
+  }
+  for &(_, y) in &points {
+    push_i16(&mut data, y - last_y);
+    last_y = y;
+  }
+  if data.len() % 2 == 1 {
+    data.push(0);
+  }
+  (data, x_min)
+}
+
+/// Build a complete TTF containing `.notdef` plus `glyphs`.
+pub fn build_pixel_font(glyphs: &[PixelGlyph]) -> Vec<u8> {
+  let num_glyphs = glyphs.len() as u16 + 1;
+
+  let mut glyf = Vec::new();
+  let mut loca = Vec::new();
+  let mut hmtx = Vec::new();
+  // `.notdef` is empty, so both its start and end offsets are 0.
+  push_u32(&mut loca, 0);
+  push_u32(&mut loca, 0);
+  push_u16(&mut hmtx, 10 * UNITS_PER_PIXEL as u16);
+  push_i16(&mut hmtx, 0);
+  for glyph in glyphs {
+    let (data, x_min) = encode_glyph(glyph);
+    glyf.extend_from_slice(&data);
+    push_u32(&mut loca, glyf.len() as u32);
+    push_u16(&mut hmtx, glyph.advance * UNITS_PER_PIXEL as u16);
+    push_i16(&mut hmtx, x_min);
+  }
+
+  let mut head = Vec::new();
+  push_u32(&mut head, 0x0001_0000);
+  push_u32(&mut head, 0x0001_0000);
+  push_u32(&mut head, 0);
+  push_u32(&mut head, 0x5F0F_3CF5);
+  push_u16(&mut head, 0);
+  push_u16(&mut head, 1000);
+  head.extend_from_slice(&[0; 16]);
+  push_i16(&mut head, -1000);
+  push_i16(&mut head, DESCENT);
+  push_i16(&mut head, 2000);
+  push_i16(&mut head, ASCENT);
+  push_u16(&mut head, 0);
+  push_u16(&mut head, 8);
+  push_i16(&mut head, 2);
+  push_i16(&mut head, 1);
+  push_i16(&mut head, 0);
+
+  let mut hhea = Vec::new();
+  push_u32(&mut hhea, 0x0001_0000);
+  push_i16(&mut hhea, ASCENT);
+  push_i16(&mut hhea, DESCENT);
+  push_i16(&mut hhea, 0);
+  push_u16(&mut hhea, 2000);
+  push_i16(&mut hhea, -1000);
+  push_i16(&mut hhea, -1000);
+  push_i16(&mut hhea, 2000);
+  push_i16(&mut hhea, 1);
+  push_i16(&mut hhea, 0);
+  push_i16(&mut hhea, 0);
+  hhea.extend_from_slice(&[0; 8]);
+  push_i16(&mut hhea, 0);
+  push_u16(&mut hhea, num_glyphs);
+
+  let mut maxp = Vec::new();
+  push_u32(&mut maxp, 0x0001_0000);
+  push_u16(&mut maxp, num_glyphs);
+  push_u16(&mut maxp, 400);
+  push_u16(&mut maxp, 100);
+  maxp.extend_from_slice(&[0; 22]);
+
+  let mut cmap = Vec::new();
+  push_u16(&mut cmap, 0);
+  push_u16(&mut cmap, 1);
+  push_u16(&mut cmap, 3);
+  push_u16(&mut cmap, 10);
+  push_u32(&mut cmap, 12);
+  let mut mapping: Vec<(u32, u32)> = glyphs
+    .iter()
+    .enumerate()
+    .map(|(i, g)| (g.c as u32, i as u32 + 1))
+    .collect();
+  mapping.sort_unstable();
+  push_u16(&mut cmap, 12);
+  push_u16(&mut cmap, 0);
+  push_u32(&mut cmap, 16 + 12 * mapping.len() as u32);
+  push_u32(&mut cmap, 0);
+  push_u32(&mut cmap, mapping.len() as u32);
+  for (code, gid) in mapping {
+    push_u32(&mut cmap, code);
+    push_u32(&mut cmap, code);
+    push_u32(&mut cmap, gid);
+  }
+
+  let tables: [(&[u8; 4], Vec<u8>); 7] = [
+    (b"cmap", cmap),
+    (b"glyf", glyf),
+    (b"head", head),
+    (b"hhea", hhea),
+    (b"hmtx", hmtx),
+    (b"loca", loca),
+    (b"maxp", maxp),
+  ];
+
+  let mut font = Vec::new();
+  push_u32(&mut font, 0x0001_0000);
+  push_u16(&mut font, tables.len() as u16);
+  push_u16(&mut font, 64);
+  push_u16(&mut font, 2);
+  push_u16(&mut font, 48);
+  let mut offset = 12 + 16 * tables.len() as u32;
+  for (tag, data) in &tables {
+    font.extend_from_slice(*tag);
+    push_u32(&mut font, 0);
+    push_u32(&mut font, offset);
+    push_u32(&mut font, data.len() as u32);
+    offset += (data.len() as u32).div_ceil(4) * 4;
+  }
+  for (_, data) in &tables {
+    font.extend_from_slice(data);
+    font.resize(font.len().div_ceil(4) * 4, 0);
+  }
+  font
+}
@@ -0,0 +1,244 @@
+//! Golden-image tests rendering the bundled test font through the full atlas pipeline.
+//!
+//! Run with `CREATE_GOLDEN=1` to (re)create the golden images after an intended change, and with
+//! `CREATE_TEST_FONT=1` to rebuild `tests/fixtures/test_font.ttf` from `tests/common`.
+
+mod common;
+
+use std::{env, fs};
+
+use ab_glyph::FontRef;
+use chinese_bitmap_font_demo::{
+  atlas::{ColorClassTiles, glyph_scale},
+  builder::AtlasBuilder,
+  measure_font,
+  render::CellColors,
+  script::get_unique_chinese_chars,
+  warning::{RenderContext, RenderWarning},
+};
+use image::{Rgb, RgbImage};
+
+fn render_test_chars(font_size: u32) -> RgbImage {
+  let font_file = fs::read(common::fixture_path("test_font.ttf")).unwrap();
+  let chars = get_unique_chinese_chars(common::TEST_CHARS);
+  assert_eq!(chars.len(), 10);
+
+  let generator = AtlasBuilder::new()
+    .font_bytes(font_file)
+    .size(font_size)
+    .chars_per_line(5)
+    .build()
+    .unwrap();
+  generator.generate(&chars).unwrap().image
+}
+
+fn assert_matches_golden(image: &RgbImage, name: &str) {
+  let path = common::fixture_path(name);
+  if env::var_os("CREATE_GOLDEN").is_some() {
+    image.save(&path).unwrap();
+    return;
+  }
+  assert!(
+    path.exists(),
+    "{} is missing, run the tests with CREATE_GOLDEN=1 to create it",
+    path.display()
+  );
+
+  let golden = image::open(&path).unwrap().to_rgb8();
+  assert_eq!(
+    image.dimensions(),
+    golden.dimensions(),
+    "{name}: size differs"
+  );
+  for (x, y, pixel) in image.enumerate_pixels() {
+    assert_eq!(
+      pixel,
+      golden.get_pixel(x, y),
+      "{name}: pixel ({x}, {y}) differs from the golden image"
+    );
+  }
+}
+
+#[test]
+fn bundled_font_is_up_to_date() {
+  let path = common::fixture_path("test_font.ttf");
+  let font = common::build_test_font();
+  if env::var_os("CREATE_TEST_FONT").is_some() {
+    fs::write(&path, &font).unwrap();
+  }
+  assert_eq!(fs::read(&path).unwrap(), font);
+}
+
+#[test]
+fn golden_10px_drop_shadow() {
+  assert_matches_golden(&render_test_chars(10), "golden_10px.png");
+}
+
+#[test]
+fn golden_11px_outline() {
+  assert_matches_golden(&render_test_chars(11), "golden_11px.png");
+}
+
+#[test]
+fn color_class_tiles_follow_in_class_color() {
+  let font_file = fs::read(common::fixture_path("test_font.ttf")).unwrap();
+  let chars = get_unique_chinese_chars(common::TEST_CHARS);
+  let class_color = Rgb([255, 200, 0]);
+  let classes = [ColorClassTiles {
+    name: "names".to_string(),
+    colors: CellColors {
+      char_color: class_color,
+      shadow_color: Rgb([1, 1, 1]),
+      gradient_bottom: None,
+    },
+    bold: false,
+    slant: 0,
+    chars: vec![chars[0]],
+  }];
+  let generator = AtlasBuilder::new()
+    .font_bytes(font_file)
+    .chars_per_line(5)
+    .build()
+    .unwrap();
+  let atlas = generator.generate_with_classes(&chars, &classes).unwrap();
+  assert_eq!(atlas.tile_count, chars.len() + 1);
+
+  let tile = atlas.placements.last().unwrap();
+  assert_eq!(
+    (tile.char, tile.class.as_deref()),
+    (chars[0], Some("names"))
+  );
+  let cell = image::imageops::crop_imm(&atlas.image, tile.cell_x, tile.cell_y, 10, 10).to_image();
+  assert!(cell.pixels().any(|pixel| *pixel == class_color));
+  let first = image::imageops::crop_imm(&atlas.image, 0, 0, 10, 10).to_image();
+  assert!(!first.pixels().any(|pixel| *pixel == class_color));
+}
+
+#[test]
+fn char_threshold_override_changes_the_tile() {
+  let font_file = fs::read(common::fixture_path("test_font.ttf")).unwrap();
+  let chars = get_unique_chinese_chars(common::TEST_CHARS);
+  let builder = AtlasBuilder::new().font_bytes(font_file).chars_per_line(5);
+  let plain = builder.clone().build().unwrap().generate(&chars).unwrap();
+  let overridden = builder
+    .char_threshold(chars[0], 1.0)
+    .build()
+    .unwrap()
+    .generate(&chars)
+    .unwrap();
+
+  assert_eq!(overridden.threshold_overrides, vec![chars[0]]);
+  assert!(plain.placements[0].glyph_bbox.is_some());
+  assert!(overridden.placements[0].glyph_bbox.is_none());
+  assert_eq!(
+    plain.placements[1].glyph_bbox,
+    overridden.placements[1].glyph_bbox
+  );
+}
+
+#[test]
+fn missing_glyphs_are_collected_as_warnings() {
+  let font_file = fs::read(common::fixture_path("test_font.ttf")).unwrap();
+  let generator = AtlasBuilder::new().font_bytes(font_file).build().unwrap();
+  let mut context = RenderContext::default();
+  generator
+    .generate_with_context(&['中', '龘'], &[], &mut context)
+    .unwrap();
+  assert_eq!(
+    context.warnings,
+    [RenderWarning::GlyphNotFound {
+      char: '龘',
+      codepoint: 0x9F98,
+      index: 1
+    }]
+  );
+}
+
+#[test]
+fn wide_shadows_past_the_cell_edge_are_reported() {
+  let font_file = fs::read(common::fixture_path("test_font.ttf")).unwrap();
+  let warnings = |shadow_width: u32| {
+    let mut context = RenderContext::default();
+    let atlas = AtlasBuilder::new()
+      .font_bytes(font_file.clone())
+      .shadow_width(shadow_width)
+      .build()
+      .unwrap()
+      .generate_with_context(&['中'], &[], &mut context)
+      .unwrap();
+    assert_eq!(atlas.placements[0].clipped, shadow_width > 1);
+    context.warnings
+  };
+  assert_eq!(warnings(1), []);
+  assert_eq!(
+    warnings(2),
+    [RenderWarning::ShadowClipped {
+      char: '中',
+      codepoint: 0x4E2D
+    }]
+  );
+}
+
+#[test]
+fn missing_glyphs_borrow_the_fallback_glyph() {
+  let font_file = fs::read(common::fixture_path("test_font.ttf")).unwrap();
+  let atlas = AtlasBuilder::new()
+    .font_bytes(font_file)
+    .missing_fallback('口')
+    .missing_fallback_for('龘', '中')
+    .build()
+    .unwrap()
+    .generate(&['中', '龍', '龘'])
+    .unwrap();
+  let tiles = atlas
+    .placements
+    .iter()
+    .map(|placement| {
+      (
+        placement.missing,
+        placement.substituted,
+        placement.font_index,
+      )
+    })
+    .collect::<Vec<_>>();
+  assert_eq!(
+    tiles,
+    [
+      (false, false, Some(0)),
+      (true, true, Some(0)),
+      (true, true, Some(0))
+    ]
+  );
+  let cell = |index: u32| image::imageops::crop_imm(&atlas.image, index * 10, 0, 10, 10).to_image();
+  assert_eq!(cell(2), cell(0));
+  assert_ne!(cell(1), cell(0));
+}
+
+#[test]
+fn negative_side_bearing_is_clamped_to_the_cell_edge() {
+  let font_file = fs::read(common::fixture_path("test_font.ttf")).unwrap();
+  let atlas = AtlasBuilder::new()
+    .font_bytes(font_file)
+    .build()
+    .unwrap()
+    .generate(&['一', '丿'])
+    .unwrap();
+  assert_eq!(atlas.clamped_chars, ['丿']);
+  let tile = &atlas.placements[1];
+  assert!(tile.clipped);
+  assert_eq!(tile.glyph_bbox.map(|bbox| bbox.x), Some(0));
+  assert!(!atlas.placements[0].clipped);
+}
+
+#[test]
+fn font_metrics_measure_the_sample_char() {
+  let font_file = fs::read(common::fixture_path("test_font.ttf")).unwrap();
+  let font = FontRef::try_from_slice(&font_file).unwrap();
+  let metrics = measure_font(&font, glyph_scale(9.0));
+  assert_eq!(metrics.units_per_em, 1000);
+  assert_eq!((metrics.ascender, metrics.descender), (11.0, -2.5));
+  assert_eq!(
+    (metrics.cjk_char_width, metrics.cjk_char_height),
+    (9.0, 9.0)
+  );
+}